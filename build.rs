@@ -0,0 +1,17 @@
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(pico_board, values(any()))");
+    if let Ok(board) = std::env::var("DEP_PICO_SDK_BOARD") {
+        println!("cargo:rustc-cfg=pico_board=\"{}\"", board);
+    }
+    println!("cargo:rustc-check-cfg=cfg(pico_platform, values(\"rp2040\", \"rp2350\"))");
+    if let Ok(platform) = std::env::var("DEP_PICO_SDK_PLATFORM") {
+        println!("cargo:rustc-cfg=pico_platform=\"{}\"", platform);
+    }
+    // Whether the SDK was built with what the `sleep` and `async-context` features need.
+    for (metadata, cfg) in [("DEP_PICO_SDK_SLEEP", "pico_sdk_sleep"), ("DEP_PICO_SDK_ASYNC_CONTEXT", "pico_sdk_async_context")] {
+        println!("cargo:rustc-check-cfg=cfg({})", cfg);
+        if std::env::var_os(metadata).is_some() {
+            println!("cargo:rustc-cfg={}", cfg);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use raspi_pico_sdk_sys as sys;
+
+/// Number of ADC inputs: the GPIO-backed channels plus the temperature sensor.
+pub const NUM_CHANNELS: u32 = sys::NUM_ADC_CHANNELS;
+
+/// GPIO of ADC channel 0; the GPIO-backed channels are on consecutive GPIOs from here (26 on
+/// the RP2040 and RP2350A, 40 on the RP2350B).
+pub const FIRST_GPIO: u32 = sys::ADC_BASE_PIN;
+/// Number of ADC channels backed by a GPIO.
+pub const NUM_GPIO_CHANNELS: u32 = NUM_CHANNELS - 1;
+/// Channel of the internal temperature sensor, the one after the GPIO-backed channels.
+pub const TEMPERATURE_SENSOR_CHANNEL: u32 = NUM_GPIO_CHANNELS;
+
+/// An ADC channel whose GPIO has been set up for analog input by [`Adc::init_pin`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdcChannel {
+    channel: u32,
+}
+
+impl AdcChannel {
+    pub fn number(&self) -> u32 {
+        self.channel
+    }
+}
+
+impl From<AdcChannel> for u32 {
+    fn from(channel: AdcChannel) -> u32 {
+        channel.channel
+    }
+}
+
+/// A channel fixed at compile time, for code that needs the channel in the type (such as the
+/// `embedded-hal` 0.2 `OneShot` trait). Created by [`Adc::input`].
+#[derive(Debug)]
+pub struct AdcInput<const CHANNEL: u8> {
+    _private: (),
+}
+
+/// The internal temperature sensor as an [`AdcInput`].
+pub type TemperatureSensor = AdcInput<{ TEMPERATURE_SENSOR_CHANNEL as u8 }>;
+
+impl<const CHANNEL: u8> From<AdcInput<CHANNEL>> for u32 {
+    fn from(_input: AdcInput<CHANNEL>) -> u32 {
+        CHANNEL as u32
+    }
+}
+
+impl<const CHANNEL: u8> From<&AdcInput<CHANNEL>> for u32 {
+    fn from(_input: &AdcInput<CHANNEL>) -> u32 {
+        CHANNEL as u32
+    }
+}
+
+pub struct Adc {
+    _private: (),
+}
+
+impl Adc {
+    pub fn init() -> Adc {
+        unsafe { sys::wrapped_adc_init() };
+        Adc { _private: () }
+    }
+
+    /// Prepares `gpio` for analog input (no digital function, pulls and output driver off)
+    /// and returns its channel. Panics if `gpio` is not one of the ADC pins.
+    pub fn init_pin(&mut self, gpio: u32) -> AdcChannel {
+        let channel = gpio_to_channel(gpio).unwrap_or_else(|| {
+            panic!("GPIO {} is not an ADC input; only GPIO {} to {} are", gpio, FIRST_GPIO, FIRST_GPIO + NUM_GPIO_CHANNELS - 1)
+        });
+        unsafe { sys::wrapped_adc_gpio_init(gpio) };
+        AdcChannel { channel }
+    }
+
+    /// Powers up the temperature sensor and returns its channel.
+    pub fn enable_temp_sensor(&mut self) -> AdcChannel {
+        unsafe { sys::wrapped_adc_set_temp_sensor_enabled(true) };
+        AdcChannel { channel: TEMPERATURE_SENSOR_CHANNEL }
+    }
+
+    /// Prepares channel `CHANNEL` like [`Adc::init_pin`] does for its GPIO, or powers up the
+    /// temperature sensor for [`TEMPERATURE_SENSOR_CHANNEL`]. Panics on any other channel.
+    pub fn input<const CHANNEL: u8>(&mut self) -> AdcInput<CHANNEL> {
+        let channel = CHANNEL as u32;
+        if channel < NUM_GPIO_CHANNELS {
+            self.init_pin(FIRST_GPIO + channel);
+        } else if channel == TEMPERATURE_SENSOR_CHANNEL {
+            self.enable_temp_sensor();
+        } else {
+            panic!("invalid ADC channel {}", channel);
+        }
+        AdcInput { _private: () }
+    }
+
+    pub fn read(&mut self, channel: impl Into<u32>) -> u16 {
+        self.select(channel.into());
+        unsafe { sys::wrapped_adc_read() }
+    }
+
+    /// Reads `channel` `samples` times and returns the rounded mean.
+    pub fn read_averaged(&mut self, channel: impl Into<u32>, samples: u32) -> u16 {
+        assert!(samples > 0, "at least one sample is required");
+        self.select(channel.into());
+        // 12-bit samples summed into a u64 cannot overflow for any u32 sample count.
+        let sum = (0..samples)
+            .map(|_| unsafe { sys::wrapped_adc_read() } as u64)
+            .sum::<u64>();
+        mean(sum, samples)
+    }
+
+    /// Reads `channel` `N` times and returns the median, which ignores occasional outliers.
+    pub fn read_median<const N: usize>(&mut self, channel: impl Into<u32>) -> u16 {
+        assert!(N > 0, "at least one sample is required");
+        self.select(channel.into());
+        let mut samples = [0u16; N];
+        for sample in samples.iter_mut() {
+            *sample = unsafe { sys::wrapped_adc_read() };
+        }
+        median(&mut samples)
+    }
+
+    fn select(&mut self, channel: u32) {
+        assert!(channel < NUM_CHANNELS, "invalid ADC channel {}", channel);
+        unsafe { sys::wrapped_adc_select_input(channel) }
+    }
+}
+
+#[cfg(feature = "embedded-hal-02")]
+mod hal_02 {
+    use embedded_hal_02::adc::{Channel, OneShot};
+
+    use super::{Adc, AdcInput};
+
+    impl<const CHANNEL: u8> Channel<Adc> for AdcInput<CHANNEL> {
+        type ID = u8;
+
+        fn channel() -> u8 {
+            CHANNEL
+        }
+    }
+
+    /// Starts and finishes a conversion within `read`, so it never returns `WouldBlock`.
+    impl<const CHANNEL: u8> OneShot<Adc, u16, AdcInput<CHANNEL>> for Adc {
+        type Error = core::convert::Infallible;
+
+        fn read(&mut self, input: &mut AdcInput<CHANNEL>) -> nb::Result<u16, Self::Error> {
+            Ok(Adc::read(self, &*input))
+        }
+    }
+}
+
+fn gpio_to_channel(gpio: u32) -> Option<u32> {
+    let channel = gpio.checked_sub(FIRST_GPIO)?;
+    if channel < NUM_GPIO_CHANNELS {
+        Some(channel)
+    } else {
+        None
+    }
+}
+
+fn mean(sum: u64, samples: u32) -> u16 {
+    let samples = samples as u64;
+    ((sum + samples / 2) / samples) as u16
+}
+
+fn median(samples: &mut [u16]) -> u16 {
+    samples.sort_unstable();
+    let middle = samples.len() / 2;
+    if samples.len() % 2 == 1 {
+        samples[middle]
+    } else {
+        ((samples[middle - 1] as u32 + samples[middle] as u32) / 2) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    fn mean_is_rounded_to_the_nearest_count() {
+        assert_eq!(mean(10, 4), 3);
+        assert_eq!(mean(9, 4), 2);
+        assert_eq!(mean(4095 * 3, 3), 4095);
+    }
+
+    #[test]
+    fn median_ignores_outliers_and_averages_the_middle_pair() {
+        assert_eq!(median(&mut [4095, 100, 102, 0, 101]), 101);
+        assert_eq!(median(&mut [100, 4095, 0, 103]), 101);
+        assert_eq!(median(&mut [7]), 7);
+    }
+
+    #[test]
+    fn every_sample_is_read_after_selecting_the_channel() {
+        let calls = record_calls(|| {
+            let mut adc = Adc { _private: () };
+            adc.read_averaged(1u32, 3);
+            adc.read_median::<2>(1u32);
+        });
+        assert_eq!(
+            calls,
+            [
+                "wrapped_adc_select_input",
+                "wrapped_adc_read",
+                "wrapped_adc_read",
+                "wrapped_adc_read",
+                "wrapped_adc_select_input",
+                "wrapped_adc_read",
+                "wrapped_adc_read",
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_adc_pins_map_to_channels() {
+        assert_eq!(gpio_to_channel(FIRST_GPIO - 1), None);
+        assert_eq!(gpio_to_channel(FIRST_GPIO), Some(0));
+        assert_eq!(gpio_to_channel(FIRST_GPIO + NUM_GPIO_CHANNELS - 1), Some(NUM_GPIO_CHANNELS - 1));
+        assert_eq!(gpio_to_channel(FIRST_GPIO + NUM_GPIO_CHANNELS), None);
+        assert_eq!(gpio_to_channel(0), None);
+    }
+
+    #[test]
+    fn pins_are_prepared_for_analog_input() {
+        let mut adc = Adc { _private: () };
+        let calls = record_calls(|| {
+            assert_eq!(adc.init_pin(FIRST_GPIO + 2).number(), 2);
+            assert_eq!(adc.enable_temp_sensor().number(), TEMPERATURE_SENSOR_CHANNEL);
+        });
+        assert_eq!(calls, ["wrapped_adc_gpio_init", "wrapped_adc_set_temp_sensor_enabled"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "GPIO 25 is not an ADC input; only GPIO")]
+    fn other_pins_are_rejected() {
+        Adc { _private: () }.init_pin(25);
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-hal-02")]
+    fn one_shot_reads_select_the_channel_of_the_input() {
+        use embedded_hal_02::adc::{Channel, OneShot};
+
+        let mut adc = Adc { _private: () };
+        let calls = record_calls(|| {
+            let mut sensor: TemperatureSensor = adc.input();
+            assert_eq!(OneShot::read(&mut adc, &mut sensor), Ok(0));
+            let mut input = adc.input::<3>();
+            assert_eq!(OneShot::read(&mut adc, &mut input), Ok(0));
+        });
+        assert_eq!(TemperatureSensor::channel() as u32, TEMPERATURE_SENSOR_CHANNEL);
+        assert_eq!(
+            calls,
+            [
+                "wrapped_adc_set_temp_sensor_enabled",
+                "wrapped_adc_select_input",
+                "wrapped_adc_read",
+                "wrapped_adc_gpio_init",
+                "wrapped_adc_select_input",
+                "wrapped_adc_read",
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid ADC channel 9")]
+    fn inputs_past_the_temperature_sensor_are_rejected() {
+        // Past the sensor of the RP2350B too, channel 8.
+        Adc { _private: () }.input::<9>();
+    }
+}
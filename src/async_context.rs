@@ -0,0 +1,128 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+
+use raspi_pico_sdk_sys as sys;
+
+use crate::irq;
+use crate::time::Instant;
+
+/// A handle to an SDK `async_context_t`, the cooperative event loop that runs the workers of
+/// drivers such as lwIP and cyw43.
+#[derive(Clone, Copy)]
+pub struct AsyncContext {
+    context: *mut sys::async_context_t,
+}
+
+impl AsyncContext {
+    /// Wraps a context initialized elsewhere, e.g. `cyw43_arch_async_context()`.
+    ///
+    /// # Safety
+    ///
+    /// `context` has to be initialized and stay valid, and in place, for as long as the handle
+    /// is used.
+    pub unsafe fn from_raw(context: *mut sys::async_context_t) -> AsyncContext {
+        AsyncContext { context }
+    }
+
+    pub fn raw(&self) -> *mut sys::async_context_t {
+        self.context
+    }
+
+    /// Runs the workers that have work pending. A polling context only does work here;
+    /// background contexts do it on their own, and this does nothing.
+    pub fn poll(&self) {
+        unsafe { sys::wrapped_async_context_poll(self.context) }
+    }
+
+    /// Sleeps until there may be work to do or `ms` have passed. Follow it with [`poll`].
+    ///
+    /// [`poll`]: AsyncContext::poll
+    pub fn wait_for_work_ms(&self, ms: u32) {
+        unsafe { sys::wrapped_async_context_wait_for_work_ms(self.context, ms) }
+    }
+
+    /// Sleeps until there may be work to do or `deadline` is reached.
+    pub fn wait_for_work_until(&self, deadline: Instant) {
+        unsafe { sys::wrapped_async_context_wait_for_work_until(self.context, deadline.to_absolute_time()) }
+    }
+
+    /// Runs `f` holding the context's lock, which drivers on the context require around their
+    /// calls from outside its workers. The lock is recursive.
+    pub fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        unsafe { sys::wrapped_async_context_acquire_lock_blocking(self.context) };
+        let result = f();
+        unsafe { sys::wrapped_async_context_release_lock(self.context) };
+        result
+    }
+}
+
+/// Storage of a polling context (`async_context_poll_t`), whose workers run only from
+/// [`AsyncContext::poll`]. Drivers keep pointers to the context, so it lives in a static:
+///
+/// `static CONTEXT: PollContext = PollContext::new();`
+pub struct PollContext {
+    context: UnsafeCell<MaybeUninit<sys::async_context_poll_t>>,
+    initialized: UnsafeCell<bool>,
+}
+
+// The context is only handed out once, and the SDK serializes access to it with its lock.
+unsafe impl Sync for PollContext {}
+
+impl PollContext {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> PollContext {
+        PollContext { context: UnsafeCell::new(MaybeUninit::uninit()), initialized: UnsafeCell::new(false) }
+    }
+
+    /// Initializes the context, tied to the calling core, with the SDK's defaults. Returns
+    /// `None` if it was initialized before or the SDK fails to.
+    pub fn init(&'static self) -> Option<AsyncContext> {
+        let first = irq::interrupt_free(|| unsafe { !core::mem::replace(&mut *self.initialized.get(), true) });
+        if !first {
+            return None;
+        }
+        let context = self.context.get() as *mut sys::async_context_poll_t;
+        if !unsafe { sys::wrapped_async_context_poll_init_with_defaults(context) } {
+            return None;
+        }
+        // `core` is the first field, so the SDK casts between the two the same way.
+        Some(AsyncContext { context: unsafe { core::ptr::addr_of_mut!((*context).core) } })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    fn a_context_is_initialized_at_most_once() {
+        static CONTEXT: PollContext = PollContext::new();
+        // The stubs fail the initialization, which still uses up the storage.
+        let calls = record_calls(|| {
+            assert!(CONTEXT.init().is_none());
+            assert!(CONTEXT.init().is_none());
+        });
+        assert_eq!(
+            calls,
+            [
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_restore_interrupts",
+                "wrapped_async_context_poll_init_with_defaults",
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_restore_interrupts",
+            ]
+        );
+    }
+
+    #[test]
+    fn work_under_the_lock_runs_between_acquiring_and_releasing_it() {
+        let mut storage = MaybeUninit::<sys::async_context_poll_t>::zeroed();
+        let context = unsafe { AsyncContext::from_raw(storage.as_mut_ptr() as *mut sys::async_context_t) };
+        let calls = record_calls(|| assert_eq!(context.with_lock(|| { context.poll(); 7 }), 7));
+        assert_eq!(
+            calls,
+            ["wrapped_async_context_acquire_lock_blocking", "wrapped_async_context_poll", "wrapped_async_context_release_lock"]
+        );
+    }
+}
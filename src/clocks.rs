@@ -0,0 +1,69 @@
+use raspi_pico_sdk_sys as sys;
+
+/// Index of `clk_sys` in `enum clock_num_rp2040`.
+const CLK_SYS: u32 = 5;
+
+/// Current frequency of the system clock, which also drives the PIO blocks.
+pub fn sys_clock_hz() -> u32 {
+    unsafe { sys::wrapped_clock_get_hz(CLK_SYS as _) }
+}
+
+/// Inputs of the frequency counter, valued as `CLOCKS_FC0_SRC_VALUE_*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum FrequencySource {
+    PllSysPrimary = 0x01,
+    PllUsbPrimary = 0x02,
+    RoscPrimary = 0x03,
+    RoscPrimaryPhaseShifted = 0x04,
+    XoscPrimary = 0x05,
+    Gpin0 = 0x06,
+    Gpin1 = 0x07,
+    ClkRef = 0x08,
+    ClkSys = 0x09,
+    ClkPeri = 0x0a,
+    ClkUsb = 0x0b,
+    ClkAdc = 0x0c,
+    #[cfg(pico_platform = "rp2040")]
+    ClkRtc = 0x0d,
+    #[cfg(pico_platform = "rp2350")]
+    ClkHstx = 0x0d,
+    #[cfg(pico_platform = "rp2350")]
+    LposcPrimary = 0x0e,
+    #[cfg(pico_platform = "rp2350")]
+    OtpClk2fc = 0x0f,
+}
+
+/// Measures `src` with the built-in frequency counter.
+pub fn frequency_count_khz(src: FrequencySource) -> u32 {
+    unsafe { sys::wrapped_frequency_count_khz(src as u32) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    fn sources_are_valued_as_the_counter_source_field() {
+        // `CLOCKS_FC0_SRC_VALUE_PLL_SYS_CLKSRC_PRIMARY` and `_CLK_SYS` of both chips.
+        assert_eq!(FrequencySource::PllSysPrimary as u32, 0x01);
+        assert_eq!(FrequencySource::ClkSys as u32, 0x09);
+        // `_CLK_RTC` of the RP2040.
+        #[cfg(pico_platform = "rp2040")]
+        assert_eq!(FrequencySource::ClkRtc as u32, 0x0d);
+        // `_CLK_HSTX`, `_LPOSC_CLKSRC` and `_OTP_CLK2FC` of the RP2350.
+        #[cfg(pico_platform = "rp2350")]
+        {
+            assert_eq!(FrequencySource::ClkHstx as u32, 0x0d);
+            assert_eq!(FrequencySource::LposcPrimary as u32, 0x0e);
+            assert_eq!(FrequencySource::OtpClk2fc as u32, 0x0f);
+        }
+    }
+
+    #[test]
+    fn the_count_is_taken_from_the_sdk() {
+        let calls = record_calls(|| assert_eq!(frequency_count_khz(FrequencySource::XoscPrimary), 0));
+        assert_eq!(calls, ["wrapped_frequency_count_khz"]);
+    }
+}
@@ -0,0 +1,76 @@
+use raspi_pico_sdk_sys as sys;
+
+/// Busy-waiting delays on the system timer.
+pub struct Delay {
+    _private: (),
+}
+
+impl Delay {
+    pub fn init() -> Delay {
+        Delay { _private: () }
+    }
+
+    pub fn delay_us(&mut self, us: u32) {
+        unsafe { sys::wrapped_busy_wait_us_32(us) }
+    }
+
+    pub fn delay_ms(&mut self, ms: u32) {
+        unsafe { sys::wrapped_busy_wait_ms(ms) }
+    }
+
+    /// The timer counts microseconds, so this waits at least `ns` rounded up to the next
+    /// microsecond.
+    pub fn delay_ns(&mut self, ns: u32) {
+        self.delay_us(ns_to_us(ns));
+    }
+}
+
+fn ns_to_us(ns: u32) -> u32 {
+    ns.div_ceil(1000)
+}
+
+#[cfg(feature = "embedded-hal")]
+mod hal {
+    use embedded_hal::delay::DelayNs;
+
+    use super::Delay;
+
+    impl DelayNs for Delay {
+        fn delay_ns(&mut self, ns: u32) {
+            Delay::delay_ns(self, ns)
+        }
+
+        fn delay_us(&mut self, us: u32) {
+            Delay::delay_us(self, us)
+        }
+
+        fn delay_ms(&mut self, ms: u32) {
+            Delay::delay_ms(self, ms)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    fn nanoseconds_round_up_to_the_next_microsecond() {
+        assert_eq!(ns_to_us(0), 0);
+        assert_eq!(ns_to_us(1), 1);
+        assert_eq!(ns_to_us(1000), 1);
+        assert_eq!(ns_to_us(1001), 2);
+        assert_eq!(ns_to_us(u32::MAX), 4_294_968);
+    }
+
+    #[test]
+    fn delays_busy_wait_on_the_timer() {
+        let calls = record_calls(|| {
+            let mut delay = Delay::init();
+            delay.delay_ns(500);
+            delay.delay_ms(2);
+        });
+        assert_eq!(calls, ["wrapped_busy_wait_us_32", "wrapped_busy_wait_ms"]);
+    }
+}
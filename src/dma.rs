@@ -0,0 +1,745 @@
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::mem::size_of;
+use core::ptr::{addr_of, addr_of_mut};
+
+use raspi_pico_sdk_sys as sys;
+
+use crate::{clocks, irq};
+
+/// 12 on the RP2040, 16 on the RP2350.
+pub const NUM_CHANNELS: usize = sys::NUM_DMA_CHANNELS as usize;
+
+/// A claimed DMA channel, released again when dropped.
+pub struct DmaChannel {
+    channel: u32,
+}
+
+impl DmaChannel {
+    /// Claims a channel nobody else is using, or returns `None` if all of them are taken.
+    pub fn claim_unused() -> Option<DmaChannel> {
+        let channel = unsafe { sys::wrapped_dma_claim_unused_channel(false) };
+        if channel < 0 {
+            None
+        } else {
+            Some(DmaChannel::register(channel as u32))
+        }
+    }
+
+    /// Claims `channel`, or returns `None` if it is already claimed, through this wrapper or
+    /// the raw `dma_channel_claim`. If the other core claims the same channel at the same time,
+    /// the SDK panics.
+    pub fn claim(channel: u32) -> Option<DmaChannel> {
+        if is_claimed(channel) {
+            return None;
+        }
+        unsafe { sys::wrapped_dma_channel_claim(channel) };
+        Some(DmaChannel::register(channel))
+    }
+
+    /// Records a channel the SDK just handed out in [`OWNED_CHANNELS`]. The SDK never hands out
+    /// a claimed channel, so finding it there means someone unclaimed it behind the owner's back.
+    fn register(channel: u32) -> DmaChannel {
+        let bit = 1 << channel;
+        with_claim_lock(|| unsafe {
+            debug_assert!(OWNED_CHANNELS & bit == 0, "DMA channel {} was unclaimed while a DmaChannel owned it", channel);
+            OWNED_CHANNELS |= bit;
+        });
+        DmaChannel { channel }
+    }
+
+    pub fn number(&self) -> u32 {
+        self.channel
+    }
+
+    /// Returns the SDK's default configuration for this channel.
+    pub fn default_config(&self) -> ChannelConfig {
+        ChannelConfig {
+            channel: self.channel,
+            ring: None,
+            config: unsafe { sys::wrapped_dma_channel_get_default_config(self.channel) },
+        }
+    }
+
+    /// Configures the channel and optionally starts the transfer.
+    ///
+    /// Panics if a ring is configured and the wrapped address is not aligned to the ring size.
+    ///
+    /// # Safety
+    /// `write_addr` and `read_addr` must stay valid for the whole transfer.
+    pub unsafe fn configure(&self, config: &ChannelConfig, write_addr: *mut c_void, read_addr: *const c_void, transfer_count: u32, trigger: bool) {
+        if let Some(ring) = config.ring {
+            let addr = if ring.write { write_addr as usize } else { read_addr as usize };
+            assert!(
+                addr % ring.size_bytes() == 0,
+                "DMA ring buffer at {:#x} is not aligned to its {} byte size",
+                addr,
+                ring.size_bytes()
+            );
+        }
+        sys::wrapped_dma_channel_configure(self.channel, config.raw(), write_addr, read_addr, transfer_count, trigger);
+    }
+
+    pub fn start(&self) {
+        unsafe { sys::wrapped_dma_channel_start(self.channel) }
+    }
+
+    pub fn abort(&self) {
+        unsafe { sys::wrapped_dma_channel_abort(self.channel) }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        unsafe { sys::wrapped_dma_channel_is_busy(self.channel) }
+    }
+
+    pub fn wait_for_finish_blocking(&self) {
+        unsafe { sys::wrapped_dma_channel_wait_for_finish_blocking(self.channel) }
+    }
+
+    /// Starts copying `src` into `dst`, which must have the same length. The transfer size
+    /// follows the element size and both addresses are incremented.
+    ///
+    /// # Safety
+    /// The returned [`Transfer`] must not be leaked (e.g. with `mem::forget`): only its `Drop`
+    /// stops the DMA from accessing the buffers once the borrows end.
+    pub unsafe fn start_copy<'a, T: Copy>(&'a self, config: &ChannelConfig, dst: &'a mut [T], src: &'a [T]) -> Transfer<'a> {
+        assert_eq!(dst.len(), src.len(), "DMA copy buffers differ in length");
+        let config = config.transfer_data_size::<T>().read_increment(true).write_increment(true);
+        self.configure(&config, dst.as_mut_ptr() as *mut c_void, src.as_ptr() as *const c_void, dst.len() as u32, true);
+        Transfer::new(self)
+    }
+
+    /// Starts filling `dst` from the fixed address `read_addr`, typically a peripheral FIFO.
+    ///
+    /// # Safety
+    /// `read_addr` must stay valid for the transfer, and the returned [`Transfer`] must not be
+    /// leaked, as for [`DmaChannel::start_copy`].
+    pub unsafe fn start_read_into<'a, T>(&'a self, config: &ChannelConfig, dst: &'a mut [T], read_addr: *const c_void) -> Transfer<'a> {
+        let config = config.transfer_data_size::<T>().read_increment(false).write_increment(true);
+        self.configure(&config, dst.as_mut_ptr() as *mut c_void, read_addr, dst.len() as u32, true);
+        Transfer::new(self)
+    }
+
+    /// Starts sending `src` to the fixed address `write_addr`, typically a peripheral FIFO.
+    ///
+    /// # Safety
+    /// `write_addr` must stay valid for the transfer, and the returned [`Transfer`] must not be
+    /// leaked, as for [`DmaChannel::start_copy`].
+    pub unsafe fn start_write_from<'a, T>(&'a self, config: &ChannelConfig, src: &'a [T], write_addr: *mut c_void) -> Transfer<'a> {
+        let config = config.transfer_data_size::<T>().read_increment(true).write_increment(false);
+        self.configure(&config, write_addr, src.as_ptr() as *const c_void, src.len() as u32, true);
+        Transfer::new(self)
+    }
+
+    /// Routes the transfer-complete interrupt of this channel to `DMA_IRQ_0`.
+    pub fn set_irq0_enabled(&self, enabled: bool) {
+        unsafe { sys::wrapped_dma_channel_set_irq0_enabled(self.channel, enabled) }
+    }
+
+    /// Calls `handler` with the channel number every time a transfer of this channel
+    /// completes, or removes the handler when `None` is given.
+    ///
+    /// All channels share `DMA_IRQ_0`. While any channel has a handler, a dispatcher is added to
+    /// the line's shared handlers, which acknowledges each such channel's interrupt before
+    /// calling its handler and leaves the other channels to other handlers of the line.
+    pub fn set_completion_handler(&self, handler: Option<fn(u32)>) {
+        if handler.is_none() {
+            self.set_irq0_enabled(false);
+        }
+        irq::interrupt_free(|| unsafe {
+            let handlers = &mut *addr_of_mut!(COMPLETION_HANDLERS);
+            handlers[self.channel as usize] = handler;
+            let needed = handlers.iter().any(Option::is_some);
+            let installed = &mut *addr_of_mut!(COMPLETION_HANDLER_INSTALLED);
+            irq::update_shared_handler(irq::DMA_IRQ_0, completion_trampoline, installed, needed);
+        });
+        if handler.is_some() {
+            self.set_irq0_enabled(true);
+        }
+    }
+}
+
+static mut COMPLETION_HANDLERS: [Option<fn(u32)>; NUM_CHANNELS] = [None; NUM_CHANNELS];
+static mut COMPLETION_HANDLER_INSTALLED: bool = false;
+/// The channels held by a [`DmaChannel`], checked against the SDK's claims in debug builds.
+static mut OWNED_CHANNELS: u32 = 0;
+
+extern "C" fn completion_trampoline() {
+    let handlers = unsafe { &*addr_of!(COMPLETION_HANDLERS) };
+    for (channel, handler) in handlers.iter().enumerate() {
+        let (channel, handler) = match handler {
+            Some(handler) => (channel as u32, handler),
+            None => continue,
+        };
+        if !unsafe { sys::wrapped_dma_channel_get_irq0_status(channel) } {
+            continue;
+        }
+        unsafe { sys::wrapped_dma_channel_acknowledge_irq0(channel) };
+        handler(channel);
+    }
+}
+
+impl Drop for DmaChannel {
+    fn drop(&mut self) {
+        if unsafe { (*addr_of!(COMPLETION_HANDLERS))[self.channel as usize] }.is_some() {
+            self.set_completion_handler(None);
+        }
+        self.abort();
+        let (channel, bit) = (self.channel, 1 << self.channel);
+        debug_assert!(is_claimed(channel), "DMA channel {} was unclaimed while a DmaChannel owned it", channel);
+        with_claim_lock(|| unsafe {
+            debug_assert!(OWNED_CHANNELS & bit != 0, "DmaChannel {} is not in the registry", channel);
+            OWNED_CHANNELS &= !bit;
+        });
+        unsafe { sys::wrapped_dma_channel_unclaim(channel) }
+    }
+}
+
+/// Whether `channel` is claimed, by a [`DmaChannel`] or through the raw API.
+pub fn is_claimed(channel: u32) -> bool {
+    assert!((channel as usize) < NUM_CHANNELS, "invalid DMA channel {}", channel);
+    unsafe { sys::wrapped_dma_channel_is_claimed(channel) }
+}
+
+/// Channels owned by a [`DmaChannel`], one bit per channel. The SDK's claim bitmap also
+/// contains the channels claimed through the raw API.
+pub fn owned_channels() -> u32 {
+    with_claim_lock(|| unsafe { OWNED_CHANNELS })
+}
+
+/// Runs `f` under the spin lock the SDK guards its claim bitmaps with, so that the registry is
+/// consistent across both cores. `f` must not call the claim API, which takes the same lock.
+fn with_claim_lock<R>(f: impl FnOnce() -> R) -> R {
+    let saved_irq = unsafe { sys::wrapped_hw_claim_lock() };
+    let result = f();
+    unsafe { sys::wrapped_hw_claim_unlock(saved_irq) };
+    result
+}
+
+pub const NUM_TIMERS: u32 = 4;
+
+/// A claimed DMA pacing timer, released again when dropped.
+///
+/// The timer raises a data request at `numerator / denominator` times the system clock, so a
+/// channel paced by it (see [`ChannelConfig::paced_by`]) moves one element per tick, e.g. one
+/// audio sample per sample period.
+pub struct DmaTimer {
+    timer: u32,
+}
+
+impl DmaTimer {
+    /// Claims a timer nobody else is using, or returns `None` if all of them are taken.
+    pub fn claim_unused() -> Option<DmaTimer> {
+        let timer = unsafe { sys::wrapped_dma_claim_unused_timer(false) };
+        if timer < 0 {
+            None
+        } else {
+            Some(DmaTimer { timer: timer as u32 })
+        }
+    }
+
+    /// Claims `timer`, or returns `None` if it is already claimed.
+    pub fn claim(timer: u32) -> Option<DmaTimer> {
+        assert!(timer < NUM_TIMERS, "invalid DMA timer {}", timer);
+        if unsafe { sys::wrapped_dma_timer_is_claimed(timer) } {
+            return None;
+        }
+        unsafe { sys::wrapped_dma_timer_claim(timer) };
+        Some(DmaTimer { timer })
+    }
+
+    pub fn number(&self) -> u32 {
+        self.timer
+    }
+
+    /// Sets the rate to `numerator / denominator` times the system clock. The hardware cannot
+    /// go faster than the system clock, so the fraction must not exceed 1.
+    pub fn set_fraction(&self, numerator: u16, denominator: u16) {
+        assert!(
+            numerator != 0 && numerator <= denominator,
+            "DMA timer fraction {}/{} is not in 0 < X/Y <= 1",
+            numerator,
+            denominator
+        );
+        unsafe { sys::wrapped_dma_timer_set_fraction(self.timer, numerator, denominator) }
+    }
+
+    /// Sets the rate that comes closest to `rate_hz` at the current system clock and returns
+    /// the rate actually set, or `None` if the rate is out of the timer's range.
+    pub fn set_rate(&self, rate_hz: u32) -> Option<f32> {
+        let sys_clock_hz = clocks::sys_clock_hz();
+        let (numerator, denominator) = fraction_for_rate(sys_clock_hz, rate_hz)?;
+        self.set_fraction(numerator, denominator);
+        Some(sys_clock_hz as f32 * f32::from(numerator) / f32::from(denominator))
+    }
+
+    /// The data request signal of this timer (`dma_get_timer_dreq`).
+    pub fn dreq(&self) -> u32 {
+        unsafe { sys::wrapped_dma_get_timer_dreq(self.timer) }
+    }
+}
+
+impl Drop for DmaTimer {
+    fn drop(&mut self) {
+        unsafe { sys::wrapped_dma_timer_unclaim(self.timer) }
+    }
+}
+
+/// The 16-bit fraction `(numerator, denominator)` of `sys_clock_hz` closest to `rate_hz`, or
+/// `None` if `rate_hz` is 0, above the system clock or too far below the slowest rate of
+/// `sys_clock_hz / 65535`.
+///
+/// Walks the continued fraction of `rate_hz / sys_clock_hz`: its convergents are the best
+/// approximations for their denominator, and where the next one no longer fits in 16 bits, the
+/// largest intermediate fraction that does is the other candidate.
+pub fn fraction_for_rate(sys_clock_hz: u32, rate_hz: u32) -> Option<(u16, u16)> {
+    if rate_hz == 0 || rate_hz > sys_clock_hz {
+        return None;
+    }
+    let max = u64::from(u16::MAX);
+    let target = f64::from(rate_hz) / f64::from(sys_clock_hz);
+    let error = |(p, q): (u64, u64)| (p as f64 / q as f64 - target).abs();
+    let (mut previous, mut current) = ((0u64, 1u64), (1u64, 0u64));
+    let (mut n, mut d) = (u64::from(rate_hz), u64::from(sys_clock_hz));
+    let best = loop {
+        let a = n / d;
+        let next = (a * current.0 + previous.0, a * current.1 + previous.1);
+        if next.0 > max || next.1 > max {
+            let limit = |previous: u64, current: u64| (max - previous).checked_div(current).unwrap_or(u64::MAX);
+            let k = limit(previous.0, current.0).min(limit(previous.1, current.1));
+            let intermediate = (k * current.0 + previous.0, k * current.1 + previous.1);
+            if current.1 == 0 || (intermediate.1 != 0 && error(intermediate) < error(current)) {
+                break intermediate;
+            }
+            break current;
+        }
+        previous = current;
+        current = next;
+        let remainder = n % d;
+        if remainder == 0 {
+            break current;
+        }
+        n = d;
+        d = remainder;
+    };
+    if best.0 == 0 || best.1 == 0 {
+        return None;
+    }
+    Some((best.0 as u16, best.1 as u16))
+}
+
+/// A running transfer started by one of the `DmaChannel::start_*` functions, which keeps the
+/// buffers borrowed. Dropping it before the transfer is done aborts the transfer, so the DMA
+/// never touches the buffers after the borrow ends.
+pub struct Transfer<'a> {
+    channel: &'a DmaChannel,
+    _buffers: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> Transfer<'a> {
+    pub(crate) fn new(channel: &'a DmaChannel) -> Transfer<'a> {
+        Transfer { channel, _buffers: PhantomData }
+    }
+
+    pub fn is_done(&self) -> bool {
+        !self.channel.is_busy()
+    }
+
+    /// Blocks until the transfer is done, which releases the buffers.
+    pub fn wait(self) {
+        self.channel.wait_for_finish_blocking();
+    }
+}
+
+impl Drop for Transfer<'_> {
+    fn drop(&mut self) {
+        if self.channel.is_busy() {
+            // `dma_channel_abort` only returns once the channel has stopped.
+            self.channel.abort();
+        }
+    }
+}
+
+/// Builder over the SDK's `dma_channel_config`.
+#[derive(Clone, Copy)]
+pub struct ChannelConfig {
+    channel: u32,
+    ring: Option<Ring>,
+    config: sys::dma_channel_config,
+}
+
+impl ChannelConfig {
+    /// Triggers `other` when this channel completes.
+    ///
+    /// Chaining a channel to itself is how the hardware disables chaining, so it is rejected
+    /// here; use [`ChannelConfig::disable_chaining`] for that instead.
+    pub fn chain_to(self, other: &DmaChannel) -> Self {
+        assert!(
+            self.channel != other.channel,
+            "DMA channel {} cannot chain to itself",
+            self.channel
+        );
+        self.set_chain_to(other.channel)
+    }
+
+    pub fn disable_chaining(self) -> Self {
+        let channel = self.channel;
+        self.set_chain_to(channel)
+    }
+
+    pub fn read_increment(mut self, increment: bool) -> Self {
+        unsafe { sys::wrapped_channel_config_set_read_increment(&mut self.config, increment) };
+        self
+    }
+
+    pub fn write_increment(mut self, increment: bool) -> Self {
+        unsafe { sys::wrapped_channel_config_set_write_increment(&mut self.config, increment) };
+        self
+    }
+
+    pub fn enable(mut self, enable: bool) -> Self {
+        unsafe { sys::wrapped_channel_config_set_enable(&mut self.config, enable) };
+        self
+    }
+
+    /// Wraps the write (or read) address every `1 << size_bits` bytes, e.g. to stream into a
+    /// circular buffer. The buffer must be aligned to its size, which
+    /// [`DmaChannel::configure`] checks. `size_bits` of 0 turns the ring off again.
+    pub fn set_ring(mut self, write: bool, size_bits: u32) -> Self {
+        self.ring = Ring::new(write, size_bits);
+        unsafe { sys::wrapped_channel_config_set_ring(&mut self.config, write, size_bits) };
+        self
+    }
+
+    /// Paces the transfer by the data request signal `dreq` of a peripheral, e.g. from
+    /// `spi_get_dreq`.
+    pub fn dreq(mut self, dreq: u32) -> Self {
+        unsafe { sys::wrapped_channel_config_set_dreq(&mut self.config, dreq) };
+        self
+    }
+
+    /// Transfers elements of `T`, which must be 1, 2 or 4 bytes large.
+    pub(crate) fn transfer_data_size<T>(mut self) -> Self {
+        // `DMA_SIZE_8`, `DMA_SIZE_16` and `DMA_SIZE_32` of `enum dma_channel_transfer_size`.
+        let size = match size_of::<T>() {
+            1 => 0,
+            2 => 1,
+            4 => 2,
+            size => panic!("DMA cannot transfer {} byte elements", size),
+        };
+        unsafe { sys::wrapped_channel_config_set_transfer_data_size(&mut self.config, size) };
+        self
+    }
+
+    /// Paces the transfer by `timer`, one element per tick.
+    pub fn paced_by(self, timer: &DmaTimer) -> Self {
+        self.dreq(timer.dreq())
+    }
+
+    pub fn raw(&self) -> &sys::dma_channel_config {
+        &self.config
+    }
+
+    fn set_chain_to(mut self, chain_to: u32) -> Self {
+        unsafe { sys::wrapped_channel_config_set_chain_to(&mut self.config, chain_to) };
+        self
+    }
+}
+
+/// Address wrapping applied to one side of a transfer.
+#[derive(Clone, Copy)]
+struct Ring {
+    write: bool,
+    size_bits: u32,
+}
+
+impl Ring {
+    /// The ring size field is 4 bits wide, so rings cover 2 to 32768 bytes.
+    const MAX_SIZE_BITS: u32 = 15;
+
+    fn new(write: bool, size_bits: u32) -> Option<Ring> {
+        assert!(
+            size_bits <= Ring::MAX_SIZE_BITS,
+            "DMA ring size of 2^{} bytes exceeds the maximum of 2^{}",
+            size_bits,
+            Ring::MAX_SIZE_BITS
+        );
+        if size_bits == 0 {
+            None
+        } else {
+            Some(Ring { write, size_bits })
+        }
+    }
+
+    fn size_bytes(&self) -> usize {
+        1 << self.size_bits
+    }
+}
+
+#[cfg(feature = "async")]
+pub(crate) use wait::wait_until;
+
+#[cfg(feature = "async")]
+mod wait {
+    use core::future::poll_fn;
+    use core::ptr::addr_of_mut;
+    use core::task::{Poll, Waker};
+
+    use super::{DmaChannel, Transfer, NUM_CHANNELS};
+    use crate::irq;
+
+    const NO_WAKER: Option<Waker> = None;
+    static mut WAKERS: [Option<Waker>; NUM_CHANNELS] = [NO_WAKER; NUM_CHANNELS];
+
+    fn wake_channel(channel: u32) {
+        let waker = irq::interrupt_free(|| unsafe { (*addr_of_mut!(WAKERS))[channel as usize].take() });
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Resolves once `done` returns `true`, checking it again whenever one of `channels`
+    /// completes a transfer. This takes over the completion handlers of `channels`.
+    pub(crate) async fn wait_until(channels: &[&DmaChannel], mut done: impl FnMut() -> bool) {
+        poll_fn(|cx| {
+            for channel in channels {
+                irq::interrupt_free(|| unsafe {
+                    (*addr_of_mut!(WAKERS))[channel.channel as usize] = Some(cx.waker().clone());
+                });
+                channel.set_completion_handler(Some(wake_channel));
+            }
+            // Checked only after arming, so that a completion in between still wakes the task.
+            if done() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    impl DmaChannel {
+        /// Waits for the current transfer to finish, woken by the completion interrupt. This
+        /// replaces the channel's completion handler.
+        pub async fn wait_for_finish(&self) {
+            wait_until(&[self], || !self.is_busy()).await
+        }
+    }
+
+    impl Transfer<'_> {
+        /// Like [`Transfer::wait`], but awaits the completion interrupt instead of spinning.
+        pub async fn finish(self) {
+            self.channel.wait_for_finish().await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    fn handler(_: u32) {}
+
+    #[test]
+    fn the_dispatcher_is_shared_while_a_channel_has_a_handler() {
+        // Built directly, as the stubs do not keep the claims that dropping checks.
+        let channel = DmaChannel { channel: 2 };
+        let calls = record_calls(|| {
+            completion_trampoline();
+            channel.set_completion_handler(Some(handler));
+            completion_trampoline();
+        });
+        assert_eq!(
+            calls,
+            [
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_irq_add_shared_handler",
+                "wrapped_irq_set_enabled",
+                "wrapped_restore_interrupts",
+                "wrapped_dma_channel_set_irq0_enabled",
+                "wrapped_dma_channel_get_irq0_status",
+            ]
+        );
+        let calls = record_calls(|| channel.set_completion_handler(None));
+        assert_eq!(
+            calls,
+            [
+                "wrapped_dma_channel_set_irq0_enabled",
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_irq_remove_handler",
+                "wrapped_irq_has_shared_handler",
+                "wrapped_irq_set_enabled",
+                "wrapped_restore_interrupts",
+            ]
+        );
+        core::mem::forget(channel);
+    }
+
+    #[test]
+    fn chaining_goes_through_the_sdk_config() {
+        let (channel, other) = (DmaChannel { channel: 2 }, DmaChannel { channel: 3 });
+        let calls = record_calls(|| {
+            channel.default_config().chain_to(&other).disable_chaining();
+        });
+        assert_eq!(
+            calls,
+            [
+                "wrapped_dma_channel_get_default_config",
+                "wrapped_channel_config_set_chain_to",
+                "wrapped_channel_config_set_chain_to",
+            ]
+        );
+        core::mem::forget((channel, other));
+    }
+
+    #[test]
+    #[should_panic(expected = "DMA channel 4 cannot chain to itself")]
+    fn chaining_to_itself_is_rejected() {
+        let channel = DmaChannel { channel: 4 };
+        let config = channel.default_config();
+        let channel = core::mem::ManuallyDrop::new(channel);
+        config.chain_to(&channel);
+    }
+
+    #[test]
+    fn rings_cover_two_to_32768_bytes() {
+        assert!(Ring::new(true, 0).is_none());
+        assert_eq!(Ring::new(true, 1).unwrap().size_bytes(), 2);
+        assert_eq!(Ring::new(false, Ring::MAX_SIZE_BITS).unwrap().size_bytes(), 32768);
+    }
+
+    #[test]
+    #[should_panic(expected = "DMA ring size of 2^16 bytes exceeds the maximum of 2^15")]
+    fn larger_rings_are_rejected() {
+        Ring::new(true, 16);
+    }
+
+    #[test]
+    fn the_wrapped_address_is_checked_against_the_ring_size() {
+        #[repr(align(16))]
+        struct Aligned([u8; 32]);
+        let mut buffer = Aligned([0; 32]);
+        let channel = core::mem::ManuallyDrop::new(DmaChannel { channel: 5 });
+        let config = channel.default_config().set_ring(true, 4);
+        let write_addr = buffer.0.as_mut_ptr() as *mut c_void;
+        let calls = record_calls(|| unsafe { channel.configure(&config, write_addr, core::ptr::null(), 16, false) });
+        assert_eq!(calls, ["wrapped_dma_channel_configure"]);
+        let misaligned = std::panic::catch_unwind(|| unsafe {
+            channel.configure(&config, write_addr.wrapping_add(8), core::ptr::null(), 8, false)
+        });
+        assert!(misaligned.is_err());
+        // Without a ring the address may be anywhere.
+        let config = config.set_ring(false, 0);
+        unsafe { channel.configure(&config, write_addr.wrapping_add(8), core::ptr::null(), 8, false) };
+    }
+
+    #[test]
+    fn a_finished_transfer_is_not_aborted_on_drop() {
+        let channel = core::mem::ManuallyDrop::new(DmaChannel { channel: 1 });
+        let config = channel.default_config();
+        let (src, mut dst) = ([1u16, 2, 3], [0u16; 3]);
+        let calls = record_calls(|| drop(unsafe { channel.start_copy(&config, &mut dst, &src) }));
+        // The stubs report every channel as idle.
+        assert_eq!(
+            calls,
+            [
+                "wrapped_channel_config_set_transfer_data_size",
+                "wrapped_channel_config_set_read_increment",
+                "wrapped_channel_config_set_write_increment",
+                "wrapped_dma_channel_configure",
+                "wrapped_dma_channel_is_busy",
+            ]
+        );
+        let calls = record_calls(|| unsafe { channel.start_copy(&config, &mut dst, &src) }.wait());
+        // Having waited, the drop finds the channel idle.
+        assert_eq!(calls[calls.len() - 2..], ["wrapped_dma_channel_wait_for_finish_blocking", "wrapped_dma_channel_is_busy"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "DMA copy buffers differ in length")]
+    fn copies_need_buffers_of_one_length() {
+        let channel = core::mem::ManuallyDrop::new(DmaChannel { channel: 1 });
+        let config = channel.default_config();
+        let _ = unsafe { channel.start_copy(&config, &mut [0u8; 2], &[0u8; 3]) };
+    }
+
+    #[test]
+    fn claimed_channels_are_registered_under_the_claim_lock() {
+        let calls = record_calls(|| {
+            let channel = DmaChannel::claim(7).unwrap();
+            assert_eq!(channel.number(), 7);
+            assert_ne!(owned_channels() & 1 << 7, 0);
+            core::mem::forget(channel);
+        });
+        assert_eq!(
+            calls,
+            [
+                "wrapped_dma_channel_is_claimed",
+                "wrapped_dma_channel_claim",
+                "wrapped_hw_claim_lock",
+                "wrapped_hw_claim_unlock",
+                "wrapped_hw_claim_lock",
+                "wrapped_hw_claim_unlock",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "DMA channel 9 was unclaimed while a DmaChannel owned it")]
+    fn dropping_a_channel_the_sdk_no_longer_claims_is_caught() {
+        // The stubs report every channel as unclaimed.
+        drop(DmaChannel { channel: 9 });
+    }
+
+    /// The error of the best 16-bit fraction for `rate_hz`, found by trying every denominator.
+    fn best_error(sys_clock_hz: u32, rate_hz: u32) -> f64 {
+        let target = f64::from(rate_hz) / f64::from(sys_clock_hz);
+        (1..=u16::MAX as u32)
+            .filter_map(|q| {
+                let p = (target * f64::from(q)).round();
+                (p >= 1.0 && p <= f64::from(q)).then(|| (p / f64::from(q) - target).abs())
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    #[test]
+    fn fractions_are_exact_where_the_rate_divides_evenly() {
+        assert_eq!(fraction_for_rate(48_000_000, 48_000), Some((1, 1000)));
+        assert_eq!(fraction_for_rate(125_000_000, 125_000_000), Some((1, 1)));
+        assert_eq!(fraction_for_rate(125_000_000, 50_000_000), Some((2, 5)));
+    }
+
+    #[test]
+    fn other_rates_get_the_closest_16_bit_fraction() {
+        for (sys_clock_hz, rate_hz) in [(125_000_000, 44_100), (133_000_000, 22_050), (125_000_000, 1_000_003), (150_000_000, 2_000)] {
+            let (numerator, denominator) = fraction_for_rate(sys_clock_hz, rate_hz).unwrap();
+            let error = (f64::from(numerator) / f64::from(denominator) - f64::from(rate_hz) / f64::from(sys_clock_hz)).abs();
+            assert!(error <= best_error(sys_clock_hz, rate_hz), "{}/{} for {} Hz", numerator, denominator, rate_hz);
+        }
+    }
+
+    #[test]
+    fn rates_out_of_the_timer_range_have_no_fraction() {
+        assert_eq!(fraction_for_rate(125_000_000, 0), None);
+        assert_eq!(fraction_for_rate(125_000_000, 125_000_001), None);
+        assert_eq!(fraction_for_rate(125_000_000, 1), None);
+    }
+
+    #[test]
+    fn the_timer_rate_follows_the_system_clock() {
+        let timer = core::mem::ManuallyDrop::new(DmaTimer { timer: 1 });
+        // No rate fits the stopped clock the stubs report.
+        let calls = record_calls(|| assert_eq!(timer.set_rate(44_100), None));
+        assert_eq!(calls, ["wrapped_clock_get_hz"]);
+        let calls = record_calls(|| timer.set_fraction(1, 2));
+        assert_eq!(calls, ["wrapped_dma_timer_set_fraction"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "DMA timer fraction 3/2 is not in 0 < X/Y <= 1")]
+    fn timer_fractions_above_one_are_rejected() {
+        core::mem::ManuallyDrop::new(DmaTimer { timer: 0 }).set_fraction(3, 2);
+    }
+}
@@ -0,0 +1,52 @@
+/// Failure codes returned by SDK functions, valued as `enum pico_error_codes` in
+/// pico/error.h, plus the conditions the wrappers of this crate detect themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PicoError {
+    Generic,
+    Timeout,
+    NoData,
+    NotPermitted,
+    InvalidArg,
+    Io,
+    /// A negative code this crate does not know about.
+    Other(i32),
+    /// A UART received a byte while its RX FIFO was full.
+    Overrun,
+    /// A UART received a break condition.
+    Break,
+    /// A UART received a byte with the wrong parity.
+    Parity,
+    /// A UART received a byte without a valid stop bit.
+    Framing,
+}
+
+impl PicoError {
+    /// Splits an SDK return value into the non-negative result and the error codes.
+    pub fn check(code: i32) -> Result<u32, PicoError> {
+        match code {
+            code if code >= 0 => Ok(code as u32),
+            -1 => Err(PicoError::Generic),
+            -2 => Err(PicoError::Timeout),
+            -3 => Err(PicoError::NoData),
+            -4 => Err(PicoError::NotPermitted),
+            -5 => Err(PicoError::InvalidArg),
+            -6 => Err(PicoError::Io),
+            code => Err(PicoError::Other(code)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_codes_are_the_sdk_errors() {
+        assert_eq!(PicoError::check(3), Ok(3));
+        assert_eq!(PicoError::check(0), Ok(0));
+        assert_eq!(PicoError::check(-1), Err(PicoError::Generic));
+        assert_eq!(PicoError::check(-2), Err(PicoError::Timeout));
+        assert_eq!(PicoError::check(-6), Err(PicoError::Io));
+        assert_eq!(PicoError::check(-42), Err(PicoError::Other(-42)));
+    }
+}
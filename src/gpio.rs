@@ -0,0 +1,549 @@
+use raspi_pico_sdk_sys as sys;
+
+/// The level a pin reads while its input is considered active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActiveLevel {
+    High,
+    Low,
+}
+
+/// A debounced change of an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    /// The input became active, e.g. a button was pressed.
+    Activated,
+    /// The input became inactive again.
+    Deactivated,
+}
+
+/// A button or switch input that only reports a change once the pin has kept its new level
+/// for the whole debounce window.
+pub struct DebouncedInput {
+    gpio: u32,
+    active_level: ActiveLevel,
+    debouncer: Debouncer,
+}
+
+impl DebouncedInput {
+    /// Configures `gpio` as an input with the pull resistor set to the inactive level, so an
+    /// open switch reads as inactive.
+    pub fn new(gpio: u32, active_level: ActiveLevel, window_us: u64) -> DebouncedInput {
+        unsafe {
+            sys::wrapped_gpio_init(gpio);
+            sys::wrapped_gpio_set_dir(gpio, false);
+            match active_level {
+                ActiveLevel::High => sys::wrapped_gpio_pull_down(gpio),
+                ActiveLevel::Low => sys::wrapped_gpio_pull_up(gpio),
+            }
+        }
+        DebouncedInput {
+            gpio,
+            active_level,
+            debouncer: Debouncer::new(read_active(gpio, active_level), window_us, now_us()),
+        }
+    }
+
+    /// Samples the pin and returns the edge once a change has been stable for the debounce
+    /// window. Has to be called more often than the window is long to catch short presses.
+    pub fn poll(&mut self) -> Option<Edge> {
+        let active = read_active(self.gpio, self.active_level);
+        self.debouncer.update(active, now_us())
+    }
+
+    /// The debounced state, not the current pin level.
+    pub fn is_active(&self) -> bool {
+        self.debouncer.stable
+    }
+}
+
+fn read_active(gpio: u32, active_level: ActiveLevel) -> bool {
+    let high = unsafe { sys::wrapped_gpio_get(gpio) };
+    high == (active_level == ActiveLevel::High)
+}
+
+fn now_us() -> u64 {
+    unsafe { sys::wrapped_time_us_64() }
+}
+
+/// The debounce state machine: a new level is only accepted after it has been sampled
+/// continuously for `window_us`.
+struct Debouncer {
+    stable: bool,
+    candidate: bool,
+    candidate_since_us: u64,
+    window_us: u64,
+}
+
+impl Debouncer {
+    fn new(active: bool, window_us: u64, now_us: u64) -> Debouncer {
+        Debouncer {
+            stable: active,
+            candidate: active,
+            candidate_since_us: now_us,
+            window_us,
+        }
+    }
+
+    fn update(&mut self, active: bool, now_us: u64) -> Option<Edge> {
+        if active != self.candidate {
+            // The level bounced (or just changed); restart the window.
+            self.candidate = active;
+            self.candidate_since_us = now_us;
+            return None;
+        }
+        if self.candidate == self.stable || now_us.wrapping_sub(self.candidate_since_us) < self.window_us {
+            return None;
+        }
+        self.stable = self.candidate;
+        Some(if self.stable { Edge::Activated } else { Edge::Deactivated })
+    }
+}
+
+/// Number of user GPIOs in bank 0: 30, or 48 on the RP2350B.
+pub const NUM_GPIOS: usize = sys::NUM_BANK0_GPIOS as usize;
+
+/// Enables or disables the input buffer of the pad. Output-only pins can turn it off to save a
+/// little power; while it is off, the pin always reads low.
+pub fn set_input_enabled(gpio: u32, enabled: bool) {
+    assert!((gpio as usize) < NUM_GPIOS, "invalid GPIO {}", gpio);
+    unsafe { sys::wrapped_gpio_set_input_enabled(gpio, enabled) }
+}
+
+/// Enables or disables the Schmitt trigger on the input, which keeps slow or noisy edges from
+/// toggling the input several times. It is on after reset.
+pub fn set_input_hysteresis_enabled(gpio: u32, enabled: bool) {
+    assert!((gpio as usize) < NUM_GPIOS, "invalid GPIO {}", gpio);
+    unsafe { sys::wrapped_gpio_set_input_hysteresis_enabled(gpio, enabled) }
+}
+
+pub fn is_input_hysteresis_enabled(gpio: u32) -> bool {
+    assert!((gpio as usize) < NUM_GPIOS, "invalid GPIO {}", gpio);
+    unsafe { sys::wrapped_gpio_is_input_hysteresis_enabled(gpio) }
+}
+
+/// Which transition [`InputPin::wait_for_edge`] waits for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinEdge {
+    Rising,
+    Falling,
+    Any,
+}
+
+/// A plain GPIO input.
+pub struct InputPin {
+    gpio: u32,
+}
+
+impl InputPin {
+    pub fn new(gpio: u32) -> InputPin {
+        assert!((gpio as usize) < NUM_GPIOS, "invalid GPIO {}", gpio);
+        unsafe {
+            sys::wrapped_gpio_init(gpio);
+            sys::wrapped_gpio_set_dir(gpio, false);
+        }
+        InputPin { gpio }
+    }
+
+    pub fn gpio(&self) -> u32 {
+        self.gpio
+    }
+
+    pub fn is_high(&self) -> bool {
+        unsafe { sys::wrapped_gpio_get(self.gpio) }
+    }
+
+    pub fn is_low(&self) -> bool {
+        !self.is_high()
+    }
+}
+
+/// `N` pins driven or read together as the bits of one value: bit `i` is `pins[i]`, wherever on
+/// the bank it is. Runs of consecutive pins are moved with a single shift each, so a group of
+/// adjacent pins costs no more than the raw mask functions.
+pub struct PinGroup<const N: usize> {
+    mask: u32,
+    /// `(value bits, shift to the pins)` of each run, the first `run_count` entries used.
+    runs: [(u32, i32); N],
+    run_count: usize,
+}
+
+impl<const N: usize> PinGroup<N> {
+    /// Initializes the pins as inputs. Panics if a pin is listed twice.
+    pub fn new(pins: [u32; N]) -> PinGroup<N> {
+        let mut mask = 0u32;
+        let mut runs = [(0, 0); N];
+        let mut run_count = 0usize;
+        for (bit, &gpio) in pins.iter().enumerate() {
+            // The mask functions of the SDK only reach the first 32 GPIOs.
+            assert!(gpio < 32 && (gpio as usize) < NUM_GPIOS, "invalid GPIO {}", gpio);
+            assert!(mask & (1 << gpio) == 0, "GPIO {} is listed twice in the pin group", gpio);
+            mask |= 1 << gpio;
+            let shift = gpio as i32 - bit as i32;
+            match run_count.checked_sub(1).map(|last| &mut runs[last]) {
+                Some((bits, run_shift)) if *run_shift == shift => *bits |= 1 << bit,
+                _ => {
+                    runs[run_count] = (1 << bit, shift);
+                    run_count += 1;
+                }
+            }
+        }
+        unsafe {
+            sys::wrapped_gpio_init_mask(mask);
+            sys::wrapped_gpio_set_dir_masked(mask, 0);
+        }
+        PinGroup { mask, runs, run_count }
+    }
+
+    /// The pins as a mask of the bank.
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+
+    /// Switches every pin of the group to output, or back to input.
+    pub fn set_output(&mut self, output: bool) {
+        unsafe { sys::wrapped_gpio_set_dir_masked(self.mask, if output { self.mask } else { 0 }) }
+    }
+
+    /// Drives the pins with the low `N` bits of `value`; other pins keep their level.
+    pub fn write(&mut self, value: u32) {
+        unsafe { sys::wrapped_gpio_put_masked(self.mask, self.to_pins(value)) }
+    }
+
+    /// Reads the pins into the low `N` bits.
+    pub fn read(&self) -> u32 {
+        self.from_pins(unsafe { sys::wrapped_gpio_get_all() })
+    }
+
+    /// Moves the bits of `value` to the positions of their pins in the bank.
+    pub fn to_pins(&self, value: u32) -> u32 {
+        self.runs[..self.run_count].iter().fold(0, |pins, &(bits, shift)| {
+            let bits = value & bits;
+            pins | if shift >= 0 { bits << shift } else { bits >> -shift }
+        })
+    }
+
+    /// Collects the levels of the group's pins from `pins`, a value of the whole bank, into the
+    /// low `N` bits.
+    pub fn from_pins(&self, pins: u32) -> u32 {
+        self.runs[..self.run_count].iter().fold(0, |value, &(bits, shift)| {
+            let pins = if shift >= 0 { pins >> shift } else { pins << -shift };
+            value | (pins & bits)
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+mod wait {
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::ptr::addr_of_mut;
+    use core::task::{Context, Poll, Waker};
+
+    use raspi_pico_sdk_sys as sys;
+
+    use super::{InputPin, PinEdge, NUM_GPIOS};
+    use crate::irq;
+
+    // `GPIO_IRQ_*` event bits of hardware/gpio.h.
+    const IRQ_LEVEL_LOW: u32 = 0x1;
+    const IRQ_LEVEL_HIGH: u32 = 0x2;
+    const IRQ_EDGE_FALL: u32 = 0x4;
+    const IRQ_EDGE_RISE: u32 = 0x8;
+    const ALL_EVENTS: u32 = 0xf;
+
+    impl PinEdge {
+        fn event_mask(self) -> u32 {
+            match self {
+                PinEdge::Rising => IRQ_EDGE_RISE,
+                PinEdge::Falling => IRQ_EDGE_FALL,
+                PinEdge::Any => IRQ_EDGE_RISE | IRQ_EDGE_FALL,
+            }
+        }
+    }
+
+    const NO_WAKER: Option<Waker> = None;
+    static mut WAKERS: [Option<Waker>; NUM_GPIOS] = [NO_WAKER; NUM_GPIOS];
+    static mut FIRED: [bool; NUM_GPIOS] = [false; NUM_GPIOS];
+
+    /// The GPIO callback of the current core: disarms the pin so that a level event does not
+    /// fire again, then wakes the task waiting on it.
+    extern "C" fn gpio_callback(gpio: u32, _event_mask: u32) {
+        let index = gpio as usize;
+        if index >= NUM_GPIOS {
+            return;
+        }
+        unsafe { sys::wrapped_gpio_set_irq_enabled(gpio, ALL_EVENTS, false) };
+        let waker = unsafe {
+            FIRED[index] = true;
+            (*addr_of_mut!(WAKERS))[index].take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Resolves once one of `events` is seen on the pin.
+    ///
+    /// The interrupt is only armed on the first poll, and disarmed again when the future
+    /// completes or is dropped, so the runtime just has to poll again after being woken.
+    struct WaitForEvent<'a> {
+        pin: &'a mut InputPin,
+        events: u32,
+        armed: bool,
+    }
+
+    impl Future for WaitForEvent<'_> {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let this = self.get_mut();
+            let index = this.pin.gpio as usize;
+            let fired = irq::interrupt_free(|| unsafe {
+                if FIRED[index] {
+                    FIRED[index] = false;
+                    return true;
+                }
+                let waker = &mut (*addr_of_mut!(WAKERS))[index];
+                match waker {
+                    Some(waker) if waker.will_wake(cx.waker()) => {}
+                    _ => *waker = Some(cx.waker().clone()),
+                }
+                false
+            });
+            if fired {
+                this.armed = false;
+                return Poll::Ready(());
+            }
+            if !this.armed {
+                this.armed = true;
+                // This replaces the GPIO callback of the current core, which is therefore not
+                // available for other uses while pins are awaited.
+                unsafe {
+                    sys::wrapped_gpio_set_irq_enabled_with_callback(this.pin.gpio, this.events, true, Some(gpio_callback))
+                };
+            }
+            Poll::Pending
+        }
+    }
+
+    impl Drop for WaitForEvent<'_> {
+        fn drop(&mut self) {
+            if !self.armed {
+                return;
+            }
+            unsafe { sys::wrapped_gpio_set_irq_enabled(self.pin.gpio, ALL_EVENTS, false) };
+            let index = self.pin.gpio as usize;
+            irq::interrupt_free(|| unsafe {
+                FIRED[index] = false;
+                (*addr_of_mut!(WAKERS))[index] = None;
+            });
+        }
+    }
+
+    impl InputPin {
+        pub async fn wait_for_edge(&mut self, edge: PinEdge) {
+            WaitForEvent { pin: self, events: edge.event_mask(), armed: false }.await
+        }
+
+        /// Returns right away if the pin is already high.
+        pub async fn wait_for_high(&mut self) {
+            WaitForEvent { pin: self, events: IRQ_LEVEL_HIGH, armed: false }.await
+        }
+
+        /// Returns right away if the pin is already low.
+        pub async fn wait_for_low(&mut self) {
+            WaitForEvent { pin: self, events: IRQ_LEVEL_LOW, armed: false }.await
+        }
+    }
+
+    impl embedded_hal::digital::ErrorType for InputPin {
+        type Error = core::convert::Infallible;
+    }
+
+    impl embedded_hal_async::digital::Wait for InputPin {
+        async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+            InputPin::wait_for_high(self).await;
+            Ok(())
+        }
+
+        async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+            InputPin::wait_for_low(self).await;
+            Ok(())
+        }
+
+        async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+            self.wait_for_edge(PinEdge::Rising).await;
+            Ok(())
+        }
+
+        async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+            self.wait_for_edge(PinEdge::Falling).await;
+            Ok(())
+        }
+
+        async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+            self.wait_for_edge(PinEdge::Any).await;
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use core::pin::pin;
+        use core::task::{Context, Poll, Waker};
+
+        use super::*;
+        use crate::testing::record_calls;
+
+        #[test]
+        fn edges_are_armed_as_their_irq_events() {
+            assert_eq!(PinEdge::Rising.event_mask(), IRQ_EDGE_RISE);
+            assert_eq!(PinEdge::Falling.event_mask(), IRQ_EDGE_FALL);
+            assert_eq!(PinEdge::Any.event_mask(), IRQ_EDGE_RISE | IRQ_EDGE_FALL);
+        }
+
+        #[test]
+        fn the_pin_is_armed_on_the_first_poll_until_its_event_fires() {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut pin_7 = InputPin { gpio: 7 };
+            let calls = record_calls(|| {
+                let mut wait = pin!(pin_7.wait_for_edge(PinEdge::Rising));
+                assert_eq!(wait.as_mut().poll(&mut cx), Poll::Pending);
+                assert_eq!(wait.as_mut().poll(&mut cx), Poll::Pending);
+                gpio_callback(7, IRQ_EDGE_RISE);
+                assert_eq!(wait.as_mut().poll(&mut cx), Poll::Ready(()));
+            });
+            assert_eq!(
+                calls,
+                [
+                    "wrapped_save_and_disable_interrupts",
+                    "wrapped_restore_interrupts",
+                    "wrapped_gpio_set_irq_enabled_with_callback",
+                    "wrapped_save_and_disable_interrupts",
+                    "wrapped_restore_interrupts",
+                    "wrapped_gpio_set_irq_enabled",
+                    "wrapped_save_and_disable_interrupts",
+                    "wrapped_restore_interrupts",
+                ]
+            );
+            // A wait dropped while armed disarms the pin.
+            let calls = record_calls(|| {
+                let mut wait = pin!(pin_7.wait_for_low());
+                assert_eq!(wait.as_mut().poll(&mut cx), Poll::Pending);
+            });
+            assert_eq!(
+                calls[2..],
+                [
+                    "wrapped_gpio_set_irq_enabled_with_callback",
+                    "wrapped_gpio_set_irq_enabled",
+                    "wrapped_save_and_disable_interrupts",
+                    "wrapped_restore_interrupts",
+                ]
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    fn input_pin_runs_against_the_host_stubs() {
+        let calls = record_calls(|| {
+            let pin = InputPin::new(3);
+            assert!(pin.is_low());
+        });
+        assert_eq!(calls, ["wrapped_gpio_init", "wrapped_gpio_set_dir", "wrapped_gpio_get"]);
+    }
+
+    #[test]
+    fn a_change_is_reported_once_it_held_for_the_window() {
+        let mut debouncer = Debouncer::new(false, 1000, 0);
+        assert_eq!(debouncer.update(true, 100), None);
+        assert_eq!(debouncer.update(true, 1099), None);
+        assert_eq!(debouncer.update(true, 1100), Some(Edge::Activated));
+        assert_eq!(debouncer.update(true, 5000), None);
+        assert_eq!(debouncer.update(false, 6000), None);
+        assert_eq!(debouncer.update(false, 7000), Some(Edge::Deactivated));
+    }
+
+    #[test]
+    fn a_bounce_restarts_the_window() {
+        let mut debouncer = Debouncer::new(false, 1000, 0);
+        assert_eq!(debouncer.update(true, 100), None);
+        assert_eq!(debouncer.update(false, 600), None);
+        assert_eq!(debouncer.update(true, 900), None);
+        assert_eq!(debouncer.update(true, 1500), None);
+        assert_eq!(debouncer.update(true, 1900), Some(Edge::Activated));
+        assert!(debouncer.stable);
+    }
+
+    #[test]
+    fn the_window_survives_the_timer_wrapping() {
+        let mut debouncer = Debouncer::new(false, 1000, 0);
+        assert_eq!(debouncer.update(true, u64::MAX - 499), None);
+        assert_eq!(debouncer.update(true, 500), Some(Edge::Activated));
+    }
+
+    #[test]
+    fn the_pull_follows_the_inactive_level() {
+        let calls = record_calls(|| {
+            let input = DebouncedInput::new(4, ActiveLevel::Low, 1000);
+            // The stubs read the pin low, which is active here.
+            assert!(input.is_active());
+        });
+        assert_eq!(
+            calls,
+            ["wrapped_gpio_init", "wrapped_gpio_set_dir", "wrapped_gpio_pull_up", "wrapped_gpio_get", "wrapped_time_us_64"]
+        );
+    }
+
+    #[test]
+    fn pad_inputs_are_configured_through_the_sdk() {
+        let calls = record_calls(|| {
+            set_input_enabled(29, false);
+            set_input_hysteresis_enabled(0, true);
+            assert!(!is_input_hysteresis_enabled(0));
+        });
+        assert_eq!(
+            calls,
+            [
+                "wrapped_gpio_set_input_enabled",
+                "wrapped_gpio_set_input_hysteresis_enabled",
+                "wrapped_gpio_is_input_hysteresis_enabled",
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid GPIO 30")]
+    fn pads_past_the_bank_are_rejected() {
+        set_input_hysteresis_enabled(30, false);
+    }
+
+    #[test]
+    fn scattered_pins_are_moved_run_by_run() {
+        let mut group = None;
+        let calls = record_calls(|| group = Some(PinGroup::new([2, 3, 4, 10, 9, 0])));
+        let group = group.unwrap();
+        assert_eq!(calls, ["wrapped_gpio_init_mask", "wrapped_gpio_set_dir_masked"]);
+        assert_eq!(group.mask(), 0b110_0001_1101);
+        assert_eq!(group.run_count, 4);
+        assert_eq!(group.to_pins(0b000_111), 0b1_1100);
+        assert_eq!(group.to_pins(0b101_000), 1 << 10 | 1);
+        assert_eq!(group.to_pins(0b010_000), 1 << 9);
+        for value in 0..1 << 6 {
+            assert_eq!(group.from_pins(group.to_pins(value)), value);
+        }
+        // Other pins of the bank are ignored.
+        assert_eq!(group.from_pins(!group.mask()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "GPIO 4 is listed twice in the pin group")]
+    fn pins_are_grouped_once() {
+        PinGroup::new([4, 5, 4]);
+    }
+}
@@ -0,0 +1,167 @@
+use core::alloc::{GlobalAlloc, Layout};
+use core::ffi::c_void;
+use core::mem::size_of;
+use core::ptr::null_mut;
+
+// `pico_malloc`'s wrappers of newlib's allocator, which take its mutex (see
+// `PICO_USE_MALLOC_MUTEX`) so both cores can allocate at the same time. Calling them directly
+// keeps a firmware linked without the `--wrap` flags of the `link_args` metadata from silently
+// bypassing the mutex: their `__real_*` calls fail to link instead. The tests run on the host's
+// allocator.
+extern "C" {
+    #[cfg_attr(test, link_name = "malloc")]
+    fn __wrap_malloc(size: usize) -> *mut c_void;
+    #[cfg_attr(test, link_name = "calloc")]
+    fn __wrap_calloc(count: usize, size: usize) -> *mut c_void;
+    #[cfg_attr(test, link_name = "realloc")]
+    fn __wrap_realloc(ptr: *mut c_void, size: usize) -> *mut c_void;
+    #[cfg_attr(test, link_name = "free")]
+    fn __wrap_free(ptr: *mut c_void);
+}
+
+/// Alignment of every block newlib's `malloc` returns.
+const MALLOC_ALIGN: usize = 8;
+
+/// The global allocator of the `alloc` feature, handing out blocks of the SDK heap.
+///
+/// `memalign` is not wrapped by `pico_malloc` and would bypass its mutex, so blocks aligned
+/// beyond [`MALLOC_ALIGN`] are cut out of a larger `malloc` block instead, with the pointer to
+/// free stored in front of them.
+pub struct SdkHeap;
+
+#[cfg_attr(not(test), global_allocator)]
+static HEAP: SdkHeap = SdkHeap;
+
+impl SdkHeap {
+    fn is_malloc_aligned(layout: &Layout) -> bool {
+        layout.align() <= MALLOC_ALIGN
+    }
+
+    /// Allocates `layout.size()` bytes at a multiple of `layout.align()` from a block of
+    /// `malloc`, with room for the block's address in front.
+    unsafe fn alloc_over_aligned(layout: Layout) -> *mut u8 {
+        let size = match layout.size().checked_add(layout.align()) {
+            Some(size) => size,
+            None => return null_mut(),
+        };
+        let block = __wrap_malloc(size) as *mut u8;
+        if block.is_null() {
+            return null_mut();
+        }
+        // `block` is 8-byte aligned and the alignment a larger power of two, so at least 8
+        // bytes are left in front of the aligned address.
+        let offset = layout.align() - (block as usize & (layout.align() - 1));
+        let ptr = block.add(offset);
+        (ptr as *mut *mut u8).sub(1).write_unaligned(block);
+        ptr
+    }
+
+    unsafe fn over_aligned_block(ptr: *mut u8) -> *mut u8 {
+        (ptr as *mut *mut u8).sub(1).read_unaligned()
+    }
+}
+
+unsafe impl GlobalAlloc for SdkHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if Self::is_malloc_aligned(&layout) {
+            __wrap_malloc(layout.size()) as *mut u8
+        } else {
+            Self::alloc_over_aligned(layout)
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if Self::is_malloc_aligned(&layout) {
+            __wrap_free(ptr as *mut c_void)
+        } else {
+            __wrap_free(Self::over_aligned_block(ptr) as *mut c_void)
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if Self::is_malloc_aligned(&layout) {
+            return __wrap_calloc(1, layout.size()) as *mut u8;
+        }
+        let ptr = Self::alloc_over_aligned(layout);
+        if !ptr.is_null() {
+            ptr.write_bytes(0, layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if Self::is_malloc_aligned(&layout) {
+            return __wrap_realloc(ptr as *mut c_void, new_size) as *mut u8;
+        }
+        // `realloc` could move the block to an address of a different alignment.
+        let new_ptr = Self::alloc_over_aligned(Layout::from_size_align_unchecked(new_size, layout.align()));
+        if !new_ptr.is_null() {
+            new_ptr.copy_from_nonoverlapping(ptr, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+// The block address has to fit in front of an over-aligned pointer.
+const _: () = assert!(size_of::<*mut u8>() <= MALLOC_ALIGN);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_aligned(ptr: *mut u8, align: usize) -> bool {
+        ptr as usize & (align - 1) == 0
+    }
+
+    #[test]
+    fn over_aligned_blocks_are_aligned_and_freed_through_their_malloc_block() {
+        for align in [16, 64, 4096] {
+            let layout = Layout::from_size_align(100, align).unwrap();
+            unsafe {
+                let ptr = HEAP.alloc(layout);
+                assert!(!ptr.is_null() && is_aligned(ptr, align));
+                let block = SdkHeap::over_aligned_block(ptr);
+                assert!(block < ptr && ptr as usize - (block as usize) <= align);
+                ptr.write_bytes(0xa5, layout.size());
+                HEAP.dealloc(ptr, layout);
+            }
+        }
+    }
+
+    #[test]
+    fn over_aligned_zeroed_blocks_are_zeroed() {
+        let layout = Layout::from_size_align(256, 32).unwrap();
+        unsafe {
+            let ptr = HEAP.alloc_zeroed(layout);
+            assert!(is_aligned(ptr, 32));
+            assert!(core::slice::from_raw_parts(ptr, layout.size()).iter().all(|&byte| byte == 0));
+            HEAP.dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn realloc_keeps_the_alignment_and_the_contents() {
+        for (align, new_size) in [(8, 1000), (64, 1000), (64, 10)] {
+            let layout = Layout::from_size_align(50, align).unwrap();
+            unsafe {
+                let ptr = HEAP.alloc(layout);
+                for i in 0..layout.size() {
+                    ptr.add(i).write(i as u8);
+                }
+                let ptr = HEAP.realloc(ptr, layout, new_size);
+                assert!(!ptr.is_null() && is_aligned(ptr, align));
+                for i in 0..layout.size().min(new_size) {
+                    assert_eq!(ptr.add(i).read(), i as u8);
+                }
+                HEAP.dealloc(ptr, Layout::from_size_align(new_size, align).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn oversized_over_aligned_layouts_fail() {
+        let layout = Layout::from_size_align(isize::MAX as usize - 63, 64).unwrap();
+        assert!(unsafe { HEAP.alloc(layout) }.is_null());
+    }
+}
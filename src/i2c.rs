@@ -0,0 +1,572 @@
+use raspi_pico_sdk_sys as sys;
+
+use crate::error::PicoError;
+
+pub const NUM_INSTANCES: usize = 2;
+
+pub struct I2c {
+    inst: *mut sys::i2c_inst_t,
+}
+
+// The instance stands for a fixed peripheral, so it can be handed to the other core.
+unsafe impl Send for I2c {}
+
+impl I2c {
+    /// Initializes I2C instance `index` (0 or 1) at `baudrate`. The SDA/SCL pins still have
+    /// to be switched to the I2C function by the caller.
+    pub fn new(index: u32, baudrate: u32) -> I2c {
+        assert!((index as usize) < NUM_INSTANCES, "invalid I2C instance {}", index);
+        let inst = unsafe { sys::wrapped_i2c_get_instance(index) };
+        unsafe { sys::wrapped_i2c_init(inst, baudrate) };
+        I2c { inst }
+    }
+
+    pub fn index(&self) -> u32 {
+        unsafe { sys::wrapped_i2c_get_index(self.inst) }
+    }
+
+    pub fn raw(&self) -> *mut sys::i2c_inst_t {
+        self.inst
+    }
+
+    /// Writes `src` to the device at `address`, returning the number of bytes written. With
+    /// `nostop` the bus is kept and the next transfer starts with a repeated start.
+    pub fn write(&mut self, address: u8, src: &[u8], nostop: bool) -> Result<usize, PicoError> {
+        let written = unsafe { sys::wrapped_i2c_write_blocking(self.inst, address, src.as_ptr(), src.len() as _, nostop) };
+        PicoError::check(written).map(|written| written as usize)
+    }
+
+    /// Reads into `dst` from the device at `address`, returning the number of bytes read.
+    /// `nostop` works as for [`I2c::write`].
+    pub fn read(&mut self, address: u8, dst: &mut [u8], nostop: bool) -> Result<usize, PicoError> {
+        let read = unsafe { sys::wrapped_i2c_read_blocking(self.inst, address, dst.as_mut_ptr(), dst.len() as _, nostop) };
+        PicoError::check(read).map(|read| read as usize)
+    }
+
+    /// Turns this instance into a slave (target) answering at `address`, calling `handler`
+    /// from the I2C interrupt for every slave event.
+    pub fn set_slave_mode(&mut self, address: u8, handler: SlaveHandler) {
+        let index = self.index() as usize;
+        crate::irq::interrupt_free(|| unsafe { SLAVE_HANDLERS[index] = Some(handler) });
+        unsafe { sys::wrapped_i2c_slave_init(self.inst, address, Some(slave_trampoline)) };
+    }
+
+    /// Returns to controller mode and forgets the slave handler.
+    pub fn clear_slave_mode(&mut self) {
+        unsafe { sys::wrapped_i2c_slave_deinit(self.inst) };
+        let index = self.index() as usize;
+        crate::irq::interrupt_free(|| unsafe { SLAVE_HANDLERS[index] = None });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlaveEvent {
+    /// Data from the controller is waiting in the receive FIFO.
+    Receive,
+    /// The controller is reading; write the response bytes.
+    Request,
+    /// The controller sent a stop or restart.
+    Finish,
+}
+
+impl SlaveEvent {
+    // The cast keeps this working whether bindgen emits `i2c_slave_event_t` as an integer
+    // alias or as a Rust enum.
+    #[allow(clippy::unnecessary_cast)]
+    fn from_raw(event: sys::i2c_slave_event_t) -> SlaveEvent {
+        // Same order as `i2c_slave_event_t` in pico/i2c_slave.h.
+        match event as u32 {
+            0 => SlaveEvent::Receive,
+            1 => SlaveEvent::Request,
+            _ => SlaveEvent::Finish,
+        }
+    }
+}
+
+pub type SlaveHandler = fn(&mut SlaveContext, SlaveEvent);
+
+/// Access to the FIFOs of the instance a slave event was raised for.
+pub struct SlaveContext {
+    inst: *mut sys::i2c_inst_t,
+}
+
+impl SlaveContext {
+    pub fn read_available(&self) -> usize {
+        unsafe { sys::wrapped_i2c_get_read_available(self.inst) as usize }
+    }
+
+    pub fn read_byte(&mut self) -> u8 {
+        unsafe { sys::wrapped_i2c_read_byte_raw(self.inst) }
+    }
+
+    pub fn write_byte(&mut self, value: u8) {
+        unsafe { sys::wrapped_i2c_write_byte_raw(self.inst, value) }
+    }
+}
+
+static mut SLAVE_HANDLERS: [Option<SlaveHandler>; NUM_INSTANCES] = [None; NUM_INSTANCES];
+
+extern "C" fn slave_trampoline(inst: *mut sys::i2c_inst_t, event: sys::i2c_slave_event_t) {
+    let index = unsafe { sys::wrapped_i2c_get_index(inst) } as usize;
+    if let Some(handler) = unsafe { SLAVE_HANDLERS[index] } {
+        handler(&mut SlaveContext { inst }, SlaveEvent::from_raw(event));
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+mod hal {
+    use embedded_hal::i2c::{ErrorKind, ErrorType, NoAcknowledgeSource, Operation, SevenBitAddress};
+
+    use super::I2c;
+    use crate::error::PicoError;
+
+    impl embedded_hal::i2c::Error for PicoError {
+        fn kind(&self) -> ErrorKind {
+            match self {
+                // The blocking SDK functions report an aborted transfer, which is nearly
+                // always a missing acknowledge, as a generic error.
+                PicoError::Generic => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+                _ => ErrorKind::Other,
+            }
+        }
+    }
+
+    impl I2c {
+        /// Makes the next transfer continue the current one instead of starting with a
+        /// repeated start, after a transfer in the same direction that was ended with `nostop`.
+        fn continue_transfer(&mut self) {
+            unsafe { (*self.inst).restart_on_next = false };
+        }
+    }
+
+    impl ErrorType for I2c {
+        type Error = PicoError;
+    }
+
+    impl embedded_hal::i2c::I2c for I2c {
+        fn read(&mut self, address: SevenBitAddress, read: &mut [u8]) -> Result<(), PicoError> {
+            self.transaction(address, &mut [Operation::Read(read)])
+        }
+
+        fn write(&mut self, address: SevenBitAddress, write: &[u8]) -> Result<(), PicoError> {
+            self.transaction(address, &mut [Operation::Write(write)])
+        }
+
+        fn write_read(&mut self, address: SevenBitAddress, write: &[u8], read: &mut [u8]) -> Result<(), PicoError> {
+            self.transaction(address, &mut [Operation::Write(write), Operation::Read(read)])
+        }
+
+        /// Every operation but the last is sent with `nostop`. Between operations of different
+        /// direction the SDK then emits a repeated start; adjacent operations of the same
+        /// direction are merged into one transfer, as embedded-hal requires.
+        fn transaction(&mut self, address: SevenBitAddress, operations: &mut [Operation<'_>]) -> Result<(), PicoError> {
+            let count = operations.len();
+            let mut previous_was_read = None;
+            for (index, operation) in operations.iter_mut().enumerate() {
+                let nostop = index + 1 < count;
+                let is_read = matches!(operation, Operation::Read(_));
+                if previous_was_read == Some(is_read) {
+                    self.continue_transfer();
+                }
+                match operation {
+                    Operation::Read(buffer) => I2c::read(self, address, buffer, nostop)?,
+                    Operation::Write(buffer) => I2c::write(self, address, buffer, nostop)?,
+                };
+                previous_was_read = Some(is_read);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+pub use dma_async::DmaI2c;
+
+#[cfg(feature = "async")]
+mod dma_async {
+    use core::ffi::c_void;
+    use core::future::poll_fn;
+    use core::ptr::{addr_of_mut, read_volatile, write_volatile};
+    use core::task::Poll;
+
+    use embedded_hal::i2c::{ErrorType, Operation, SevenBitAddress};
+    use raspi_pico_sdk_sys as sys;
+
+    use super::I2c;
+    use crate::dma::{self, DmaChannel, Transfer};
+    use crate::error::PicoError;
+    use crate::time::Instant;
+
+    // Bits of `IC_DATA_CMD`: a command word is a data byte or a read request, optionally
+    // preceded by a repeated start or followed by a stop.
+    const CMD_READ: u32 = 1 << 8;
+    const CMD_STOP: u32 = 1 << 9;
+    const CMD_RESTART: u32 = 1 << 10;
+    // `IC_RAW_INTR_STAT`, `IC_TX_ABRT_SOURCE` and `IC_DMA_CR` bits.
+    const INTR_TX_ABRT: u32 = 1 << 6;
+    const INTR_STOP_DET: u32 = 1 << 9;
+    const ABRT_ARB_LOST: u32 = 1 << 12;
+    const DMA_CR_RDMAE: u32 = 1 << 0;
+    const DMA_CR_TDMAE: u32 = 1 << 1;
+
+    /// Command words queued by one pass of the TX channel.
+    const CHUNK: usize = 64;
+
+    /// How long the bytes left in the FIFOs and the stop may take once the TX channel is done:
+    /// far longer than 16 bytes take at any standard bus speed, so that only a target holding
+    /// the clock low runs into it.
+    const TAIL_TIMEOUT_US: u64 = 100_000;
+
+    /// Resolves once `done` returns `true`, yielding to the executor between checks, or fails
+    /// with [`PicoError::Timeout`] once `deadline` is reached.
+    async fn yield_until(deadline: Instant, mut done: impl FnMut() -> bool) -> Result<(), PicoError> {
+        poll_fn(|cx| {
+            if done() {
+                Poll::Ready(Ok(()))
+            } else if deadline.is_reached() {
+                Poll::Ready(Err(PicoError::Timeout))
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// An [`I2c`] controller whose transfers are fed by two DMA channels and awaited through
+    /// the DMA completion interrupt. This takes over the completion handlers of both channels.
+    ///
+    /// The hardware FIFOs hold 16 bytes, so the last bytes of a read and of the whole
+    /// transaction are polled for, yielding to the executor between checks, which is also how
+    /// aborts (a missing acknowledge or lost arbitration) are noticed once the TX channel is
+    /// done. An aborted transaction returns the error after the controller has released the
+    /// bus, and one whose tail takes longer than [`TAIL_TIMEOUT_US`] fails with
+    /// [`PicoError::Timeout`].
+    pub struct DmaI2c {
+        i2c: I2c,
+        tx: DmaChannel,
+        rx: DmaChannel,
+    }
+
+    impl DmaI2c {
+        pub fn new(i2c: I2c, tx: DmaChannel, rx: DmaChannel) -> DmaI2c {
+            let dma_i2c = DmaI2c { i2c, tx, rx };
+            unsafe { write_volatile(addr_of_mut!((*dma_i2c.hw()).dma_cr), DMA_CR_RDMAE | DMA_CR_TDMAE) };
+            dma_i2c
+        }
+
+        pub fn free(self) -> (I2c, DmaChannel, DmaChannel) {
+            unsafe { write_volatile(addr_of_mut!((*self.hw()).dma_cr), 0) };
+            (self.i2c, self.tx, self.rx)
+        }
+
+        fn hw(&self) -> *mut sys::i2c_hw_t {
+            unsafe { sys::wrapped_i2c_get_hw(self.i2c.inst) }
+        }
+
+        fn raw_interrupts(&self) -> u32 {
+            unsafe { read_volatile(addr_of_mut!((*self.hw()).raw_intr_stat)) }
+        }
+
+        /// Prepares the controller for a transaction with `address`: the target can only be
+        /// changed while the controller is disabled, and leftovers of an earlier aborted
+        /// transaction are cleared.
+        fn start(&mut self, address: SevenBitAddress) {
+            let hw = self.hw();
+            unsafe {
+                write_volatile(addr_of_mut!((*hw).enable), 0);
+                write_volatile(addr_of_mut!((*hw).tar), address as u32);
+                write_volatile(addr_of_mut!((*hw).enable), 1);
+                read_volatile(addr_of_mut!((*hw).clr_stop_det));
+                read_volatile(addr_of_mut!((*hw).clr_tx_abrt));
+                while sys::wrapped_i2c_get_read_available(self.i2c.inst) > 0 {
+                    sys::wrapped_i2c_read_byte_raw(self.i2c.inst);
+                }
+            }
+        }
+
+        /// The error of an aborted transfer, clearing the abort so that the controller accepts
+        /// commands again.
+        fn take_abort(&self) -> Result<(), PicoError> {
+            if self.raw_interrupts() & INTR_TX_ABRT == 0 {
+                return Ok(());
+            }
+            let hw = self.hw();
+            let source = unsafe { read_volatile(addr_of_mut!((*hw).tx_abrt_source)) };
+            unsafe { read_volatile(addr_of_mut!((*hw).clr_tx_abrt)) };
+            // The blocking functions report a missing acknowledge as a generic error too.
+            Err(if source & ABRT_ARB_LOST != 0 { PicoError::Io } else { PicoError::Generic })
+        }
+
+        /// Queues `commands` and, for read requests, receives the answers into `read`.
+        async fn run(&mut self, commands: &[u32], read: Option<&mut [u8]>) -> Result<(), PicoError> {
+            let hw = self.hw();
+            let data = unsafe { addr_of_mut!((*hw).data_cmd) } as *mut c_void;
+            let inst = self.i2c.inst;
+            let rx_transfer = match read {
+                Some(read) => {
+                    let config = self.rx.default_config()
+                        .transfer_data_size::<u8>()
+                        .dreq(unsafe { sys::wrapped_i2c_get_dreq(inst, false) })
+                        .read_increment(false)
+                        .write_increment(true);
+                    unsafe { self.rx.configure(&config, read.as_mut_ptr() as *mut c_void, data, read.len() as u32, true) };
+                    Some(Transfer::new(&self.rx))
+                }
+                None => None,
+            };
+            let config = self.tx.default_config()
+                .transfer_data_size::<u32>()
+                .dreq(unsafe { sys::wrapped_i2c_get_dreq(inst, true) })
+                .read_increment(true)
+                .write_increment(false);
+            unsafe { self.tx.configure(&config, data, commands.as_ptr() as *const c_void, commands.len() as u32, true) };
+            let _tx_transfer = Transfer::new(&self.tx);
+
+            // After an abort the controller discards the commands, so the TX channel finishes
+            // either way.
+            let tx = &self.tx;
+            dma::wait_until(&[tx], || !tx.is_busy()).await;
+            if let Some(rx_transfer) = &rx_transfer {
+                // At most a FIFO's worth of answers is still outstanding. An abort leaves the RX
+                // channel unfinished, so its completion interrupt cannot end this wait.
+                let deadline = Instant::timeout_us(TAIL_TIMEOUT_US);
+                yield_until(deadline, || rx_transfer.is_done() || self.raw_interrupts() & INTR_TX_ABRT != 0).await?;
+            }
+            // An early return drops the transfers, which aborts the channels.
+            let aborted = self.take_abort();
+            drop(rx_transfer);
+            aborted
+        }
+
+        /// Sends (`read` of `None`) or receives the bytes of one operation in chunks.
+        async fn operation(&mut self, write: Option<&[u8]>, mut read: Option<&mut [u8]>, restart: bool, stop: bool) -> Result<(), PicoError> {
+            let len = write.map_or(0, |write| write.len()) + read.as_ref().map_or(0, |read| read.len());
+            let mut commands = [0u32; CHUNK];
+            let mut start = 0;
+            while start < len {
+                let end = (start + CHUNK).min(len);
+                for (offset, command) in commands[..end - start].iter_mut().enumerate() {
+                    *command = command_word(write, start + offset, len, restart, stop);
+                }
+                let chunk_read = read.as_mut().map(|read| &mut read[start..end]);
+                self.run(&commands[..end - start], chunk_read).await?;
+                start = end;
+            }
+            Ok(())
+        }
+    }
+
+    /// The command word sending byte `index` of the `len` of an operation, or requesting it
+    /// without `write`.
+    fn command_word(write: Option<&[u8]>, index: usize, len: usize, restart: bool, stop: bool) -> u32 {
+        let mut command = match write {
+            Some(write) => write[index] as u32,
+            None => CMD_READ,
+        };
+        if index == 0 && restart {
+            command |= CMD_RESTART;
+        }
+        if index + 1 == len && stop {
+            command |= CMD_STOP;
+        }
+        command
+    }
+
+    impl ErrorType for DmaI2c {
+        type Error = PicoError;
+    }
+
+    impl embedded_hal_async::i2c::I2c for DmaI2c {
+        async fn read(&mut self, address: SevenBitAddress, read: &mut [u8]) -> Result<(), PicoError> {
+            self.transaction(address, &mut [Operation::Read(read)]).await
+        }
+
+        async fn write(&mut self, address: SevenBitAddress, write: &[u8]) -> Result<(), PicoError> {
+            self.transaction(address, &mut [Operation::Write(write)]).await
+        }
+
+        async fn write_read(&mut self, address: SevenBitAddress, write: &[u8], read: &mut [u8]) -> Result<(), PicoError> {
+            self.transaction(address, &mut [Operation::Write(write), Operation::Read(read)]).await
+        }
+
+        /// Adjacent operations of the same direction continue one transfer, and a change of
+        /// direction is sent as a repeated start. Empty operations are skipped, as the
+        /// controller cannot address a device without transferring a byte.
+        async fn transaction(&mut self, address: SevenBitAddress, operations: &mut [Operation<'_>]) -> Result<(), PicoError> {
+            let is_empty = |operation: &Operation<'_>| match operation {
+                Operation::Read(buffer) => buffer.is_empty(),
+                Operation::Write(buffer) => buffer.is_empty(),
+            };
+            let last = match operations.iter().rposition(|operation| !is_empty(operation)) {
+                Some(last) => last,
+                None => return Ok(()),
+            };
+            self.start(address);
+            let mut previous_was_read = None;
+            for (index, operation) in operations[..=last].iter_mut().enumerate() {
+                if is_empty(operation) {
+                    continue;
+                }
+                let is_read = matches!(operation, Operation::Read(_));
+                let restart = previous_was_read.is_some_and(|previous| previous != is_read);
+                previous_was_read = Some(is_read);
+                let stop = index == last;
+                match operation {
+                    Operation::Read(buffer) => self.operation(None, Some(buffer), restart, stop).await?,
+                    Operation::Write(buffer) => self.operation(Some(buffer), None, restart, stop).await?,
+                }
+            }
+            // The last bytes are still in the FIFO; the transaction is over once the stop has
+            // been sent, or the controller gave up.
+            let deadline = Instant::timeout_us(TAIL_TIMEOUT_US);
+            yield_until(deadline, || self.raw_interrupts() & (INTR_STOP_DET | INTR_TX_ABRT) != 0).await?;
+            self.take_abort()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use core::future::Future;
+        use core::mem::ManuallyDrop;
+        use core::pin::pin;
+        use core::ptr::null_mut;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        use core::task::{Context, Poll, Waker};
+        use std::sync::Arc;
+        use std::task::Wake;
+
+        use embedded_hal_async::i2c::I2c as _;
+
+        use super::*;
+        use crate::testing::record_calls;
+
+        #[test]
+        fn the_first_and_last_commands_carry_the_restart_and_the_stop() {
+            let write = [0x12, 0x34, 0x56];
+            let words = (0..3).map(|index| command_word(Some(&write), index, 3, true, true)).collect::<Vec<_>>();
+            assert_eq!(words, [0x12 | CMD_RESTART, 0x34, 0x56 | CMD_STOP]);
+            assert_eq!(command_word(None, 0, 2, false, true), CMD_READ);
+            assert_eq!(command_word(None, 1, 2, false, true), CMD_READ | CMD_STOP);
+            assert_eq!(command_word(None, 0, 1, true, false), CMD_READ | CMD_RESTART);
+        }
+
+        #[test]
+        fn the_tail_is_polled_without_blocking_the_executor() {
+            struct CountingWaker(AtomicUsize);
+
+            impl Wake for CountingWaker {
+                fn wake(self: Arc<Self>) {
+                    self.wake_by_ref();
+                }
+
+                fn wake_by_ref(self: &Arc<Self>) {
+                    self.0.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+
+            let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+            let waker = Waker::from(counter.clone());
+            let mut checks = 0;
+            let calls = record_calls(|| {
+                let mut tail = pin!(yield_until(Instant::timeout_us(TAIL_TIMEOUT_US), || {
+                    checks += 1;
+                    checks == 3
+                }));
+                let mut cx = Context::from_waker(&waker);
+                assert_eq!(tail.as_mut().poll(&mut cx), Poll::Pending);
+                assert_eq!(tail.as_mut().poll(&mut cx), Poll::Pending);
+                assert_eq!(tail.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+            });
+            // Every pending check asked to be polled again.
+            assert_eq!(counter.0.load(Ordering::Relaxed), 2);
+            // The deadline was checked after each of them, and not once the tail was done.
+            assert_eq!(calls.iter().filter(|call| **call == "wrapped_time_reached").count(), 2);
+        }
+
+        #[test]
+        fn transactions_without_bytes_do_not_touch_the_bus() {
+            // Built directly, as the controller registers are not there on the host.
+            let i2c = I2c { inst: null_mut() };
+            let (tx, rx) = (DmaChannel::claim(10).unwrap(), DmaChannel::claim(11).unwrap());
+            let mut dma_i2c = ManuallyDrop::new(DmaI2c { i2c, tx, rx });
+            let calls = record_calls(|| {
+                let mut operations = [Operation::Write(&[]), Operation::Read(&mut [])];
+                let mut transaction = pin!(dma_i2c.transaction(0x40, &mut operations));
+                let poll = transaction.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+                assert_eq!(poll, Poll::Ready(Ok(())));
+            });
+            assert!(calls.is_empty());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::null_mut;
+    use std::cell::RefCell;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::testing::record_calls;
+
+    std::thread_local! {
+        static EVENTS: RefCell<Vec<SlaveEvent>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record_event(_: &mut SlaveContext, event: SlaveEvent) {
+        EVENTS.with(|events| events.borrow_mut().push(event));
+    }
+
+    #[test]
+    fn slave_events_are_decoded_in_the_sdk_order() {
+        assert_eq!(SlaveEvent::from_raw(sys::i2c_slave_event_t_I2C_SLAVE_RECEIVE), SlaveEvent::Receive);
+        assert_eq!(SlaveEvent::from_raw(sys::i2c_slave_event_t_I2C_SLAVE_REQUEST), SlaveEvent::Request);
+        assert_eq!(SlaveEvent::from_raw(sys::i2c_slave_event_t_I2C_SLAVE_FINISH), SlaveEvent::Finish);
+    }
+
+    #[test]
+    fn slave_events_reach_the_handler_until_slave_mode_is_cleared() {
+        let mut i2c = I2c { inst: null_mut() };
+        let calls = record_calls(|| {
+            i2c.set_slave_mode(0x17, record_event);
+            slave_trampoline(null_mut(), sys::i2c_slave_event_t_I2C_SLAVE_REQUEST);
+            i2c.clear_slave_mode();
+            slave_trampoline(null_mut(), sys::i2c_slave_event_t_I2C_SLAVE_FINISH);
+        });
+        assert_eq!(EVENTS.with(|events| events.borrow().clone()), [SlaveEvent::Request]);
+        assert_eq!(
+            calls,
+            [
+                "wrapped_i2c_get_index",
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_restore_interrupts",
+                "wrapped_i2c_slave_init",
+                "wrapped_i2c_get_index",
+                "wrapped_i2c_slave_deinit",
+                "wrapped_i2c_get_index",
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_restore_interrupts",
+                "wrapped_i2c_get_index",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-hal")]
+    fn transactions_merge_adjacent_operations_of_one_direction() {
+        use embedded_hal::i2c::{Error, ErrorKind, I2c as _, NoAcknowledgeSource, Operation};
+
+        let mut inst = sys::i2c_inst_t { hw: null_mut(), restart_on_next: true };
+        let mut i2c = I2c { inst: &mut inst };
+        let calls = record_calls(|| {
+            let mut read = [0; 2];
+            i2c.transaction(0x50, &mut [Operation::Write(&[1]), Operation::Write(&[2]), Operation::Read(&mut read)]).unwrap();
+        });
+        assert_eq!(calls, ["wrapped_i2c_write_blocking", "wrapped_i2c_write_blocking", "wrapped_i2c_read_blocking"]);
+        // The second write continued the first instead of starting again.
+        assert!(!inst.restart_on_next);
+        assert_eq!(PicoError::Generic.kind(), ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown));
+        assert_eq!(PicoError::Timeout.kind(), ErrorKind::Other);
+    }
+}
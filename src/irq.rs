@@ -0,0 +1,74 @@
+use raspi_pico_sdk_sys as sys;
+
+// The IRQ numbers of the SDK are enum constants named differently on each platform
+// (`irq_num_rp2040`, `irq_num_rp2350`), so they are repeated here for each.
+
+#[cfg(not(pico_platform = "rp2350"))]
+pub const PWM_IRQ_WRAP: u32 = 4;
+#[cfg(not(pico_platform = "rp2350"))]
+pub const PIO0_IRQ_0: u32 = 7;
+#[cfg(not(pico_platform = "rp2350"))]
+pub const PIO0_IRQ_1: u32 = 8;
+#[cfg(not(pico_platform = "rp2350"))]
+pub const PIO1_IRQ_0: u32 = 9;
+#[cfg(not(pico_platform = "rp2350"))]
+pub const PIO1_IRQ_1: u32 = 10;
+#[cfg(not(pico_platform = "rp2350"))]
+pub const DMA_IRQ_0: u32 = 11;
+#[cfg(not(pico_platform = "rp2350"))]
+pub const IO_IRQ_BANK0: u32 = 13;
+
+/// `PWM_IRQ_WRAP_0`, the first of the two wrap interrupts of the RP2350.
+#[cfg(pico_platform = "rp2350")]
+pub const PWM_IRQ_WRAP: u32 = 8;
+#[cfg(pico_platform = "rp2350")]
+pub const PIO0_IRQ_0: u32 = 15;
+#[cfg(pico_platform = "rp2350")]
+pub const PIO0_IRQ_1: u32 = 16;
+#[cfg(pico_platform = "rp2350")]
+pub const PIO1_IRQ_0: u32 = 17;
+#[cfg(pico_platform = "rp2350")]
+pub const PIO1_IRQ_1: u32 = 18;
+#[cfg(pico_platform = "rp2350")]
+pub const PIO2_IRQ_0: u32 = 19;
+#[cfg(pico_platform = "rp2350")]
+pub const PIO2_IRQ_1: u32 = 20;
+#[cfg(pico_platform = "rp2350")]
+pub const DMA_IRQ_0: u32 = 10;
+#[cfg(pico_platform = "rp2350")]
+pub const IO_IRQ_BANK0: u32 = 21;
+
+pub fn set_enabled(irq: u32, enabled: bool) {
+    unsafe { sys::wrapped_irq_set_enabled(irq, enabled) }
+}
+
+/// `PICO_SHARED_IRQ_HANDLER_DEFAULT_ORDER_PRIORITY`, for handlers that need not run before or
+/// after the other handlers of the interrupt.
+const SHARED_HANDLER_DEFAULT_ORDER: u8 = 0x80;
+
+/// Adds `handler` to the handlers of `irq`, which other code can add its own to, while `needed`
+/// and removes it again once not, with `installed` tracking whether it is. The interrupt is
+/// enabled with the handler and disabled once no handler is left.
+///
+/// Callers decide and update under [`interrupt_free`], so the decisions of concurrent callers
+/// cannot interleave.
+pub(crate) fn update_shared_handler(irq: u32, handler: extern "C" fn(), installed: &mut bool, needed: bool) {
+    if needed && !*installed {
+        unsafe { sys::wrapped_irq_add_shared_handler(irq, Some(handler), SHARED_HANDLER_DEFAULT_ORDER) };
+        set_enabled(irq, true);
+    } else if !needed && *installed {
+        unsafe { sys::wrapped_irq_remove_handler(irq, Some(handler)) };
+        if !unsafe { sys::wrapped_irq_has_shared_handler(irq) } {
+            set_enabled(irq, false);
+        }
+    }
+    *installed = needed;
+}
+
+/// Runs `f` with interrupts disabled on the current core.
+pub(crate) fn interrupt_free<R>(f: impl FnOnce() -> R) -> R {
+    let status = unsafe { sys::wrapped_save_and_disable_interrupts() };
+    let result = f();
+    unsafe { sys::wrapped_restore_interrupts(status) };
+    result
+}
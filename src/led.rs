@@ -0,0 +1,82 @@
+use raspi_pico_sdk_sys as sys;
+
+/// The LED soldered onto the board.
+///
+/// On the Pico W boards it hangs off the CYW43 wireless chip, so the SDK has to be built with
+/// `PICO_SDK_RS_WIRELESS` and the wireless architecture initialized (`cyw43_arch_init`) before
+/// it can be used. Everywhere else it is the plain GPIO named by `PICO_DEFAULT_LED_PIN`.
+pub struct Led {
+    on: bool,
+}
+
+impl Led {
+    pub fn onboard() -> Led {
+        init();
+        let mut led = Led { on: false };
+        led.off();
+        led
+    }
+
+    pub fn set(&mut self, on: bool) {
+        put(on);
+        self.on = on;
+    }
+
+    pub fn on(&mut self) {
+        self.set(true);
+    }
+
+    pub fn off(&mut self) {
+        self.set(false);
+    }
+
+    pub fn toggle(&mut self) {
+        let on = !self.on;
+        self.set(on);
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+}
+
+#[cfg(any(pico_board = "pico_w", pico_board = "pico2_w"))]
+fn init() {}
+
+#[cfg(any(pico_board = "pico_w", pico_board = "pico2_w"))]
+fn put(on: bool) {
+    unsafe { sys::wrapped_cyw43_arch_gpio_put(sys::CYW43_WL_GPIO_LED_PIN, on) }
+}
+
+#[cfg(not(any(pico_board = "pico_w", pico_board = "pico2_w")))]
+fn init() {
+    unsafe {
+        sys::wrapped_gpio_init(sys::PICO_DEFAULT_LED_PIN);
+        sys::wrapped_gpio_set_dir(sys::PICO_DEFAULT_LED_PIN, true);
+    }
+}
+
+#[cfg(not(any(pico_board = "pico_w", pico_board = "pico2_w")))]
+fn put(on: bool) {
+    unsafe { sys::wrapped_gpio_put(sys::PICO_DEFAULT_LED_PIN, on) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    fn the_led_starts_off_and_toggles() {
+        let mut calls = record_calls(|| {
+            let mut led = Led::onboard();
+            assert!(!led.is_on());
+            led.toggle();
+            assert!(led.is_on());
+            led.toggle();
+            assert!(!led.is_on());
+        });
+        assert_eq!(calls.drain(..2).collect::<Vec<_>>(), ["wrapped_gpio_init", "wrapped_gpio_set_dir"]);
+        assert_eq!(calls, ["wrapped_gpio_put"; 3]);
+    }
+}
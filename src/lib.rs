@@ -1,2 +1,87 @@
-#![no_std]
-pub use raspi_pico_sdk_sys::*;
+#![cfg_attr(not(test), no_std)]
+pub use raspi_pico_sdk_sys::*;
+
+#[cfg(all(feature = "sleep", not(pico_sdk_sleep)))]
+compile_error!("the `sleep` feature needs pico-extras: set PICO_SDK_RS_PICO_EXTRAS_PATH to a checkout of it");
+#[cfg(all(feature = "async-context", not(pico_sdk_async_context)))]
+compile_error!("the `async-context` feature needs the SDK's async context: set PICO_SDK_RS_ASYNC_CONTEXT=1");
+
+pub mod adc;
+#[cfg(feature = "async-context")]
+pub mod async_context;
+pub mod clocks;
+pub mod delay;
+pub mod dma;
+pub mod error;
+pub mod gpio;
+#[cfg(feature = "alloc")]
+pub mod heap;
+pub mod i2c;
+pub mod irq;
+pub mod led;
+pub mod pio;
+#[cfg(pico_platform = "rp2350")]
+pub mod powman;
+pub mod pwm;
+#[cfg(pico_platform = "rp2040")]
+pub mod rtc;
+pub mod shared_bus;
+#[cfg(feature = "sleep")]
+pub mod sleep;
+pub mod spi;
+pub mod sync;
+#[cfg(test)]
+mod testing;
+pub mod time;
+pub mod uart;
+pub mod watchdog;
+pub mod zerocross;
+
+/// What [`init`] set up.
+pub struct Board {
+    /// The frequency the SDK's runtime set the system clock to before `main`.
+    pub sys_clock_hz: u32,
+    /// The onboard LED, switched off. `None` on the Pico W boards if the wireless chip it hangs
+    /// off failed to initialize.
+    pub led: Option<led::Led>,
+}
+
+/// Brings up what most firmware starts with: the stdio drivers linked into the SDK build
+/// (`stdio_init_all`) and the onboard LED. On the Pico W boards that initializes the wireless
+/// chip first (`cyw43_arch_init`), so the SDK has to be built with `PICO_SDK_RS_WIRELESS`. The
+/// clocks need nothing more, the SDK's runtime sets them to the defaults before `main` runs.
+pub fn init() -> Board {
+    let _ = unsafe { raspi_pico_sdk_sys::wrapped_stdio_init_all() };
+    Board { sys_clock_hz: clocks::sys_clock_hz(), led: init_led() }
+}
+
+#[cfg(any(pico_board = "pico_w", pico_board = "pico2_w"))]
+fn init_led() -> Option<led::Led> {
+    let code = unsafe { raspi_pico_sdk_sys::wrapped_cyw43_arch_init() };
+    error::PicoError::check(code).ok().map(|_| led::Led::onboard())
+}
+
+#[cfg(not(any(pico_board = "pico_w", pico_board = "pico2_w")))]
+fn init_led() -> Option<led::Led> {
+    Some(led::Led::onboard())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    #[cfg(not(any(pico_board = "pico_w", pico_board = "pico2_w")))]
+    fn init_brings_up_stdio_before_the_led() {
+        let mut board = None;
+        let calls = record_calls(|| board = Some(init()));
+        assert_eq!(
+            calls,
+            ["wrapped_stdio_init_all", "wrapped_clock_get_hz", "wrapped_gpio_init", "wrapped_gpio_set_dir", "wrapped_gpio_put"]
+        );
+        let board = board.unwrap();
+        assert_eq!(board.sys_clock_hz, 0);
+        assert!(!board.led.unwrap().is_on());
+    }
+}
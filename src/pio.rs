@@ -0,0 +1,267 @@
+use core::ptr::{addr_of, addr_of_mut};
+
+use raspi_pico_sdk_sys as sys;
+
+use crate::{clocks, irq};
+
+/// 2 on the RP2040, 3 on the RP2350.
+pub const NUM_INSTANCES: usize = sys::NUM_PIOS as usize;
+pub const NUM_STATE_MACHINES: u32 = 4;
+/// Every PIO block has two IRQ lines, each with its own set of enabled sources.
+pub const NUM_IRQ_LINES: usize = 2;
+
+/// Number of interrupt sources one PIO block can raise.
+const NUM_SOURCES: usize = 12;
+
+/// What can raise a PIO interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptSource {
+    /// The RX FIFO of the given state machine holds data.
+    RxFifoNotEmpty(u32),
+    /// The TX FIFO of the given state machine has room.
+    TxFifoNotFull(u32),
+    /// One of the four IRQ flags a program sets with the `irq` instruction.
+    Flag(u32),
+}
+
+impl InterruptSource {
+    /// Position of the source in the `INTR` register, which is also its `pio_interrupt_source`
+    /// value: four RX-not-empty bits, then four TX-not-full bits, then the four IRQ flags.
+    fn bit(self) -> u32 {
+        match self {
+            InterruptSource::RxFifoNotEmpty(sm) => sm,
+            InterruptSource::TxFifoNotFull(sm) => 4 + sm,
+            InterruptSource::Flag(flag) => 8 + flag,
+        }
+    }
+
+    fn from_bit(bit: u32) -> InterruptSource {
+        match bit / 4 {
+            0 => InterruptSource::RxFifoNotEmpty(bit % 4),
+            1 => InterruptSource::TxFifoNotFull(bit % 4),
+            _ => InterruptSource::Flag(bit % 4),
+        }
+    }
+
+    fn validate(self) {
+        let index = match self {
+            InterruptSource::RxFifoNotEmpty(sm) | InterruptSource::TxFifoNotFull(sm) => sm,
+            InterruptSource::Flag(flag) => flag,
+        };
+        assert!(index < 4, "invalid PIO interrupt source {:?}", self);
+    }
+
+    /// Whether the source is currently asserted. The FIFO sources are level-triggered, so
+    /// they stay asserted until the FIFO is serviced.
+    fn is_active(self, pio: sys::PIO) -> bool {
+        unsafe {
+            match self {
+                InterruptSource::RxFifoNotEmpty(sm) => !sys::wrapped_pio_sm_is_rx_fifo_empty(pio, sm),
+                InterruptSource::TxFifoNotFull(sm) => !sys::wrapped_pio_sm_is_tx_fifo_full(pio, sm),
+                InterruptSource::Flag(flag) => sys::wrapped_pio_interrupt_get(pio, flag),
+            }
+        }
+    }
+}
+
+pub struct Pio {
+    index: u32,
+    pio: sys::PIO,
+}
+
+impl Pio {
+    /// Wraps PIO block `index` (below [`NUM_INSTANCES`]). Loading programs and configuring state machines is
+    /// still done through the raw API.
+    pub fn new(index: u32) -> Pio {
+        assert!((index as usize) < NUM_INSTANCES, "invalid PIO instance {}", index);
+        Pio { index, pio: unsafe { sys::wrapped_pio_get_instance(index) } }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn raw(&self) -> sys::PIO {
+        self.pio
+    }
+
+    /// Calls `handler` whenever `source` is asserted on IRQ line `line` (0 or 1) of this
+    /// block, or disables the source when `None` is given.
+    ///
+    /// Sources enabled on the same line share one NVIC interrupt. While any of them has a
+    /// handler, a dispatcher is added to the interrupt's shared handlers, which only looks at
+    /// the sources with a handler. IRQ flags are cleared before their handler runs, but the
+    /// FIFO sources keep firing until the handler reads (or fills) the FIFO.
+    pub fn set_interrupt_handler(&mut self, line: u32, source: InterruptSource, handler: Option<fn(InterruptSource)>) {
+        assert!((line as usize) < NUM_IRQ_LINES, "invalid PIO IRQ line {}", line);
+        source.validate();
+        let (index, line_index) = (self.index as usize, line as usize);
+        let irq_num = unsafe { sys::wrapped_pio_get_irq_num(self.pio, line) };
+        if handler.is_none() {
+            unsafe { sys::wrapped_pio_set_irqn_source_enabled(self.pio, line, source.bit(), false) };
+        }
+        irq::interrupt_free(|| unsafe {
+            let handlers = &mut (*addr_of_mut!(HANDLERS))[index][line_index];
+            handlers[source.bit() as usize] = handler;
+            let needed = handlers.iter().any(Option::is_some);
+            let installed = &mut (*addr_of_mut!(HANDLER_INSTALLED))[index][line_index];
+            irq::update_shared_handler(irq_num, TRAMPOLINES[index][line_index], installed, needed);
+        });
+        if handler.is_some() {
+            unsafe { sys::wrapped_pio_set_irqn_source_enabled(self.pio, line, source.bit(), true) };
+        }
+    }
+}
+
+/// State machine clock divider for running at `target_hz` from the current system clock, as
+/// the integer and 8 bit fractional part (`sm_config_set_clkdiv_int_frac8`).
+///
+/// Targets outside the range the divider can reach (1 to 65535 + 255/256) are clamped to the
+/// nearest end; use [`clkdiv_from_hz_checked`] to detect that.
+pub fn clkdiv_from_hz(target_hz: u32) -> (u16, u8) {
+    match clkdiv(clocks::sys_clock_hz(), target_hz) {
+        Ok(divider) | Err(divider) => divider,
+    }
+}
+
+/// Like [`clkdiv_from_hz`], but `None` if `target_hz` cannot be reached.
+pub fn clkdiv_from_hz_checked(target_hz: u32) -> Option<(u16, u8)> {
+    clkdiv(clocks::sys_clock_hz(), target_hz).ok()
+}
+
+/// The 16.8 fixed point divider closest to `sys_hz / target_hz`, or the clamped divider as
+/// the error.
+fn clkdiv(sys_hz: u32, target_hz: u32) -> Result<(u16, u8), (u16, u8)> {
+    const MIN: u64 = 1 << 8;
+    const MAX: u64 = (1 << 24) - 1;
+    assert!(target_hz > 0, "PIO target frequency must not be zero");
+    let target_hz = target_hz as u64;
+    let divider = ((sys_hz as u64) * 256 + target_hz / 2) / target_hz;
+    let clamped = divider.clamp(MIN, MAX);
+    let parts = ((clamped >> 8) as u16, clamped as u8);
+    if clamped == divider {
+        Ok(parts)
+    } else {
+        Err(parts)
+    }
+}
+
+type SourceHandlers = [Option<fn(InterruptSource)>; NUM_SOURCES];
+
+static mut HANDLERS: [[SourceHandlers; NUM_IRQ_LINES]; NUM_INSTANCES] = [[[None; NUM_SOURCES]; NUM_IRQ_LINES]; NUM_INSTANCES];
+static mut HANDLER_INSTALLED: [[bool; NUM_IRQ_LINES]; NUM_INSTANCES] = [[false; NUM_IRQ_LINES]; NUM_INSTANCES];
+
+#[cfg(not(pico_platform = "rp2350"))]
+const TRAMPOLINES: [[extern "C" fn(); NUM_IRQ_LINES]; NUM_INSTANCES] = [
+    [trampoline::<0, 0>, trampoline::<0, 1>],
+    [trampoline::<1, 0>, trampoline::<1, 1>],
+];
+#[cfg(pico_platform = "rp2350")]
+const TRAMPOLINES: [[extern "C" fn(); NUM_IRQ_LINES]; NUM_INSTANCES] = [
+    [trampoline::<0, 0>, trampoline::<0, 1>],
+    [trampoline::<1, 0>, trampoline::<1, 1>],
+    [trampoline::<2, 0>, trampoline::<2, 1>],
+];
+
+extern "C" fn trampoline<const INSTANCE: usize, const LINE: usize>() {
+    let pio = unsafe { sys::wrapped_pio_get_instance(INSTANCE as u32) };
+    for bit in 0..NUM_SOURCES as u32 {
+        let handler = match unsafe { (*addr_of!(HANDLERS))[INSTANCE][LINE][bit as usize] } {
+            Some(handler) => handler,
+            None => continue,
+        };
+        let source = InterruptSource::from_bit(bit);
+        if !source.is_active(pio) {
+            continue;
+        }
+        if let InterruptSource::Flag(flag) = source {
+            unsafe { sys::wrapped_pio_interrupt_clear(pio, flag) };
+        }
+        handler(source);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    fn handler(_: InterruptSource) {}
+
+    #[test]
+    fn sources_map_to_their_intr_bits() {
+        assert_eq!(InterruptSource::RxFifoNotEmpty(0).bit(), 0);
+        assert_eq!(InterruptSource::RxFifoNotEmpty(3).bit(), 3);
+        assert_eq!(InterruptSource::TxFifoNotFull(0).bit(), 4);
+        assert_eq!(InterruptSource::TxFifoNotFull(3).bit(), 7);
+        assert_eq!(InterruptSource::Flag(0).bit(), 8);
+        assert_eq!(InterruptSource::Flag(3).bit(), 11);
+        for bit in 0..NUM_SOURCES as u32 {
+            assert_eq!(InterruptSource::from_bit(bit).bit(), bit);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid PIO interrupt source")]
+    fn sources_beyond_the_fourth_are_rejected() {
+        InterruptSource::Flag(4).validate();
+    }
+
+    #[test]
+    fn the_interrupt_is_taken_from_the_sdk_and_shared() {
+        let calls = record_calls(|| {
+            let mut pio = Pio::new(0);
+            pio.set_interrupt_handler(1, InterruptSource::Flag(0), Some(handler));
+            pio.set_interrupt_handler(1, InterruptSource::Flag(0), None);
+        });
+        assert_eq!(
+            calls,
+            [
+                "wrapped_pio_get_instance",
+                "wrapped_pio_get_irq_num",
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_irq_add_shared_handler",
+                "wrapped_irq_set_enabled",
+                "wrapped_restore_interrupts",
+                "wrapped_pio_set_irqn_source_enabled",
+                "wrapped_pio_get_irq_num",
+                "wrapped_pio_set_irqn_source_enabled",
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_irq_remove_handler",
+                "wrapped_irq_has_shared_handler",
+                "wrapped_irq_set_enabled",
+                "wrapped_restore_interrupts",
+            ]
+        );
+    }
+
+    #[test]
+    fn clkdiv_rounds_to_the_nearest_256th() {
+        assert_eq!(clkdiv(125_000_000, 1_000_000), Ok((125, 0)));
+        // 41.6666 is 41 + 170.67 / 256.
+        assert_eq!(clkdiv(125_000_000, 3_000_000), Ok((41, 171)));
+        assert_eq!(clkdiv(125_000_000, 125_000_000), Ok((1, 0)));
+    }
+
+    #[test]
+    fn clkdiv_clamps_unreachable_targets() {
+        assert_eq!(clkdiv(125_000_000, 200_000_000), Err((1, 0)));
+        assert_eq!(clkdiv(125_000_000, 1), Err((u16::MAX, u8::MAX)));
+    }
+
+    #[test]
+    fn the_divider_follows_the_system_clock() {
+        // The stubs report a stopped system clock, which no divider reaches.
+        let calls = record_calls(|| {
+            assert_eq!(clkdiv_from_hz(1_000_000), (1, 0));
+            assert_eq!(clkdiv_from_hz_checked(1_000_000), None);
+        });
+        assert_eq!(calls, ["wrapped_clock_get_hz", "wrapped_clock_get_hz"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "PIO target frequency must not be zero")]
+    fn a_zero_target_is_rejected() {
+        let _ = clkdiv(125_000_000, 0);
+    }
+}
@@ -0,0 +1,173 @@
+use raspi_pico_sdk_sys as sys;
+
+use crate::error::PicoError;
+
+/// Number of GPIO wakeup slots of the power manager.
+pub const NUM_GPIO_WAKEUPS: u32 = 4;
+
+/// The power domains of the RP2350, valued as `enum powman_power_domains`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum PowerDomain {
+    SramBank1 = 0,
+    SramBank0 = 1,
+    XipCache = 2,
+    /// The processors, the bus fabric and the peripherals. Switching it off is the deepest
+    /// low-power state, which only the power manager's timer and GPIO wakeups survive.
+    SwitchedCore = 3,
+}
+
+/// Which power domains are on, a `powman_power_state`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerState(u32);
+
+impl PowerState {
+    /// Every domain off.
+    pub const OFF: PowerState = PowerState(0);
+    /// Every domain on, the state after boot.
+    pub const ON: PowerState = PowerState(0xf);
+
+    pub fn from_bits(bits: u32) -> PowerState {
+        PowerState(bits)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn with_domain_on(self, domain: PowerDomain) -> PowerState {
+        PowerState(self.0 | 1 << domain as u32)
+    }
+
+    pub fn with_domain_off(self, domain: PowerDomain) -> PowerState {
+        PowerState(self.0 & !(1 << domain as u32))
+    }
+
+    pub fn is_domain_on(&self, domain: PowerDomain) -> bool {
+        self.0 & 1 << domain as u32 != 0
+    }
+}
+
+/// The current power state (`powman_get_power_state`).
+pub fn power_state() -> PowerState {
+    PowerState(unsafe { sys::wrapped_powman_get_power_state() })
+}
+
+/// Switches to `state` (`powman_set_power_state`). With [`PowerDomain::SwitchedCore`] off,
+/// this only returns if the switch was refused; the chip restarts from the wakeup state set
+/// with [`configure_wakeup_state`] once a wakeup fires.
+pub fn set_power_state(state: PowerState) -> Result<(), PicoError> {
+    PicoError::check(unsafe { sys::wrapped_powman_set_power_state(state.0) }).map(|_| ())
+}
+
+/// Sets the state to enter in [`set_power_state`] with `sleep` and the one to wake up to, or
+/// fails with [`PicoError::InvalidArg`] if the hardware cannot go between them.
+pub fn configure_wakeup_state(sleep: PowerState, wakeup: PowerState) -> Result<(), PicoError> {
+    if unsafe { sys::wrapped_powman_configure_wakeup_state(sleep.0, wakeup.0) } {
+        Ok(())
+    } else {
+        Err(PicoError::InvalidArg)
+    }
+}
+
+/// The power manager's millisecond timer, which keeps running in every power state.
+pub struct PowmanTimer {
+    _private: (),
+}
+
+impl PowmanTimer {
+    /// Drives the timer from the low-power oscillator, which keeps running with the crystal
+    /// off at the cost of accuracy (`powman_timer_set_1khz_tick_source_lposc`), and starts it.
+    pub fn from_lposc() -> PowmanTimer {
+        unsafe {
+            sys::wrapped_powman_timer_set_1khz_tick_source_lposc();
+            sys::wrapped_powman_timer_start();
+        }
+        PowmanTimer { _private: () }
+    }
+
+    /// Drives the timer from the crystal (`powman_timer_set_1khz_tick_source_xosc`) and starts
+    /// it.
+    pub fn from_xosc() -> PowmanTimer {
+        unsafe {
+            sys::wrapped_powman_timer_set_1khz_tick_source_xosc();
+            sys::wrapped_powman_timer_start();
+        }
+        PowmanTimer { _private: () }
+    }
+
+    pub fn ms(&self) -> u64 {
+        unsafe { sys::wrapped_powman_timer_get_ms() }
+    }
+
+    pub fn set_ms(&mut self, ms: u64) {
+        unsafe { sys::wrapped_powman_timer_set_ms(ms) }
+    }
+
+    pub fn is_running(&self) -> bool {
+        unsafe { sys::wrapped_powman_timer_is_running() }
+    }
+
+    /// Wakes the chip from a low-power state when the timer reaches `ms`.
+    pub fn enable_alarm_wakeup_at_ms(&mut self, ms: u64) {
+        unsafe { sys::wrapped_powman_enable_alarm_wakeup_at_ms(ms) }
+    }
+
+    pub fn disable_alarm_wakeup(&mut self) {
+        unsafe { sys::wrapped_powman_disable_alarm_wakeup() }
+    }
+
+    /// Stops the timer.
+    pub fn stop(self) {
+        unsafe { sys::wrapped_powman_timer_stop() }
+    }
+}
+
+/// Wakes the chip from a low-power state on an edge or level of `gpio`, using wakeup slot
+/// `slot`.
+pub fn enable_gpio_wakeup(slot: u32, gpio: u32, edge: bool, high: bool) {
+    assert!(slot < NUM_GPIO_WAKEUPS, "invalid powman GPIO wakeup {}", slot);
+    unsafe { sys::wrapped_powman_enable_gpio_wakeup(slot, gpio, edge, high) }
+}
+
+pub fn disable_gpio_wakeup(slot: u32) {
+    assert!(slot < NUM_GPIO_WAKEUPS, "invalid powman GPIO wakeup {}", slot);
+    unsafe { sys::wrapped_powman_disable_gpio_wakeup(slot) }
+}
+
+/// Disables the alarm and every GPIO wakeup.
+pub fn disable_all_wakeups() {
+    unsafe { sys::wrapped_powman_disable_all_wakeups() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    fn domains_are_the_bits_of_the_state() {
+        let state = PowerState::ON.with_domain_off(PowerDomain::SwitchedCore).with_domain_off(PowerDomain::SramBank1);
+        assert_eq!(state.bits(), 0b0110);
+        assert!(state.is_domain_on(PowerDomain::XipCache) && state.is_domain_on(PowerDomain::SramBank0));
+        assert!(!state.is_domain_on(PowerDomain::SwitchedCore) && !state.is_domain_on(PowerDomain::SramBank1));
+        assert_eq!(state.with_domain_on(PowerDomain::SwitchedCore).with_domain_on(PowerDomain::SramBank1), PowerState::ON);
+        assert_eq!(PowerState::from_bits(0b1000), PowerState::OFF.with_domain_on(PowerDomain::SwitchedCore));
+    }
+
+    #[test]
+    fn a_refused_wakeup_state_is_an_invalid_argument() {
+        let calls = record_calls(|| {
+            // The stubs refuse every pair of states.
+            assert_eq!(configure_wakeup_state(PowerState::OFF, PowerState::ON), Err(PicoError::InvalidArg));
+            assert_eq!(set_power_state(PowerState::ON), Ok(()));
+        });
+        assert_eq!(calls, ["wrapped_powman_configure_wakeup_state", "wrapped_powman_set_power_state"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid powman GPIO wakeup 4")]
+    fn only_four_gpio_wakeups_exist() {
+        enable_gpio_wakeup(NUM_GPIO_WAKEUPS, 3, true, true);
+    }
+}
@@ -0,0 +1,233 @@
+use core::ptr::{addr_of, addr_of_mut};
+
+use raspi_pico_sdk_sys as sys;
+
+use crate::irq;
+
+/// 8 on the RP2040, 12 on the RP2350.
+pub const NUM_SLICES: usize = sys::NUM_PWM_SLICES as usize;
+
+pub fn gpio_to_slice_num(gpio: u32) -> u32 {
+    unsafe { sys::wrapped_pwm_gpio_to_slice_num(gpio) }
+}
+
+/// Enables the wrap interrupt of `slice` on the shared `PWM_IRQ_WRAP` line.
+pub fn set_irq_enabled(slice: u32, enabled: bool) {
+    unsafe { sys::wrapped_pwm_set_irq_enabled(slice, enabled) }
+}
+
+static mut WRAP_HANDLERS: [Option<fn(u32)>; NUM_SLICES] = [None; NUM_SLICES];
+static mut WRAP_HANDLER_INSTALLED: bool = false;
+
+/// The slices of the wrap `status` mask that have a handler. The others are left pending for
+/// whoever else handles `PWM_IRQ_WRAP`.
+fn handled_slices(status: u32, handlers: &[Option<fn(u32)>; NUM_SLICES]) -> u32 {
+    let registered = (0..NUM_SLICES)
+        .filter(|&slice| handlers[slice].is_some())
+        .fold(0, |mask, slice| mask | 1 << slice);
+    status & registered
+}
+
+extern "C" fn wrap_trampoline() {
+    let handlers = unsafe { &*addr_of!(WRAP_HANDLERS) };
+    let handled = handled_slices(unsafe { sys::wrapped_pwm_get_irq_status_mask() }, handlers);
+    for slice in 0..NUM_SLICES as u32 {
+        if handled & (1 << slice) == 0 {
+            continue;
+        }
+        unsafe { sys::wrapped_pwm_clear_irq(slice) };
+        if let Some(handler) = handlers[slice as usize] {
+            handler(slice);
+        }
+    }
+}
+
+/// Calls `handler` with the slice number every time `slice` wraps, or removes the handler
+/// when `None` is given.
+///
+/// All slices share `PWM_IRQ_WRAP`. While any slice has a handler, a dispatcher is added to
+/// the line's shared handlers, which clears each such slice's interrupt before calling its
+/// handler and leaves the other slices to other handlers of the line.
+pub fn set_wrap_handler(slice: u32, handler: Option<fn(u32)>) {
+    assert!((slice as usize) < NUM_SLICES, "invalid PWM slice {}", slice);
+    if handler.is_none() {
+        set_irq_enabled(slice, false);
+    }
+    irq::interrupt_free(|| unsafe {
+        let handlers = &mut *addr_of_mut!(WRAP_HANDLERS);
+        handlers[slice as usize] = handler;
+        let needed = handlers.iter().any(Option::is_some);
+        let installed = &mut *addr_of_mut!(WRAP_HANDLER_INSTALLED);
+        irq::update_shared_handler(irq::PWM_IRQ_WRAP, wrap_trampoline, installed, needed);
+    });
+    if handler.is_some() {
+        set_irq_enabled(slice, true);
+    }
+}
+
+/// `GPIO_FUNC_PWM` of `enum gpio_function`.
+const GPIO_FUNC_PWM: u32 = 4;
+/// `PWM_CHAN_B` of `pwm_gpio_to_channel`; only the B pin of a slice is an input.
+const PWM_CHAN_B: u32 = 1;
+/// Values of `enum pwm_clkdiv_mode`.
+const PWM_DIV_B_HIGH: u32 = 1;
+const PWM_DIV_B_RISING: u32 = 2;
+/// Largest integer divider of a slice.
+const MAX_CLKDIV: u32 = 255;
+
+/// Measures the frequency in Hz and the duty cycle (the fraction of time high, from 0 to 1) of
+/// the signal on `gpio`, which has to be the B pin (odd numbered) of its slice.
+///
+/// The slice first counts the system clock while the input is high, then its rising edges,
+/// each for `gate_us`. Its counter holds 16 bits: the level count picks a divider to fit, but
+/// more than 65535 edges in the gate wrap, so keep the frequency below `65535 / gate` and the
+/// gate long enough for several periods. The whole slice is taken over, also its A channel,
+/// and left disabled.
+pub fn measure_input(gpio: u32, gate_us: u32) -> (u32, f32) {
+    assert!(gate_us > 0, "PWM measurement gate must not be zero");
+    assert_eq!(
+        unsafe { sys::wrapped_pwm_gpio_to_channel(gpio) },
+        PWM_CHAN_B,
+        "GPIO {} is not the B pin of a PWM slice",
+        gpio
+    );
+    let slice = gpio_to_slice_num(gpio);
+    let sys_hz = crate::clocks::sys_clock_hz();
+    let clock_counts = sys_hz as u64 * gate_us as u64 / 1_000_000;
+    let divider = clock_counts.div_ceil(u16::MAX as u64).max(1);
+    assert!(
+        divider <= MAX_CLKDIV as u64,
+        "a PWM measurement gate of {} us overflows the counter even at the largest divider",
+        gate_us
+    );
+    unsafe { sys::wrapped_gpio_set_function(gpio, GPIO_FUNC_PWM as _) };
+    let high_count = count_over_gate(slice, PWM_DIV_B_HIGH, divider as u32, gate_us);
+    let edges = count_over_gate(slice, PWM_DIV_B_RISING, 1, gate_us);
+    let frequency_hz = (edges as u64 * 1_000_000 / gate_us as u64) as u32;
+    (frequency_hz, duty_from_counts(high_count, divider as u32, clock_counts))
+}
+
+/// Fraction of the gate the input was high: `high_count` counts of the system clock divided
+/// by `divider`, out of the `clock_counts` system clock cycles of the gate.
+pub fn duty_from_counts(high_count: u16, divider: u32, clock_counts: u64) -> f32 {
+    if clock_counts == 0 {
+        return 0.0;
+    }
+    let duty = (high_count as u64 * divider as u64) as f32 / clock_counts as f32;
+    // The counter runs in whole divider steps, so a steady high level can overshoot slightly.
+    duty.min(1.0)
+}
+
+/// Runs `slice` in the gated counting `mode` for `gate_us` and returns where the counter got.
+fn count_over_gate(slice: u32, mode: u32, divider: u32, gate_us: u32) -> u16 {
+    unsafe {
+        let mut config = sys::wrapped_pwm_get_default_config();
+        sys::wrapped_pwm_config_set_clkdiv_mode(&mut config, mode as _);
+        sys::wrapped_pwm_config_set_clkdiv_int(&mut config, divider);
+        // Resets the counter and keeps the slice stopped until the gate opens.
+        sys::wrapped_pwm_init(slice, &mut config, false);
+        sys::wrapped_pwm_set_enabled(slice, true);
+        sys::wrapped_busy_wait_us_32(gate_us);
+        sys::wrapped_pwm_set_enabled(slice, false);
+        sys::wrapped_pwm_get_counter(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    fn handler(_: u32) {}
+
+    #[test]
+    fn only_slices_with_a_handler_are_dispatched() {
+        let mut handlers = [None; NUM_SLICES];
+        assert_eq!(handled_slices(0xff, &handlers), 0);
+        handlers[1] = Some(handler as fn(u32));
+        handlers[5] = Some(handler as fn(u32));
+        assert_eq!(handled_slices(0xff, &handlers), 0b10_0010);
+        assert_eq!(handled_slices(0b10_0001, &handlers), 0b10_0000);
+    }
+
+    #[test]
+    fn the_dispatcher_is_shared_while_a_slice_has_a_handler() {
+        let calls = record_calls(|| {
+            set_wrap_handler(0, Some(handler));
+            set_wrap_handler(1, Some(handler));
+            set_wrap_handler(0, None);
+        });
+        assert_eq!(
+            calls,
+            [
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_irq_add_shared_handler",
+                "wrapped_irq_set_enabled",
+                "wrapped_restore_interrupts",
+                "wrapped_pwm_set_irq_enabled",
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_restore_interrupts",
+                "wrapped_pwm_set_irq_enabled",
+                "wrapped_pwm_set_irq_enabled",
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_restore_interrupts",
+            ]
+        );
+        let calls = record_calls(|| set_wrap_handler(1, None));
+        assert_eq!(
+            calls,
+            [
+                "wrapped_pwm_set_irq_enabled",
+                "wrapped_save_and_disable_interrupts",
+                "wrapped_irq_remove_handler",
+                "wrapped_irq_has_shared_handler",
+                "wrapped_irq_set_enabled",
+                "wrapped_restore_interrupts",
+            ]
+        );
+    }
+
+    #[test]
+    fn duty_is_the_high_time_over_the_gate() {
+        assert_eq!(duty_from_counts(0, 1, 1000), 0.0);
+        assert_eq!(duty_from_counts(250, 2, 1000), 0.5);
+        assert_eq!(duty_from_counts(1000, 1, 1000), 1.0);
+    }
+
+    #[test]
+    fn duty_is_capped_at_a_steady_high_level() {
+        assert_eq!(duty_from_counts(501, 2, 1000), 1.0);
+        assert_eq!(duty_from_counts(10, 1, 0), 0.0);
+    }
+
+    #[test]
+    fn the_counter_runs_only_while_the_gate_is_open() {
+        let calls = record_calls(|| assert_eq!(count_over_gate(1, PWM_DIV_B_RISING, 1, 1000), 0));
+        assert_eq!(
+            calls,
+            [
+                "wrapped_pwm_get_default_config",
+                "wrapped_pwm_config_set_clkdiv_mode",
+                "wrapped_pwm_config_set_clkdiv_int",
+                "wrapped_pwm_init",
+                "wrapped_pwm_set_enabled",
+                "wrapped_busy_wait_us_32",
+                "wrapped_pwm_set_enabled",
+                "wrapped_pwm_get_counter",
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "GPIO 2 is not the B pin of a PWM slice")]
+    fn only_b_pins_are_measured() {
+        // The stubs put every pin on channel A.
+        measure_input(2, 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "PWM measurement gate must not be zero")]
+    fn the_gate_must_not_be_zero() {
+        measure_input(3, 0);
+    }
+}
@@ -0,0 +1,166 @@
+use raspi_pico_sdk_sys as sys;
+
+/// Calendar time as kept by the RTC. `dotw` is the day of the week, 0 being Sunday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i16,
+    pub month: i8,
+    pub day: i8,
+    pub dotw: i8,
+    pub hour: i8,
+    pub min: i8,
+    pub sec: i8,
+}
+
+impl From<DateTime> for sys::datetime_t {
+    fn from(t: DateTime) -> Self {
+        sys::datetime_t {
+            year: t.year,
+            month: t.month,
+            day: t.day,
+            dotw: t.dotw,
+            hour: t.hour,
+            min: t.min,
+            sec: t.sec,
+        }
+    }
+}
+
+impl From<sys::datetime_t> for DateTime {
+    fn from(t: sys::datetime_t) -> Self {
+        DateTime {
+            year: t.year,
+            month: t.month,
+            day: t.day,
+            dotw: t.dotw,
+            hour: t.hour,
+            min: t.min,
+            sec: t.sec,
+        }
+    }
+}
+
+/// Alarm condition for [`set_alarm`]. Fields left as `None` match any value, so e.g.
+/// [`AlarmSpec::daily_at`] fires every day at the given time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AlarmSpec {
+    pub year: Option<i16>,
+    pub month: Option<i8>,
+    pub day: Option<i8>,
+    pub dotw: Option<i8>,
+    pub hour: Option<i8>,
+    pub min: Option<i8>,
+    pub sec: Option<i8>,
+}
+
+impl AlarmSpec {
+    pub fn daily_at(hour: i8, min: i8, sec: i8) -> Self {
+        AlarmSpec {
+            hour: Some(hour),
+            min: Some(min),
+            sec: Some(sec),
+            ..AlarmSpec::default()
+        }
+    }
+
+    pub fn weekly_at(dotw: i8, hour: i8, min: i8, sec: i8) -> Self {
+        AlarmSpec {
+            dotw: Some(dotw),
+            ..AlarmSpec::daily_at(hour, min, sec)
+        }
+    }
+
+    /// Encodes the spec the way `rtc_set_alarm` expects, with -1 as the "match any" wildcard.
+    pub fn to_datetime(&self) -> sys::datetime_t {
+        sys::datetime_t {
+            year: self.year.unwrap_or(-1),
+            month: self.month.unwrap_or(-1),
+            day: self.day.unwrap_or(-1),
+            dotw: self.dotw.unwrap_or(-1),
+            hour: self.hour.unwrap_or(-1),
+            min: self.min.unwrap_or(-1),
+            sec: self.sec.unwrap_or(-1),
+        }
+    }
+}
+
+static mut ALARM_HANDLER: Option<fn()> = None;
+
+extern "C" fn alarm_trampoline() {
+    if let Some(handler) = unsafe { ALARM_HANDLER } {
+        handler();
+    }
+}
+
+pub fn init() {
+    unsafe { sys::wrapped_rtc_init() }
+}
+
+/// Sets the current time, returning `false` if `t` is not a valid date.
+pub fn set_datetime(t: DateTime) -> bool {
+    let t = sys::datetime_t::from(t);
+    unsafe { sys::wrapped_rtc_set_datetime(&t) }
+}
+
+pub fn get_datetime() -> Option<DateTime> {
+    let mut t: sys::datetime_t = unsafe { core::mem::zeroed() };
+    if unsafe { sys::wrapped_rtc_get_datetime(&mut t) } {
+        Some(t.into())
+    } else {
+        None
+    }
+}
+
+pub fn running() -> bool {
+    unsafe { sys::wrapped_rtc_running() }
+}
+
+/// Calls `handler` from the RTC interrupt whenever the current time matches `spec`.
+pub fn set_alarm(spec: &AlarmSpec, handler: fn()) {
+    let t = spec.to_datetime();
+    unsafe {
+        sys::wrapped_rtc_disable_alarm();
+        ALARM_HANDLER = Some(handler);
+        sys::wrapped_rtc_set_alarm(&t, Some(alarm_trampoline));
+    }
+}
+
+pub fn enable_alarm() {
+    unsafe { sys::wrapped_rtc_enable_alarm() }
+}
+
+pub fn disable_alarm() {
+    unsafe { sys::wrapped_rtc_disable_alarm() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    fn datetime(year: i16, month: i8, day: i8, dotw: i8, hour: i8, min: i8, sec: i8) -> DateTime {
+        DateTime { year, month, day, dotw, hour, min, sec }
+    }
+
+    #[test]
+    fn unset_alarm_fields_are_encoded_as_the_wildcard() {
+        assert_eq!(DateTime::from(AlarmSpec::default().to_datetime()), datetime(-1, -1, -1, -1, -1, -1, -1));
+        assert_eq!(DateTime::from(AlarmSpec::daily_at(7, 30, 0).to_datetime()), datetime(-1, -1, -1, -1, 7, 30, 0));
+        assert_eq!(DateTime::from(AlarmSpec::weekly_at(1, 7, 30, 0).to_datetime()), datetime(-1, -1, -1, 1, 7, 30, 0));
+        let spec = AlarmSpec { year: Some(2024), month: Some(2), day: Some(29), ..AlarmSpec::default() };
+        assert_eq!(DateTime::from(spec.to_datetime()), datetime(2024, 2, 29, -1, -1, -1, -1));
+    }
+
+    #[test]
+    fn datetime_round_trips_through_the_sdk_struct() {
+        let t = datetime(2021, 1, 28, 4, 23, 59, 58);
+        assert_eq!(DateTime::from(sys::datetime_t::from(t)), t);
+    }
+
+    #[test]
+    fn alarm_is_disabled_while_it_is_changed() {
+        fn handler() {}
+        let calls = record_calls(|| set_alarm(&AlarmSpec::daily_at(7, 30, 0), handler));
+        assert_eq!(calls, ["wrapped_rtc_disable_alarm", "wrapped_rtc_set_alarm"]);
+    }
+}
@@ -0,0 +1,186 @@
+use core::cell::UnsafeCell;
+
+use raspi_pico_sdk_sys as sys;
+
+use crate::sync::SpinLock;
+
+/// A bus shared by several devices, e.g. an [`crate::spi::Spi`] or [`crate::i2c::I2c`] in a
+/// static used from both cores.
+///
+/// Every access takes the spin lock for its whole duration, which also disables interrupts on
+/// the current core, so accesses never interleave and are not preempted. Keep them short, and
+/// never access the same bus again from within an access: that deadlocks.
+pub struct SharedBus<B> {
+    bus: UnsafeCell<B>,
+    lock: SpinLock,
+}
+
+// The spin lock serializes all accesses to the bus, from both cores.
+unsafe impl<B: Send> Sync for SharedBus<B> {}
+
+impl<B> SharedBus<B> {
+    pub fn new(bus: B, lock: SpinLock) -> SharedBus<B> {
+        SharedBus { bus: UnsafeCell::new(bus), lock }
+    }
+
+    /// Runs `f` with exclusive access to the bus.
+    pub fn with_bus<R>(&self, f: impl FnOnce(&mut B) -> R) -> R {
+        let _guard = self.lock.lock();
+        f(unsafe { &mut *self.bus.get() })
+    }
+
+    /// A device that only needs its accesses serialized, such as one on an I2C bus.
+    pub fn device(&self) -> BusDevice<'_, B> {
+        BusDevice { bus: self }
+    }
+
+    /// A device selected by the active-low chip select on `cs_gpio`, which is configured as an
+    /// output and deasserted.
+    pub fn spi_device(&self, cs_gpio: u32) -> SpiDevice<'_, B> {
+        unsafe {
+            sys::wrapped_gpio_init(cs_gpio);
+            sys::wrapped_gpio_put(cs_gpio, true);
+            sys::wrapped_gpio_set_dir(cs_gpio, true);
+        }
+        SpiDevice { bus: self, cs_gpio }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.bus.into_inner()
+    }
+}
+
+/// A device on a [`SharedBus`] without a select line.
+pub struct BusDevice<'a, B> {
+    bus: &'a SharedBus<B>,
+}
+
+impl<B> BusDevice<'_, B> {
+    pub fn with_bus<R>(&mut self, f: impl FnOnce(&mut B) -> R) -> R {
+        self.bus.with_bus(f)
+    }
+}
+
+/// A device on a shared SPI bus with its own chip select.
+pub struct SpiDevice<'a, B> {
+    bus: &'a SharedBus<B>,
+    cs_gpio: u32,
+}
+
+impl<B> SpiDevice<'_, B> {
+    pub fn cs_gpio(&self) -> u32 {
+        self.cs_gpio
+    }
+
+    /// Runs `f` with exclusive access to the bus while the chip select is asserted. `f` has to
+    /// wait for the bus to finish (e.g. `flush`) before returning, or the end of the transfer
+    /// is cut off.
+    pub fn with_bus<R>(&mut self, f: impl FnOnce(&mut B) -> R) -> R {
+        let cs_gpio = self.cs_gpio;
+        self.bus.with_bus(|bus| {
+            unsafe { sys::wrapped_gpio_put(cs_gpio, false) };
+            let result = f(bus);
+            unsafe { sys::wrapped_gpio_put(cs_gpio, true) };
+            result
+        })
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+mod hal {
+    use embedded_hal::i2c::{self, I2c};
+    use embedded_hal::spi::{self, Operation, SpiBus};
+
+    use super::{BusDevice, SpiDevice};
+    use crate::spi::run_operations;
+
+    impl<B: i2c::ErrorType> i2c::ErrorType for BusDevice<'_, B> {
+        type Error = B::Error;
+    }
+
+    impl<B: I2c> I2c for BusDevice<'_, B> {
+        fn transaction(&mut self, address: u8, operations: &mut [i2c::Operation<'_>]) -> Result<(), Self::Error> {
+            self.with_bus(|bus| bus.transaction(address, operations))
+        }
+    }
+
+    impl<B: spi::ErrorType> spi::ErrorType for SpiDevice<'_, B> {
+        type Error = B::Error;
+    }
+
+    impl<B: SpiBus<Word>, Word: Copy + 'static> spi::SpiDevice<Word> for SpiDevice<'_, B> {
+        fn transaction(&mut self, operations: &mut [Operation<'_, Word>]) -> Result<(), Self::Error> {
+            self.with_bus(|bus| {
+                let result = run_operations(bus, operations);
+                // The chip select must stay asserted until the last word is out, even after an
+                // error.
+                let flushed = bus.flush();
+                result.and(flushed)
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    fn shared_bus() -> SharedBus<Vec<u8>> {
+        SharedBus::new(Vec::new(), SpinLock::claim_unused().unwrap())
+    }
+
+    #[test]
+    fn every_device_access_holds_the_lock() {
+        let bus = shared_bus();
+        let (mut first, mut second) = (bus.device(), bus.device());
+        let calls = record_calls(|| {
+            first.with_bus(|bus| bus.push(1));
+            second.with_bus(|bus| bus.push(2));
+        });
+        assert_eq!(
+            calls,
+            ["wrapped_spin_lock_blocking", "wrapped_spin_unlock", "wrapped_spin_lock_blocking", "wrapped_spin_unlock"]
+        );
+        assert_eq!(bus.into_inner(), [1, 2]);
+    }
+
+    #[test]
+    fn the_chip_select_is_asserted_only_inside_the_lock() {
+        let bus = shared_bus();
+        let mut device = None;
+        let calls = record_calls(|| device = Some(bus.spi_device(17)));
+        assert_eq!(calls, ["wrapped_gpio_init", "wrapped_gpio_put", "wrapped_gpio_set_dir"]);
+        let mut device = device.unwrap();
+        assert_eq!(device.cs_gpio(), 17);
+        let calls = record_calls(|| assert_eq!(device.with_bus(|bus| bus.len()), 0));
+        assert_eq!(
+            calls,
+            ["wrapped_spin_lock_blocking", "wrapped_gpio_put", "wrapped_gpio_put", "wrapped_spin_unlock"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-hal")]
+    fn spi_transactions_flush_before_deselecting() {
+        use embedded_hal::spi::{Operation, SpiDevice as _};
+
+        let bus = SharedBus::new(crate::spi::Spi::new(0, 1_000_000), SpinLock::claim_unused().unwrap());
+        let mut device = bus.spi_device(5);
+        let calls = record_calls(|| {
+            device.transaction(&mut [Operation::Write(&[1, 2]), Operation::Read(&mut [0; 2])]).unwrap();
+        });
+        assert_eq!(
+            calls,
+            [
+                "wrapped_spin_lock_blocking",
+                "wrapped_gpio_put",
+                "wrapped_spi_write_blocking",
+                "wrapped_spi_read_blocking",
+                "wrapped_spi_is_busy",
+                "wrapped_gpio_put",
+                "wrapped_spin_unlock",
+            ]
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use core::ptr::{addr_of_mut, read_volatile, write_volatile};
+
+use raspi_pico_sdk_sys as sys;
+
+#[cfg(pico_platform = "rp2040")]
+use crate::rtc::DateTime;
+
+/// `scb_hw->scr` (SLEEPDEEP and friends), which the sleep functions change.
+const SCB_SCR: *mut u32 = (0xe000_0000_usize + 0xed10) as *mut u32;
+/// `clocks_hw`, whose `sleep_en0` and `sleep_en1` select the clocks kept running while asleep.
+const CLOCKS: *mut sys::clocks_hw_t = sys::CLOCKS_BASE as *mut sys::clocks_hw_t;
+
+/// What wakes the chip from [`dormant_until_gpio`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DormantWake {
+    RisingEdge,
+    FallingEdge,
+    High,
+    Low,
+}
+
+impl DormantWake {
+    /// The `edge` and `high` arguments of `sleep_goto_dormant_until_pin`.
+    fn edge_and_high(self) -> (bool, bool) {
+        match self {
+            DormantWake::RisingEdge => (true, true),
+            DormantWake::FallingEdge => (true, false),
+            DormantWake::High => (false, true),
+            DormantWake::Low => (false, false),
+        }
+    }
+}
+
+/// Stops every clock, including the crystal oscillator, until `wake` is seen on `gpio`.
+///
+/// The system runs from the crystal while dormant and the clocks are set up again as after boot
+/// before this returns, so peripherals that depend on a clock configured differently (e.g. a
+/// UART baud rate after changing `clk_peri`) have to be reconfigured.
+pub fn dormant_until_gpio(gpio: u32, wake: DormantWake) {
+    assert!((gpio as usize) < crate::gpio::NUM_GPIOS, "invalid GPIO {}", gpio);
+    let (edge, high) = wake.edge_and_high();
+    with_clocks_restored(|| unsafe {
+        sys::wrapped_sleep_run_from_xosc();
+        sys::wrapped_sleep_goto_dormant_until_pin(gpio, edge, high);
+    });
+}
+
+/// Sleeps with only the RTC running until it reaches `datetime`. The RTC must have been
+/// initialized and set; the clocks are restored as for [`dormant_until_gpio`]. The RP2350 has no
+/// RTC; its power manager's timer wakes it instead ([`crate::powman`]).
+#[cfg(pico_platform = "rp2040")]
+pub fn sleep_until_rtc(datetime: DateTime) {
+    let mut datetime = sys::datetime_t::from(datetime);
+    with_clocks_restored(|| unsafe {
+        sys::wrapped_sleep_run_from_xosc();
+        sys::wrapped_sleep_goto_sleep_until(&mut datetime, Some(rtc_wake));
+    });
+}
+
+/// The RTC alarm only has to wake the core; the sleep functions handle the rest.
+#[cfg(pico_platform = "rp2040")]
+extern "C" fn rtc_wake() {}
+
+/// Runs `sleep` and then undoes what the sleep functions leave behind: the ring oscillator they
+/// stop, the deep sleep bits and the sleep clock enables, and the clock tree switched to the
+/// crystal.
+fn with_clocks_restored(sleep: impl FnOnce()) {
+    let (sleep_en0, sleep_en1) = unsafe { (addr_of_mut!((*CLOCKS).sleep_en0), addr_of_mut!((*CLOCKS).sleep_en1)) };
+    let (scr, saved_en0, saved_en1) = unsafe { (read_volatile(SCB_SCR), read_volatile(sleep_en0), read_volatile(sleep_en1)) };
+    sleep();
+    unsafe {
+        sys::wrapped_rosc_enable();
+        write_volatile(SCB_SCR, scr);
+        write_volatile(sleep_en0, saved_en0);
+        write_volatile(sleep_en1, saved_en1);
+        sys::wrapped_clocks_init();
+    }
+}
@@ -0,0 +1,414 @@
+use raspi_pico_sdk_sys as sys;
+
+use crate::delay::Delay;
+use crate::error::PicoError;
+
+pub const NUM_INSTANCES: usize = 2;
+
+pub struct Spi {
+    inst: *mut sys::spi_inst_t,
+}
+
+// The instance stands for a fixed peripheral, so it can be handed to the other core.
+unsafe impl Send for Spi {}
+
+impl Spi {
+    /// Initializes SPI instance `index` (0 or 1) at `baudrate` in mode 0 with 8 bit frames.
+    /// The pins still have to be switched to the SPI function by the caller.
+    pub fn new(index: u32, baudrate: u32) -> Spi {
+        assert!((index as usize) < NUM_INSTANCES, "invalid SPI instance {}", index);
+        let inst = unsafe { sys::wrapped_spi_get_instance(index) };
+        unsafe { sys::wrapped_spi_init(inst, baudrate) };
+        Spi { inst }
+    }
+
+    pub fn index(&self) -> u32 {
+        unsafe { sys::wrapped_spi_get_index(self.inst) }
+    }
+
+    pub fn raw(&self) -> *mut sys::spi_inst_t {
+        self.inst
+    }
+
+    pub fn write(&mut self, src: &[u8]) -> Result<(), PicoError> {
+        PicoError::check(unsafe { sys::wrapped_spi_write_blocking(self.inst, src.as_ptr(), src.len() as _) })?;
+        Ok(())
+    }
+
+    /// Reads into `dst` while sending `repeated_tx` for every byte.
+    pub fn read(&mut self, repeated_tx: u8, dst: &mut [u8]) -> Result<(), PicoError> {
+        PicoError::check(unsafe { sys::wrapped_spi_read_blocking(self.inst, repeated_tx, dst.as_mut_ptr(), dst.len() as _) })?;
+        Ok(())
+    }
+
+    /// Sends `src` while reading the same number of bytes into `dst`.
+    pub fn transfer(&mut self, src: &[u8], dst: &mut [u8]) -> Result<(), PicoError> {
+        assert_eq!(src.len(), dst.len(), "SPI transfer buffers differ in length");
+        PicoError::check(unsafe { sys::wrapped_spi_write_read_blocking(self.inst, src.as_ptr(), dst.as_mut_ptr(), src.len() as _) })?;
+        Ok(())
+    }
+
+    /// Sends `buffer` and replaces it with the bytes read back.
+    pub fn transfer_in_place(&mut self, buffer: &mut [u8]) -> Result<(), PicoError> {
+        // Every byte is pushed to the TX FIFO before the byte at the same position is read
+        // back, so the SDK can use one buffer for both directions.
+        let ptr = buffer.as_mut_ptr();
+        PicoError::check(unsafe { sys::wrapped_spi_write_read_blocking(self.inst, ptr, ptr, buffer.len() as _) })?;
+        Ok(())
+    }
+
+    /// Waits until the last frame has left the shift register.
+    pub fn flush(&mut self) {
+        while unsafe { sys::wrapped_spi_is_busy(self.inst) } {}
+    }
+}
+
+/// An [`Spi`] bus used by a single device, selected by the active-low chip select on a GPIO.
+pub struct SpiDevice {
+    spi: Spi,
+    cs_gpio: u32,
+    cs_setup_ns: u32,
+    cs_hold_ns: u32,
+}
+
+impl SpiDevice {
+    /// Takes over `spi` for the device on `cs_gpio`, which is configured as an output and
+    /// deasserted.
+    pub fn new(spi: Spi, cs_gpio: u32) -> SpiDevice {
+        unsafe {
+            sys::wrapped_gpio_init(cs_gpio);
+            sys::wrapped_gpio_put(cs_gpio, true);
+            sys::wrapped_gpio_set_dir(cs_gpio, true);
+        }
+        SpiDevice { spi, cs_gpio, cs_setup_ns: 0, cs_hold_ns: 0 }
+    }
+
+    pub fn cs_gpio(&self) -> u32 {
+        self.cs_gpio
+    }
+
+    /// Waits `setup_ns` after asserting the chip select before the first transfer, and
+    /// `hold_ns` after the last one has finished before deasserting it, for devices that
+    /// need more than the half clock period the SPI block allows.
+    pub fn set_cs_delays(&mut self, setup_ns: u32, hold_ns: u32) {
+        self.cs_setup_ns = setup_ns;
+        self.cs_hold_ns = hold_ns;
+    }
+
+    /// Runs `f` while the chip select is asserted. The bus is flushed before the chip select
+    /// is deasserted, so the last frame is not cut off.
+    pub fn with_bus<R>(&mut self, f: impl FnOnce(&mut Spi) -> R) -> R {
+        let mut delay = Delay::init();
+        unsafe { sys::wrapped_gpio_put(self.cs_gpio, false) };
+        if self.cs_setup_ns > 0 {
+            delay.delay_ns(self.cs_setup_ns);
+        }
+        let result = f(&mut self.spi);
+        self.spi.flush();
+        if self.cs_hold_ns > 0 {
+            delay.delay_ns(self.cs_hold_ns);
+        }
+        unsafe { sys::wrapped_gpio_put(self.cs_gpio, true) };
+        result
+    }
+
+    /// Returns the bus and the chip select GPIO, which stays a deasserted output.
+    pub fn free(self) -> (Spi, u32) {
+        (self.spi, self.cs_gpio)
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+pub(crate) use hal::run_operations;
+
+#[cfg(feature = "embedded-hal")]
+mod hal {
+    use embedded_hal::spi::{ErrorKind, ErrorType, Operation, SpiBus};
+
+    use super::{Spi, SpiDevice};
+    use crate::delay::Delay;
+    use crate::error::PicoError;
+
+    /// Byte sent while only reading.
+    const READ_FILL: u8 = 0;
+
+    impl embedded_hal::spi::Error for PicoError {
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    impl ErrorType for Spi {
+        type Error = PicoError;
+    }
+
+    impl SpiBus for Spi {
+        fn read(&mut self, words: &mut [u8]) -> Result<(), PicoError> {
+            Spi::read(self, READ_FILL, words)
+        }
+
+        fn write(&mut self, words: &[u8]) -> Result<(), PicoError> {
+            Spi::write(self, words)
+        }
+
+        /// Buffers of different length are allowed: the shorter side is padded by reading or
+        /// writing the rest of the longer one on its own.
+        fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), PicoError> {
+            let common = read.len().min(write.len());
+            Spi::transfer(self, &write[..common], &mut read[..common])?;
+            if write.len() > common {
+                Spi::write(self, &write[common..])
+            } else {
+                Spi::read(self, READ_FILL, &mut read[common..])
+            }
+        }
+
+        fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), PicoError> {
+            Spi::transfer_in_place(self, words)
+        }
+
+        fn flush(&mut self) -> Result<(), PicoError> {
+            Spi::flush(self);
+            Ok(())
+        }
+    }
+
+    impl ErrorType for SpiDevice {
+        type Error = PicoError;
+    }
+
+    impl embedded_hal::spi::SpiDevice for SpiDevice {
+        fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), PicoError> {
+            self.with_bus(|spi| run_operations(spi, operations))
+        }
+    }
+
+    /// Runs the operations of an `SpiDevice` transaction on `bus`, waiting for the bus to be
+    /// idle before each delay.
+    pub(crate) fn run_operations<B: SpiBus<Word>, Word: Copy + 'static>(bus: &mut B, operations: &mut [Operation<'_, Word>]) -> Result<(), B::Error> {
+        for operation in operations {
+            match operation {
+                Operation::Read(words) => bus.read(words)?,
+                Operation::Write(words) => bus.write(words)?,
+                Operation::Transfer(read, write) => bus.transfer(read, write)?,
+                Operation::TransferInPlace(words) => bus.transfer_in_place(words)?,
+                Operation::DelayNs(ns) => {
+                    bus.flush()?;
+                    Delay::init().delay_ns(*ns);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+pub use dma_async::DmaSpi;
+
+#[cfg(feature = "async")]
+mod dma_async {
+    use core::ffi::c_void;
+    use core::ptr::{addr_of_mut, read_volatile};
+
+    use embedded_hal::spi::ErrorType;
+    use embedded_hal_async::spi::SpiBus;
+    use raspi_pico_sdk_sys as sys;
+
+    use super::Spi;
+    use crate::dma::{self, DmaChannel, Transfer};
+    use crate::error::PicoError;
+
+    /// Byte sent while only reading, as for the blocking `SpiBus`.
+    const READ_FILL: u8 = 0;
+
+    /// An [`Spi`] whose transfers are done by two DMA channels and awaited through the DMA
+    /// completion interrupt. This takes over the completion handlers of both channels.
+    ///
+    /// Dropping a transfer's future aborts the DMA; bytes already in the FIFOs still go out, and
+    /// whatever is read back is discarded before the next transfer starts.
+    pub struct DmaSpi {
+        spi: Spi,
+        tx: DmaChannel,
+        rx: DmaChannel,
+    }
+
+    impl DmaSpi {
+        pub fn new(spi: Spi, tx: DmaChannel, rx: DmaChannel) -> DmaSpi {
+            DmaSpi { spi, tx, rx }
+        }
+
+        pub fn free(self) -> (Spi, DmaChannel, DmaChannel) {
+            (self.spi, self.tx, self.rx)
+        }
+
+        /// Sends `len` bytes from `write` while reading as many into `read`. Without a write
+        /// buffer [`READ_FILL`] is sent, without a read buffer the received bytes are dropped.
+        async fn run(&mut self, write: Option<*const u8>, read: Option<*mut u8>, len: usize) {
+            if len == 0 {
+                return;
+            }
+            let inst = self.spi.inst;
+            self.spi.flush();
+            while unsafe { sys::wrapped_spi_is_readable(inst) } {
+                unsafe { read_volatile(addr_of_mut!((*sys::wrapped_spi_get_hw(inst)).dr)) };
+            }
+            let data = unsafe { addr_of_mut!((*sys::wrapped_spi_get_hw(inst)).dr) } as *mut c_void;
+            let fill = READ_FILL;
+            let mut discard = 0u8;
+            let rx_config = self.rx.default_config()
+                .transfer_data_size::<u8>()
+                .dreq(unsafe { sys::wrapped_spi_get_dreq(inst, false) })
+                .read_increment(false)
+                .write_increment(read.is_some());
+            let tx_config = self.tx.default_config()
+                .transfer_data_size::<u8>()
+                .dreq(unsafe { sys::wrapped_spi_get_dreq(inst, true) })
+                .read_increment(write.is_some())
+                .write_increment(false);
+            // The guards abort both channels should this future be dropped, before `fill`
+            // and `discard` go away.
+            unsafe {
+                self.rx.configure(&rx_config, read.unwrap_or(&mut discard) as *mut c_void, data, len as u32, true);
+            }
+            let _rx_transfer = Transfer::new(&self.rx);
+            unsafe {
+                self.tx.configure(&tx_config, data, write.unwrap_or(&fill) as *const c_void, len as u32, true);
+            }
+            let _tx_transfer = Transfer::new(&self.tx);
+            // Every byte is read back after it was sent, so the RX channel finishes last.
+            let rx = &self.rx;
+            dma::wait_until(&[rx], || !rx.is_busy()).await;
+        }
+    }
+
+    impl ErrorType for DmaSpi {
+        type Error = PicoError;
+    }
+
+    impl SpiBus for DmaSpi {
+        async fn read(&mut self, words: &mut [u8]) -> Result<(), PicoError> {
+            self.run(None, Some(words.as_mut_ptr()), words.len()).await;
+            Ok(())
+        }
+
+        async fn write(&mut self, words: &[u8]) -> Result<(), PicoError> {
+            self.run(Some(words.as_ptr()), None, words.len()).await;
+            Ok(())
+        }
+
+        /// Buffers of different length are allowed, as for the blocking `SpiBus` of [`Spi`].
+        async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), PicoError> {
+            let common = read.len().min(write.len());
+            self.run(Some(write.as_ptr()), Some(read.as_mut_ptr()), common).await;
+            if write.len() > common {
+                self.run(Some(write[common..].as_ptr()), None, write.len() - common).await;
+            } else {
+                self.run(None, Some(read[common..].as_mut_ptr()), read.len() - common).await;
+            }
+            Ok(())
+        }
+
+        async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), PicoError> {
+            // As in the blocking version, a byte is sent before the one at its position is
+            // read back, so one buffer serves both channels.
+            let ptr = words.as_mut_ptr();
+            self.run(Some(ptr), Some(ptr), words.len()).await;
+            Ok(())
+        }
+
+        async fn flush(&mut self) -> Result<(), PicoError> {
+            self.spi.flush();
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use core::future::Future;
+        use core::mem::ManuallyDrop;
+        use core::pin::pin;
+        use core::ptr::null_mut;
+        use core::task::{Context, Poll, Waker};
+
+        use super::*;
+        use crate::testing::record_calls;
+
+        #[test]
+        fn empty_transfers_do_not_touch_the_bus() {
+            // Built directly, as the SPI registers are not there on the host.
+            let spi = Spi { inst: null_mut() };
+            let (tx, rx) = (DmaChannel::claim(8).unwrap(), DmaChannel::claim(9).unwrap());
+            let mut dma_spi = ManuallyDrop::new(DmaSpi::new(spi, tx, rx));
+            let calls = record_calls(|| {
+                let mut context = Context::from_waker(Waker::noop());
+                assert_eq!(pin!(dma_spi.write(&[])).poll(&mut context), Poll::Ready(Ok(())));
+                assert_eq!(pin!(dma_spi.transfer_in_place(&mut [])).poll(&mut context), Poll::Ready(Ok(())));
+                // Both sides are empty once the common part is done.
+                assert_eq!(pin!(dma_spi.transfer(&mut [], &[])).poll(&mut context), Poll::Ready(Ok(())));
+            });
+            assert!(calls.is_empty());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::null_mut;
+
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    #[should_panic(expected = "SPI transfer buffers differ in length")]
+    fn transfer_buffers_must_have_the_same_length() {
+        let mut spi = Spi { inst: null_mut() };
+        let _ = spi.transfer(&[1, 2], &mut [0]);
+    }
+
+    #[test]
+    fn flush_waits_for_the_bus_and_in_place_transfers_are_one_call() {
+        let mut spi = Spi { inst: null_mut() };
+        let calls = record_calls(|| {
+            spi.transfer_in_place(&mut [1, 2, 3]).unwrap();
+            spi.flush();
+        });
+        assert_eq!(calls, ["wrapped_spi_write_read_blocking", "wrapped_spi_is_busy"]);
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-hal")]
+    fn the_longer_side_of_a_bus_transfer_is_finished_on_its_own() {
+        use embedded_hal::spi::SpiBus;
+
+        let mut spi = Spi { inst: null_mut() };
+        let calls = record_calls(|| SpiBus::transfer(&mut spi, &mut [0; 2], &[1, 2, 3]).unwrap());
+        assert_eq!(calls, ["wrapped_spi_write_read_blocking", "wrapped_spi_write_blocking"]);
+        let calls = record_calls(|| SpiBus::transfer(&mut spi, &mut [0; 3], &[1]).unwrap());
+        assert_eq!(calls, ["wrapped_spi_write_read_blocking", "wrapped_spi_read_blocking"]);
+    }
+
+    #[test]
+    fn the_chip_select_frames_the_flushed_transfer_and_its_delays() {
+        let mut device = None;
+        let calls = record_calls(|| device = Some(SpiDevice::new(Spi { inst: null_mut() }, 17)));
+        assert_eq!(calls, ["wrapped_gpio_init", "wrapped_gpio_put", "wrapped_gpio_set_dir"]);
+        let mut device = device.unwrap();
+        let calls = record_calls(|| device.with_bus(|spi| spi.write(&[1]).unwrap()));
+        assert_eq!(
+            calls,
+            ["wrapped_gpio_put", "wrapped_spi_write_blocking", "wrapped_spi_is_busy", "wrapped_gpio_put"]
+        );
+        device.set_cs_delays(100, 2000);
+        let calls = record_calls(|| device.with_bus(|_| ()));
+        assert_eq!(
+            calls,
+            [
+                "wrapped_gpio_put",
+                "wrapped_busy_wait_us_32",
+                "wrapped_spi_is_busy",
+                "wrapped_busy_wait_us_32",
+                "wrapped_gpio_put",
+            ]
+        );
+    }
+}
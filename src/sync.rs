@@ -0,0 +1,156 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+
+use raspi_pico_sdk_sys as sys;
+
+/// A counting semaphore over the SDK's `semaphore_t`, usable from both cores and from
+/// interrupt handlers (except for the blocking acquires).
+pub struct Semaphore {
+    sem: UnsafeCell<sys::semaphore_t>,
+}
+
+// The SDK guards every access to the semaphore with a hardware spin lock.
+unsafe impl Sync for Semaphore {}
+unsafe impl Send for Semaphore {}
+
+impl Semaphore {
+    /// Creates a semaphore holding `initial_permits` of at most `max_permits` permits.
+    pub fn new(initial_permits: u16, max_permits: u16) -> Semaphore {
+        assert!(max_permits > 0, "a semaphore needs at least one permit");
+        assert!(max_permits <= i16::MAX as u16, "a semaphore holds at most {} permits", i16::MAX);
+        assert!(
+            initial_permits <= max_permits,
+            "{} initial permits exceed the maximum of {}",
+            initial_permits,
+            max_permits
+        );
+        let mut sem = MaybeUninit::<sys::semaphore_t>::uninit();
+        unsafe {
+            sys::wrapped_sem_init(sem.as_mut_ptr(), initial_permits as i16, max_permits as i16);
+            Semaphore { sem: UnsafeCell::new(sem.assume_init()) }
+        }
+    }
+
+    /// Number of permits that can be acquired right now.
+    pub fn available(&self) -> u32 {
+        unsafe { sys::wrapped_sem_available(self.sem.get()) as u32 }
+    }
+
+    /// Waits until a permit is available and takes it.
+    pub fn acquire_blocking(&self) {
+        unsafe { sys::wrapped_sem_acquire_blocking(self.sem.get()) }
+    }
+
+    /// Takes a permit if one is available.
+    pub fn try_acquire(&self) -> bool {
+        unsafe { sys::wrapped_sem_try_acquire(self.sem.get()) }
+    }
+
+    /// Waits at most `timeout_ms` for a permit, returning whether one was taken.
+    pub fn acquire_timeout_ms(&self, timeout_ms: u32) -> bool {
+        unsafe { sys::wrapped_sem_acquire_timeout_ms(self.sem.get(), timeout_ms) }
+    }
+
+    /// Returns a permit, waking a waiter if there is one. Returns `false` without changing
+    /// anything when all permits are already available.
+    pub fn release(&self) -> bool {
+        unsafe { sys::wrapped_sem_release(self.sem.get()) }
+    }
+}
+
+/// One of the hardware spin locks, released again when dropped.
+pub struct SpinLock {
+    num: u32,
+    lock: *mut sys::spin_lock_t,
+}
+
+// Spin locks are shared hardware registers meant to be taken from both cores.
+unsafe impl Sync for SpinLock {}
+unsafe impl Send for SpinLock {}
+
+impl SpinLock {
+    /// Claims a spin lock nobody else is using, or returns `None` if all of them are taken.
+    pub fn claim_unused() -> Option<SpinLock> {
+        let num = unsafe { sys::wrapped_spin_lock_claim_unused(false) };
+        if num < 0 {
+            None
+        } else {
+            let num = num as u32;
+            Some(SpinLock { num, lock: unsafe { sys::wrapped_spin_lock_instance(num) } })
+        }
+    }
+
+    pub fn number(&self) -> u32 {
+        self.num
+    }
+
+    /// Disables interrupts on the current core and spins until the lock is taken. Both are
+    /// undone when the guard is dropped.
+    pub fn lock(&self) -> SpinLockGuard<'_> {
+        let saved_irq = unsafe { sys::wrapped_spin_lock_blocking(self.lock) };
+        SpinLockGuard { lock: self, saved_irq }
+    }
+}
+
+impl Drop for SpinLock {
+    fn drop(&mut self) {
+        unsafe { sys::wrapped_spin_lock_unclaim(self.num) }
+    }
+}
+
+pub struct SpinLockGuard<'a> {
+    lock: &'a SpinLock,
+    saved_irq: u32,
+}
+
+impl Drop for SpinLockGuard<'_> {
+    fn drop(&mut self) {
+        unsafe { sys::wrapped_spin_unlock(self.lock.lock, self.saved_irq) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    fn the_semaphore_is_initialized_by_the_sdk() {
+        let calls = record_calls(|| {
+            let sem = Semaphore::new(0, 1);
+            assert!(!sem.try_acquire());
+            sem.release();
+        });
+        assert_eq!(calls, ["wrapped_sem_init", "wrapped_sem_try_acquire", "wrapped_sem_release"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "2 initial permits exceed the maximum of 1")]
+    fn initial_permits_cannot_exceed_the_maximum() {
+        Semaphore::new(2, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "a semaphore holds at most 32767 permits")]
+    fn the_maximum_fits_the_sdk_counter() {
+        Semaphore::new(0, 32768);
+    }
+
+    #[test]
+    fn the_guard_unlocks_and_the_lock_is_unclaimed_when_dropped() {
+        let calls = record_calls(|| {
+            let lock = SpinLock::claim_unused().unwrap();
+            drop(lock.lock());
+        });
+        assert_eq!(
+            calls,
+            [
+                "wrapped_spin_lock_claim_unused",
+                "wrapped_spin_lock_instance",
+                "wrapped_spin_lock_blocking",
+                "wrapped_spin_unlock",
+                "wrapped_spin_lock_unclaim",
+            ]
+        );
+    }
+}
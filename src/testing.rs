@@ -0,0 +1,30 @@
+//! Records the SDK calls of the code under test, which the unit tests run against the host stubs
+//! of `raspi_pico_sdk_sys`.
+
+use std::cell::RefCell;
+use std::sync::Once;
+use std::vec::Vec;
+
+use raspi_pico_sdk_sys as sys;
+
+thread_local! {
+    static CALLS: RefCell<Option<Vec<&'static str>>> = const { RefCell::new(None) };
+}
+
+fn record(name: &'static str) {
+    CALLS.with(|calls| {
+        if let Some(calls) = calls.borrow_mut().as_mut() {
+            calls.push(name);
+        }
+    });
+}
+
+/// Runs `f` and returns the wrappers it called, in order. Every test thread records its own
+/// calls.
+pub fn record_calls(f: impl FnOnce()) -> Vec<&'static str> {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| sys::host_stubs::set_call_hook(Some(record)));
+    CALLS.with(|calls| *calls.borrow_mut() = Some(Vec::new()));
+    f();
+    CALLS.with(|calls| calls.borrow_mut().take().unwrap())
+}
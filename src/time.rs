@@ -0,0 +1,104 @@
+use raspi_pico_sdk_sys as sys;
+
+/// A point in time on the system timer, in microseconds since boot.
+///
+/// The SDK's `absolute_time_t` is opaque in some builds, so it is only converted to and from
+/// at the calls that need it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    us_since_boot: u64,
+}
+
+impl Instant {
+    pub fn now() -> Instant {
+        Instant::from_absolute_time(unsafe { sys::wrapped_get_absolute_time() })
+    }
+
+    pub fn from_us_since_boot(us_since_boot: u64) -> Instant {
+        Instant { us_since_boot }
+    }
+
+    pub fn us_since_boot(&self) -> u64 {
+        self.us_since_boot
+    }
+
+    /// The deadline `ms` milliseconds from now (`make_timeout_time_ms`).
+    pub fn timeout_ms(ms: u32) -> Instant {
+        Instant::from_absolute_time(unsafe { sys::wrapped_make_timeout_time_ms(ms) })
+    }
+
+    /// The deadline `us` microseconds from now (`make_timeout_time_us`).
+    pub fn timeout_us(us: u64) -> Instant {
+        Instant::from_absolute_time(unsafe { sys::wrapped_make_timeout_time_us(us) })
+    }
+
+    /// Whether the timer has reached this instant (`time_reached`).
+    pub fn is_reached(&self) -> bool {
+        unsafe { sys::wrapped_time_reached(self.to_absolute_time()) }
+    }
+
+    /// Spins until this instant is reached, returning right away if it already has been.
+    pub fn busy_wait_until(&self) {
+        if !self.is_reached() {
+            unsafe { sys::wrapped_busy_wait_until(self.to_absolute_time()) }
+        }
+    }
+
+    /// Microseconds from `earlier` to this instant, or 0 if `earlier` is later.
+    pub fn us_since(&self, earlier: Instant) -> u64 {
+        self.us_since_boot.saturating_sub(earlier.us_since_boot)
+    }
+
+    /// Microseconds left until this instant, or 0 once it has been reached.
+    pub fn us_remaining(&self) -> u64 {
+        self.us_since(Instant::now())
+    }
+
+    pub fn checked_add_us(&self, us: u64) -> Option<Instant> {
+        self.us_since_boot.checked_add(us).map(Instant::from_us_since_boot)
+    }
+
+    fn from_absolute_time(time: sys::absolute_time_t) -> Instant {
+        Instant { us_since_boot: unsafe { sys::wrapped_to_us_since_boot(time) } }
+    }
+
+    pub(crate) fn to_absolute_time(self) -> sys::absolute_time_t {
+        unsafe { sys::wrapped_from_us_since_boot(self.us_since_boot) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    fn durations_saturate_at_their_ends() {
+        let (earlier, later) = (Instant::from_us_since_boot(1_000), Instant::from_us_since_boot(1_250));
+        assert_eq!(later.us_since(earlier), 250);
+        assert_eq!(earlier.us_since(later), 0);
+        assert!(earlier < later);
+        assert_eq!(earlier.checked_add_us(250), Some(later));
+        assert_eq!(later.checked_add_us(u64::MAX), None);
+    }
+
+    #[test]
+    fn instants_are_converted_at_the_sdk_calls() {
+        let calls = record_calls(|| {
+            // The stubs report the timer at boot, before any deadline.
+            assert_eq!(Instant::now(), Instant::from_us_since_boot(0));
+            Instant::from_us_since_boot(500).busy_wait_until();
+        });
+        assert_eq!(
+            calls,
+            [
+                "wrapped_get_absolute_time",
+                "wrapped_to_us_since_boot",
+                "wrapped_from_us_since_boot",
+                "wrapped_time_reached",
+                "wrapped_from_us_since_boot",
+                "wrapped_busy_wait_until",
+            ]
+        );
+    }
+}
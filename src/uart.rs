@@ -0,0 +1,178 @@
+use raspi_pico_sdk_sys as sys;
+
+use crate::error::PicoError;
+
+pub const NUM_INSTANCES: usize = 2;
+
+// Error flags the UART stores next to every received byte in `UARTDR`, and the busy flag in
+// `UARTFR`, as in hardware/regs/uart.h.
+const DR_FE_BITS: u32 = 1 << 8;
+const DR_PE_BITS: u32 = 1 << 9;
+const DR_BE_BITS: u32 = 1 << 10;
+const DR_OE_BITS: u32 = 1 << 11;
+const FR_BUSY_BITS: u32 = 1 << 3;
+
+pub struct Uart {
+    inst: *mut sys::uart_inst_t,
+}
+
+impl Uart {
+    /// Initializes UART instance `index` (0 or 1) at `baudrate` with 8 data bits, no parity
+    /// and one stop bit. The TX/RX pins still have to be switched to the UART function by the
+    /// caller.
+    pub fn new(index: u32, baudrate: u32) -> Uart {
+        assert!((index as usize) < NUM_INSTANCES, "invalid UART instance {}", index);
+        let inst = unsafe { sys::wrapped_uart_get_instance(index) };
+        unsafe { sys::wrapped_uart_init(inst, baudrate) };
+        Uart { inst }
+    }
+
+    pub fn index(&self) -> u32 {
+        unsafe { sys::wrapped_uart_get_index(self.inst) }
+    }
+
+    pub fn raw(&self) -> *mut sys::uart_inst_t {
+        self.inst
+    }
+
+    pub fn is_readable(&self) -> bool {
+        unsafe { sys::wrapped_uart_is_readable(self.inst) }
+    }
+
+    pub fn is_writable(&self) -> bool {
+        unsafe { sys::wrapped_uart_is_writable(self.inst) }
+    }
+
+    /// Whether the UART is still shifting out data.
+    pub fn is_busy(&self) -> bool {
+        let hw = unsafe { sys::wrapped_uart_get_hw(self.inst) };
+        let flags = unsafe { core::ptr::read_volatile(core::ptr::addr_of!((*hw).fr)) };
+        flags & FR_BUSY_BITS != 0
+    }
+
+    /// Takes the next byte from the RX FIFO, or returns `None` if it is empty. A byte received
+    /// with a framing, parity, break or overrun condition is reported as that error.
+    pub fn try_read(&mut self) -> Option<Result<u8, PicoError>> {
+        if !self.is_readable() {
+            return None;
+        }
+        // `uart_getc` drops the error flags, so the data register is read directly.
+        let hw = unsafe { sys::wrapped_uart_get_hw(self.inst) };
+        let data = unsafe { core::ptr::read_volatile(core::ptr::addr_of!((*hw).dr)) };
+        Some(decode_data(data))
+    }
+
+    /// Puts `byte` into the TX FIFO, or returns `false` if it is full.
+    pub fn try_write(&mut self, byte: u8) -> bool {
+        if !self.is_writable() {
+            return false;
+        }
+        unsafe { sys::wrapped_uart_putc_raw(self.inst, byte as _) };
+        true
+    }
+}
+
+/// The byte of a `UARTDR` read, or the condition it was received with.
+fn decode_data(data: u32) -> Result<u8, PicoError> {
+    if data & DR_OE_BITS != 0 {
+        Err(PicoError::Overrun)
+    } else if data & DR_BE_BITS != 0 {
+        Err(PicoError::Break)
+    } else if data & DR_PE_BITS != 0 {
+        Err(PicoError::Parity)
+    } else if data & DR_FE_BITS != 0 {
+        Err(PicoError::Framing)
+    } else {
+        Ok(data as u8)
+    }
+}
+
+#[cfg(feature = "embedded-hal-nb")]
+mod hal_nb {
+    use embedded_hal_nb::serial::{ErrorKind, ErrorType, Read, Write};
+
+    use super::Uart;
+    use crate::error::PicoError;
+
+    impl embedded_hal_nb::serial::Error for PicoError {
+        fn kind(&self) -> ErrorKind {
+            match self {
+                PicoError::Overrun => ErrorKind::Overrun,
+                PicoError::Parity => ErrorKind::Parity,
+                PicoError::Framing => ErrorKind::FrameFormat,
+                _ => ErrorKind::Other,
+            }
+        }
+    }
+
+    impl ErrorType for Uart {
+        type Error = PicoError;
+    }
+
+    impl Read for Uart {
+        fn read(&mut self) -> nb::Result<u8, PicoError> {
+            match self.try_read() {
+                Some(result) => result.map_err(nb::Error::Other),
+                None => Err(nb::Error::WouldBlock),
+            }
+        }
+    }
+
+    impl Write for Uart {
+        fn write(&mut self, word: u8) -> nb::Result<(), PicoError> {
+            if self.try_write(word) {
+                Ok(())
+            } else {
+                Err(nb::Error::WouldBlock)
+            }
+        }
+
+        fn flush(&mut self) -> nb::Result<(), PicoError> {
+            if self.is_busy() {
+                Err(nb::Error::WouldBlock)
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::null_mut;
+
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    fn received_bytes_carry_their_error_flags() {
+        assert_eq!(decode_data(0x41), Ok(0x41));
+        assert_eq!(decode_data(DR_FE_BITS | 0x41), Err(PicoError::Framing));
+        assert_eq!(decode_data(DR_PE_BITS | DR_FE_BITS), Err(PicoError::Parity));
+        // A break also sets the framing flag, and an overrun goes with any byte.
+        assert_eq!(decode_data(DR_BE_BITS | DR_FE_BITS), Err(PicoError::Break));
+        assert_eq!(decode_data(DR_OE_BITS | DR_BE_BITS | 0x41), Err(PicoError::Overrun));
+    }
+
+    #[test]
+    fn empty_and_full_fifos_are_not_touched() {
+        let mut uart = Uart { inst: null_mut() };
+        let calls = record_calls(|| {
+            assert_eq!(uart.try_read(), None);
+            assert!(!uart.try_write(b'a'));
+        });
+        assert_eq!(calls, ["wrapped_uart_is_readable", "wrapped_uart_is_writable"]);
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-hal-nb")]
+    fn serial_reads_and_writes_would_block_on_the_fifos() {
+        use embedded_hal_nb::serial::{Error, ErrorKind, Read, Write};
+
+        let mut uart = Uart { inst: null_mut() };
+        assert!(matches!(Read::read(&mut uart), Err(nb::Error::WouldBlock)));
+        assert!(matches!(Write::write(&mut uart, b'a'), Err(nb::Error::WouldBlock)));
+        assert_eq!(PicoError::Framing.kind(), ErrorKind::FrameFormat);
+        assert_eq!(PicoError::Break.kind(), ErrorKind::Other);
+    }
+}
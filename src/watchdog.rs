@@ -0,0 +1,111 @@
+use core::ptr::addr_of_mut;
+
+use raspi_pico_sdk_sys as sys;
+
+/// `watchdog_hw`. Its scratch registers 0 to 3 survive a watchdog reboot and are not used by
+/// the SDK (4 to 7 hold its reboot target).
+const WATCHDOG: *mut sys::watchdog_hw_t = sys::WATCHDOG_BASE as *mut sys::watchdog_hw_t;
+
+/// `watchdog_hw->scratch[0]`, which holds the encoded task.
+fn scratch0() -> *mut u32 {
+    unsafe { addr_of_mut!((*WATCHDOG).scratch[0]) }
+}
+
+/// Marks the scratch register as holding a task id, so that the zeroed register after a
+/// power-on (or a value left by other code) is not mistaken for one.
+const TASK_MAGIC: u32 = 0x7a5c_0000;
+const TASK_MAGIC_MASK: u32 = 0xffff_0000;
+
+/// Identifies the operations reported by [`Watchdog::hung_task`], usually implemented by a
+/// fieldless enum.
+pub trait Task: Sized {
+    fn id(&self) -> u16;
+    fn from_id(id: u16) -> Option<Self>;
+}
+
+pub struct Watchdog {
+    _private: (),
+}
+
+impl Watchdog {
+    /// Starts the watchdog; the chip reboots if it is not fed for `delay_ms`.
+    pub fn enable(delay_ms: u32, pause_on_debug: bool) -> Watchdog {
+        unsafe { sys::wrapped_watchdog_enable(delay_ms, pause_on_debug) };
+        Watchdog { _private: () }
+    }
+
+    pub fn feed(&mut self) {
+        unsafe { sys::wrapped_watchdog_update() }
+    }
+
+    /// Feeds the watchdog and records `task` as running, so that a hang inside it can be
+    /// told apart after the reboot.
+    pub fn enter<T: Task>(&mut self, task: &T) {
+        unsafe { core::ptr::write_volatile(scratch0(), encode_task(task.id())) };
+        self.feed();
+    }
+
+    /// Whether the last reboot was caused by the watchdog timer running out, as opposed to a
+    /// reboot requested through the watchdog or any other reset.
+    pub fn caused_reboot() -> bool {
+        unsafe { sys::wrapped_watchdog_enable_caused_reboot() }
+    }
+
+    /// The task last passed to [`Watchdog::enter`] before the watchdog rebooted the chip, or
+    /// `None` if the last reboot had another cause or no task was recorded.
+    pub fn hung_task<T: Task>() -> Option<T> {
+        if !Watchdog::caused_reboot() {
+            return None;
+        }
+        decode_task(unsafe { core::ptr::read_volatile(scratch0()) }).and_then(T::from_id)
+    }
+}
+
+fn encode_task(id: u16) -> u32 {
+    TASK_MAGIC | id as u32
+}
+
+fn decode_task(scratch: u32) -> Option<u16> {
+    if scratch & TASK_MAGIC_MASK == TASK_MAGIC {
+        Some(scratch as u16)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::record_calls;
+
+    #[test]
+    fn tasks_round_trip_through_the_scratch_encoding() {
+        for id in [0, 1, 0x1234, u16::MAX] {
+            assert_eq!(decode_task(encode_task(id)), Some(id));
+        }
+    }
+
+    #[test]
+    fn scratch_values_without_the_magic_are_no_task() {
+        assert_eq!(decode_task(0), None);
+        assert_eq!(decode_task(0x0000_0007), None);
+        assert_eq!(decode_task(0x7a5d_0007), None);
+        assert_eq!(decode_task(0xffff_ffff), None);
+    }
+
+    #[test]
+    fn no_task_hung_unless_the_watchdog_rebooted() {
+        struct Step;
+        impl Task for Step {
+            fn id(&self) -> u16 {
+                0
+            }
+
+            fn from_id(_: u16) -> Option<Step> {
+                Some(Step)
+            }
+        }
+        let calls = record_calls(|| assert!(Watchdog::hung_task::<Step>().is_none()));
+        assert_eq!(calls, ["wrapped_watchdog_enable_caused_reboot"]);
+    }
+}
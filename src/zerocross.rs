@@ -0,0 +1,263 @@
+use core::ptr::addr_of_mut;
+
+use raspi_pico_sdk_sys as sys;
+
+use crate::irq;
+use crate::time::Instant;
+
+// `GPIO_IRQ_EDGE_RISE` of hardware/gpio.h.
+const IRQ_EDGE_RISE: u32 = 0x8;
+
+/// How many periods the frequency is averaged over.
+const AVERAGED_PERIODS: usize = 8;
+
+/// The line as last measured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMeasurement {
+    /// The averaged line frequency.
+    pub frequency_hz: f32,
+    /// The averaged period, in microseconds.
+    pub period_us: f32,
+    /// The latest accepted zero crossing.
+    pub last_crossing: Instant,
+}
+
+impl LineMeasurement {
+    /// The phase of the line at `at` as a fraction of a period since the upward zero
+    /// crossing, in `0.0..1.0`, assuming the frequency has not changed since the last
+    /// crossing.
+    pub fn phase(&self, at: Instant) -> f32 {
+        phase_from_elapsed_us(at.us_since(self.last_crossing), self.period_us)
+    }
+
+    /// The first upward zero crossing expected after `at`, e.g. to schedule a triac firing
+    /// relative to it.
+    pub fn next_crossing(&self, at: Instant) -> Instant {
+        let elapsed = at.us_since(self.last_crossing) as f32;
+        let periods = (elapsed / self.period_us) as u64 + 1;
+        let offset = (periods as f32 * self.period_us) as u64;
+        Instant::from_us_since_boot(self.last_crossing.us_since_boot() + offset)
+    }
+}
+
+/// The line frequency from the intervals between consecutive crossings, in microseconds, or
+/// `None` if there are none yet.
+pub fn frequency_from_intervals(intervals_us: &[u32]) -> Option<f32> {
+    let total: u64 = intervals_us.iter().map(|&interval| u64::from(interval)).sum();
+    if total == 0 {
+        return None;
+    }
+    Some(intervals_us.len() as f32 * 1_000_000.0 / total as f32)
+}
+
+/// The fraction of a period of `period_us` that `elapsed_us` falls into, in `0.0..1.0`.
+pub fn phase_from_elapsed_us(elapsed_us: u64, period_us: f32) -> f32 {
+    if period_us <= 0.0 {
+        return 0.0;
+    }
+    let periods = elapsed_us as f32 / period_us;
+    let phase = periods - (periods as u64) as f32;
+    // Rounding can land exactly on the next period.
+    if phase >= 1.0 {
+        0.0
+    } else {
+        phase
+    }
+}
+
+/// The edge history filled in by the interrupt handler.
+struct State {
+    gpio: u32,
+    min_interval_us: u32,
+    last_edge: Option<u64>,
+    intervals: [u32; AVERAGED_PERIODS],
+    count: usize,
+    next: usize,
+}
+
+impl State {
+    const fn new() -> State {
+        State { gpio: 0, min_interval_us: 0, last_edge: None, intervals: [0; AVERAGED_PERIODS], count: 0, next: 0 }
+    }
+
+    fn record_edge(&mut self, now_us: u64) {
+        if let Some(last) = self.last_edge {
+            let interval = now_us.saturating_sub(last);
+            if interval < u64::from(self.min_interval_us) {
+                return;
+            }
+            self.intervals[self.next] = interval.min(u64::from(u32::MAX)) as u32;
+            self.next = (self.next + 1) % AVERAGED_PERIODS;
+            self.count = (self.count + 1).min(AVERAGED_PERIODS);
+        }
+        self.last_edge = Some(now_us);
+    }
+
+    fn measurement(&self) -> Option<LineMeasurement> {
+        let frequency_hz = frequency_from_intervals(&self.intervals[..self.count])?;
+        Some(LineMeasurement {
+            frequency_hz,
+            period_us: 1_000_000.0 / frequency_hz,
+            last_crossing: Instant::from_us_since_boot(self.last_edge?),
+        })
+    }
+}
+
+static mut STATE: State = State::new();
+static mut RUNNING: bool = false;
+
+/// The raw handler of the pin, shared with the other GPIO interrupt users of the core.
+extern "C" fn edge_handler() {
+    let state = unsafe { &mut *addr_of_mut!(STATE) };
+    let gpio = state.gpio;
+    if unsafe { sys::wrapped_gpio_get_irq_event_mask(gpio) } & IRQ_EDGE_RISE == 0 {
+        return;
+    }
+    unsafe { sys::wrapped_gpio_acknowledge_irq(gpio, IRQ_EDGE_RISE) };
+    state.record_edge(Instant::now().us_since_boot());
+}
+
+/// An AC zero-cross detector on the digital output of a comparator (or an optocoupler) that
+/// goes high as the line voltage crosses zero upwards.
+///
+/// Every rising edge of the pin is timestamped in the GPIO interrupt, and the line frequency is
+/// averaged over the last few periods. Edges closer to the previous crossing than the minimum
+/// window are taken as comparator chatter and ignored. Only one detector can run at a time, and
+/// its interrupt stays on the core that started it.
+pub struct ZeroCross {
+    gpio: u32,
+}
+
+impl ZeroCross {
+    /// Configures `gpio` as an input and starts timestamping its rising edges, or returns
+    /// `None` if a detector is already running.
+    ///
+    /// `min_interval_us` should be longer than the comparator chatters around a crossing but
+    /// well below the shortest expected period, e.g. 5000 for 50 or 60 Hz mains.
+    ///
+    /// The edges are taken with a raw GPIO interrupt handler, so the pin's events are not
+    /// passed to the core's GPIO callback and the pin must not be awaited at the same time.
+    pub fn start(gpio: u32, min_interval_us: u32) -> Option<ZeroCross> {
+        let started = irq::interrupt_free(|| unsafe {
+            if RUNNING {
+                return false;
+            }
+            RUNNING = true;
+            let state = &mut *addr_of_mut!(STATE);
+            *state = State::new();
+            state.gpio = gpio;
+            state.min_interval_us = min_interval_us;
+            true
+        });
+        if !started {
+            return None;
+        }
+        unsafe {
+            sys::wrapped_gpio_init(gpio);
+            sys::wrapped_gpio_set_dir(gpio, false);
+            sys::wrapped_gpio_add_raw_irq_handler(gpio, Some(edge_handler));
+            sys::wrapped_gpio_set_irq_enabled(gpio, IRQ_EDGE_RISE, true);
+        }
+        irq::set_enabled(irq::IO_IRQ_BANK0, true);
+        Some(ZeroCross { gpio })
+    }
+
+    pub fn gpio(&self) -> u32 {
+        self.gpio
+    }
+
+    /// The averaged line frequency and the latest crossing, or `None` until two crossings
+    /// have been seen.
+    pub fn measurement(&self) -> Option<LineMeasurement> {
+        irq::interrupt_free(|| unsafe { (*addr_of_mut!(STATE)).measurement() })
+    }
+
+    /// The averaged line frequency, if it has been measured yet.
+    pub fn frequency_hz(&self) -> Option<f32> {
+        self.measurement().map(|measurement| measurement.frequency_hz)
+    }
+
+    /// The latest accepted crossing, which is stale if the line has gone away.
+    pub fn last_crossing(&self) -> Option<Instant> {
+        irq::interrupt_free(|| unsafe { (*addr_of_mut!(STATE)).last_edge }).map(Instant::from_us_since_boot)
+    }
+
+    /// Stops the detector and releases the pin's interrupt.
+    pub fn stop(self) {}
+}
+
+impl Drop for ZeroCross {
+    fn drop(&mut self) {
+        unsafe {
+            sys::wrapped_gpio_set_irq_enabled(self.gpio, IRQ_EDGE_RISE, false);
+            sys::wrapped_gpio_remove_raw_irq_handler(self.gpio, Some(edge_handler));
+        }
+        irq::interrupt_free(|| unsafe { RUNNING = false });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_frequency_is_averaged_over_the_intervals() {
+        assert_eq!(frequency_from_intervals(&[]), None);
+        assert_eq!(frequency_from_intervals(&[20_000]), Some(50.0));
+        assert_eq!(frequency_from_intervals(&[16_000, 17_000, 17_000]), Some(60.0));
+    }
+
+    #[test]
+    fn the_phase_is_the_fraction_of_the_current_period() {
+        assert_eq!(phase_from_elapsed_us(0, 20_000.0), 0.0);
+        assert_eq!(phase_from_elapsed_us(5_000, 20_000.0), 0.25);
+        assert_eq!(phase_from_elapsed_us(45_000, 20_000.0), 0.25);
+        assert_eq!(phase_from_elapsed_us(40_000, 20_000.0), 0.0);
+        // Without a period yet there is no phase to speak of.
+        assert_eq!(phase_from_elapsed_us(5_000, 0.0), 0.0);
+    }
+
+    #[test]
+    fn chatter_within_the_window_is_ignored() {
+        let mut state = State::new();
+        state.min_interval_us = 5_000;
+        state.record_edge(1_000);
+        assert_eq!(state.measurement(), None);
+        state.record_edge(1_200);
+        state.record_edge(21_000);
+        state.record_edge(21_300);
+        state.record_edge(41_000);
+        let measurement = state.measurement().unwrap();
+        assert_eq!(measurement.frequency_hz, 50.0);
+        assert_eq!(measurement.period_us, 20_000.0);
+        assert_eq!(measurement.last_crossing, Instant::from_us_since_boot(41_000));
+    }
+
+    #[test]
+    fn only_the_latest_periods_are_averaged() {
+        let mut state = State::new();
+        let mut now = 0;
+        for _ in 0..AVERAGED_PERIODS {
+            state.record_edge(now);
+            now += 10_000;
+        }
+        for _ in 0..=AVERAGED_PERIODS {
+            state.record_edge(now);
+            now += 20_000;
+        }
+        assert_eq!(state.count, AVERAGED_PERIODS);
+        assert_eq!(state.measurement().unwrap().frequency_hz, 50.0);
+    }
+
+    #[test]
+    fn the_next_crossing_is_a_whole_number_of_periods_on() {
+        let measurement = LineMeasurement {
+            frequency_hz: 50.0,
+            period_us: 20_000.0,
+            last_crossing: Instant::from_us_since_boot(100_000),
+        };
+        assert_eq!(measurement.next_crossing(Instant::from_us_since_boot(105_000)), Instant::from_us_since_boot(120_000));
+        assert_eq!(measurement.next_crossing(Instant::from_us_since_boot(150_000)), Instant::from_us_since_boot(160_000));
+        assert_eq!(measurement.phase(Instant::from_us_since_boot(115_000)), 0.75);
+    }
+}
@@ -1,55 +1,760 @@
 use io::Write;
 use std::{io, process::Command};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 
-use clang::{Clang, Entity, EntityKind, Index};
+use clang::{Clang, Entity, EntityKind, Index, Type, TypeKind};
+use clang::diagnostic::Severity;
+use regex::Regex;
 
-const GENERATED_CODE_MARKER: &'static str = "\n\n/// Generated Code\n\n";
+const GENERATED_CODE_MARKER: &str = "\n\n/// Generated Code\n\n";
 
 fn main() {
+    // Missing tools are reported as one line naming the fix instead of a panic and backtrace.
+    if let Err(message) = run() {
+        eprintln!("error: {}", message);
+        std::process::exit(1);
+    }
+}
+
+/// A failure the user can fix, such as a missing toolchain, described with how to fix it.
+type BuildResult<T> = Result<T, String>;
+
+/// The include directories and the definitions the SDK is compiled with.
+type CompileOptions = (Vec<String>, Vec<String>);
+
+/// The name of a wrapped function and its number of parameters.
+type WrappedFunction = (String, usize);
+
+fn run() -> BuildResult<()> {
+    check_profile()?;
+    let module_name = get_env("PICO_SDK_RS_MODULE_NAME");
+    if let Some(module_name) = module_name.as_deref().filter(|name| !is_identifier(name)) {
+        return Err(format!("PICO_SDK_RS_MODULE_NAME `{}` is not a valid identifier", module_name));
+    }
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let out_dir = Path::new(&out_dir);
+    let cache_key = binding_cache_key();
+    if replay_cached_build(out_dir, &cache_key) {
+        return Ok(());
+    }
+    let (result, output) = capture_stdout(&out_dir.join(CAPTURED_STDOUT_FILE), || generate_bindings(out_dir, module_name.as_deref()));
+    result?;
+    // Recorded last, so that an interrupted build leaves no key for its directives.
+    if let (false, Some(output)) = (get_env_flag("PICO_SDK_RS_NO_CACHE"), output) {
+        let directives = output.lines().filter(|line| line.starts_with("cargo:")).map(|line| format!("{}\n", line)).collect::<String>();
+        fs::write(out_dir.join(CACHE_DIRECTIVES_FILE), directives).expect("failed to write the cached directives");
+        fs::write(out_dir.join(CACHE_KEY_FILE), &cache_key).expect("failed to write the binding cache key");
+    }
+    Ok(())
+}
+
+/// The cmake, clang and bindgen pipeline, writing `bindings.rs` (and `safe.rs`) to `out_dir`.
+fn generate_bindings(out_dir: &Path, module_name: Option<&str>) -> BuildResult<()> {
     println!("cargo:rerun-if-changed=cmake_depend/CMakeLists.txt");
     println!("cargo:rerun-if-changed=cmake_pico/CMakeLists.txt");
     println!("cargo:rerun-if-changed=cmake_pico/entry.c");
     println!("cargo:rerun-if-changed=src/lib.rs");
     println!("cargo:rerun-if-changed=build.rs");
 
+    println!("cargo:rerun-if-env-changed=PICO_SDK_RS_PROFILE");
+    emit_link_directives(out_dir);
+    pin_libclang()?;
+
     let target_triple = std::env::var("TARGET").unwrap();
-    let implicit_include_directories = get_implicit_include_directories(&target_triple);
+    let implicit_include_directories = get_implicit_include_directories(&target_triple)?;
 
-    let out_dir = std::env::var("OUT_DIR").unwrap();
-    let out_dir = Path::new(&out_dir);
-    let (entry_path, mut entry) = create_entry_point_file_to_out_dir(out_dir);
+    let boards = split_env_list("PICO_SDK_RS_BOARDS");
+    let (mut code, board) = if boards.is_empty() {
+        generate_board_binding(&target_triple, &implicit_include_directories, out_dir, selected_board().as_deref(), true)?
+    } else {
+        generate_combined_binding(&target_triple, &implicit_include_directories, out_dir, &boards)?
+    };
+    if let Some(board) = &board {
+        println!("cargo:board={}", board);
+    }
+    // For the features of `raspi_pico_sdk` that need parts of the SDK only built on request.
+    if get_env("PICO_SDK_RS_PICO_EXTRAS_PATH").is_some() {
+        println!("cargo:sleep=1");
+    }
+    if get_env_flag("PICO_SDK_RS_ASYNC_CONTEXT") {
+        println!("cargo:async_context=1");
+    }
+
+    println!("cargo:rustc-check-cfg=cfg(pico_sdk_safe_wrappers)");
+    if get_env_flag("PICO_SDK_RS_SAFE_WRAPPERS") {
+        let mut safe_code = create_safe_wrappers(&code, module_name);
+        if !get_env_flag("PICO_SDK_RS_NO_RUSTFMT") {
+            safe_code = format_code(safe_code);
+        }
+        fs::write(out_dir.join("safe.rs"), safe_code).expect("failed to write safe.rs");
+        println!("cargo:rustc-cfg=pico_sdk_safe_wrappers");
+    }
+    if let Some(module_name) = module_name {
+        code = wrap_in_module(&code, module_name);
+    }
+    if !get_env_flag("PICO_SDK_RS_NO_RUSTFMT") {
+        code = format_code(code);
+    }
+    let bindings_path = out_dir.join("bindings.rs");
+    fs::write(&bindings_path, code)
+        .expect("failed to write bindings.rs");
+    if let Some(hook) = get_env("PICO_SDK_RS_POST_HOOK") {
+        run_post_hook(&hook, &bindings_path)?;
+    }
+    Ok(())
+}
+
+/// Written next to `bindings.rs` once a build has finished.
+const CACHE_KEY_FILE: &str = "bindings.key";
+/// The cargo directives of the build that wrote the key, which a cached build repeats.
+const CACHE_DIRECTIVES_FILE: &str = "bindings.directives";
+/// Where [`capture_stdout`] records the pipeline's output.
+const CAPTURED_STDOUT_FILE: &str = "pipeline.stdout";
+
+/// Prefixes of the environment variables of the SDK build and of the crate's features.
+const CACHE_KEY_PREFIXES: [&str; 2] = ["PICO_", "CARGO_FEATURE_"];
+/// Environment variables that the SDK build, clang and bindgen read, also in their
+/// `<name>_<target>` forms (`CC_thumbv6m_none_eabi`). Cargo sets others that change between runs
+/// (such as the jobserver in `CARGO_MAKEFLAGS`), and prefixes would catch unrelated ones such as
+/// `CCACHE_DIR` or `HOSTNAME`.
+const CACHE_KEY_VARIABLES: [&str; 16] = [
+    "TARGET", "HOST", "PROFILE", "OPT_LEVEL", "DEBUG", "CC", "CFLAGS", "AR", "CMAKE", "CMAKE_GENERATOR", "CMAKE_TOOLCHAIN_FILE",
+    "LIBCLANG_PATH", "CLANG_PATH", "BINDGEN_EXTRA_CLANG_ARGS", "RUSTFMT", "RUSTC",
+];
+
+/// Whether `name` is one of [`CACHE_KEY_PREFIXES`] or [`CACHE_KEY_VARIABLES`] for `target_triple`.
+fn is_cache_key_variable(name: &str, target_triple: &str) -> bool {
+    CACHE_KEY_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+        || CACHE_KEY_VARIABLES.iter().filter_map(|variable| name.strip_prefix(variable)).any(|rest| {
+            rest.is_empty() || rest.strip_prefix('_').is_some_and(|target| target == target_triple || target == target_triple.replace('-', "_"))
+        })
+}
+
+/// `program` as found on `PATH`, or as given if it names a path.
+fn find_program(program: &str) -> Option<PathBuf> {
+    if Path::new(program).components().count() > 1 {
+        return Some(PathBuf::from(program));
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|directory| directory.join(program))
+        .find(|path| path.is_file())
+}
+
+/// Hashes what the generated bindings are derived from: the build script and the cmake
+/// projects, the relevant environment, the gcc and cmake it resolves to and their versions, the
+/// files the options name and `Cargo.lock`. The include
+/// paths and definitions are only known after configuring the SDK, so the inputs of that step
+/// stand in for them: the headers under `PICO_SDK_RS_INCLUDE_PRIORITY` and the sources of the SDK
+/// and pico-extras checkouts, by their size and modification time. Generated sections are left
+/// out, as the build writes them itself.
+fn binding_cache_key() -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = Fnv1a::default();
+    let target_triple = std::env::var("TARGET").unwrap_or_default();
+    let mut variables = std::env::vars()
+        .filter(|(name, _)| is_cache_key_variable(name, &target_triple))
+        .collect::<Vec<_>>();
+    variables.sort();
+    variables.hash(&mut hasher);
+
+    // The same names can stand for other tools after a `PATH` change or a toolchain upgrade.
+    let cmake = std::env::var("CMAKE").unwrap_or("cmake".to_string());
+    for program in [select_gcc(&target_triple).0, cmake] {
+        let path = find_program(&program);
+        path.hash(&mut hasher);
+        if let Some(path) = path {
+            let version = Command::new(path).arg("--version").stdin(Stdio::null()).stderr(Stdio::null()).output();
+            version.ok().map(|output| output.stdout).hash(&mut hasher);
+        }
+    }
+
+    let mut files = ["build.rs", "cmake_pico/CMakeLists.txt", "cmake_pico/entry.c", "cmake_pico/pico_sdk_import.cmake", "cmake_depend/CMakeLists.txt"]
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    for name in ["PICO_SDK_RS_CUSTOM_ENTRY_POINT", "PICO_SDK_RS_ABI_GOLDEN", "PICO_SDK_RS_POST_HOOK"] {
+        files.extend(std::env::var_os(name).map(PathBuf::from));
+    }
+    for name in ["PICO_SDK_RS_C_BINDING_ALTERNATIVES", "PICO_SDK_RS_EXTRA_SOURCES"] {
+        files.extend(std::env::var_os(name).iter().flat_map(std::env::split_paths));
+    }
+    let extra_sources = std::env::var_os("PICO_SDK_RS_EXTRA_SOURCES").iter().flat_map(std::env::split_paths).collect::<Vec<_>>();
+    files.extend(extra_source_headers(&extra_sources));
+    files.extend(find_cargo_lock());
+    for file in files {
+        file.hash(&mut hasher);
+        if let Ok(contents) = fs::read(&file) {
+            let end = contents.windows(GENERATED_CODE_MARKER.len())
+                .position(|window| window == GENERATED_CODE_MARKER.as_bytes())
+                .unwrap_or(contents.len());
+            contents[..end].hash(&mut hasher);
+        }
+    }
+
+    let mut trees = std::env::var_os("PICO_SDK_RS_INCLUDE_PRIORITY").iter().flat_map(std::env::split_paths).collect::<Vec<_>>();
+    for name in ["PICO_SDK_PATH", "PICO_SDK_FETCH_FROM_GIT_PATH", "PICO_SDK_RS_PICO_EXTRAS_PATH"] {
+        trees.extend(std::env::var_os(name).map(PathBuf::from));
+    }
+    for tree in trees {
+        for file in source_tree_files(&tree) {
+            file.hash(&mut hasher);
+            if let Ok(metadata) = fs::metadata(&file) {
+                metadata.len().hash(&mut hasher);
+                metadata.modified().ok().hash(&mut hasher);
+            }
+        }
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// The 64 bit FNV-1a hash, which unlike `DefaultHasher` is the same in every build of the build
+/// script, so the key of one build still matches after the script is rebuilt.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl std::hash::Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The `Cargo.lock` of the workspace the crate is built in, if there is one.
+fn find_cargo_lock() -> Option<PathBuf> {
+    let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR")?);
+    manifest_dir.ancestors().map(|dir| dir.join("Cargo.lock")).find(|lock| lock.is_file())
+}
+
+/// The C sources, headers and cmake files under `dir`, sorted, leaving out version control
+/// directories.
+fn source_tree_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+            if path.is_dir() {
+                if !name.starts_with('.') {
+                    pending.push(path);
+                }
+            } else if name == "CMakeLists.txt" || [".h", ".c", ".S", ".cmake"].iter().any(|extension| name.ends_with(extension)) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Skips the pipeline when the previous build in `out_dir` had the same inputs, repeating the
+/// directives it recorded. Returns false, and forgets the previous key, when the pipeline has to
+/// run.
+fn replay_cached_build(out_dir: &Path, cache_key: &str) -> bool {
+    let key_path = out_dir.join(CACHE_KEY_FILE);
+    let previous_key = fs::read_to_string(&key_path).ok();
+    // A build that fails after this point leaves no key for the directives of a finished one.
+    let _ = fs::remove_file(&key_path);
+    if get_env_flag("PICO_SDK_RS_NO_CACHE") || previous_key.as_deref() != Some(cache_key) || !out_dir.join("bindings.rs").exists() {
+        return false;
+    }
+    let directives = match fs::read_to_string(out_dir.join(CACHE_DIRECTIVES_FILE)) {
+        Ok(directives) => directives,
+        Err(_) => return false,
+    };
+    print!("{}", directives);
+    fs::write(&key_path, cache_key).expect("failed to write the binding cache key");
+    true
+}
+
+/// Runs `f` with the standard output of the process going to `path`, so that the directives of
+/// the cmake crate, bindgen and the programs they run are recorded along with the build
+/// script's own, and then repeats it on the standard output. The output is only recorded on
+/// Unix hosts; elsewhere `f` prints as usual and nothing is cached.
+#[cfg(unix)]
+fn capture_stdout<R>(path: &Path, f: impl FnOnce() -> R) -> (R, Option<String>) {
+    use std::os::unix::io::AsRawFd;
+
+    let file = File::create(path).expect("failed to create the pipeline output file");
+    io::stdout().flush().expect("failed to flush stdout");
+    let stdout = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if stdout < 0 {
+        return (f(), None);
+    }
+    if unsafe { libc::dup2(file.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        unsafe { libc::close(stdout) };
+        return (f(), None);
+    }
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    let _ = io::stdout().flush();
+    unsafe {
+        libc::dup2(stdout, libc::STDOUT_FILENO);
+        libc::close(stdout);
+    }
+    let output = fs::read_to_string(path).ok();
+    print!("{}", output.as_deref().unwrap_or(""));
+    match result {
+        Ok(result) => (result, output),
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
+}
+
+#[cfg(not(unix))]
+fn capture_stdout<R>(_path: &Path, f: impl FnOnce() -> R) -> (R, Option<String>) {
+    (f(), None)
+}
+
+/// Runs the executable `hook` with the path of the finished `bindings.rs`, which it may edit in
+/// place. The build fails if the hook does.
+fn run_post_hook(hook: &str, bindings_path: &Path) -> BuildResult<()> {
+    println!("cargo:rerun-if-changed={}", hook);
+    let status = Command::new(hook)
+        .arg(bindings_path)
+        .status()
+        .map_err(|e| format!("failed to run PICO_SDK_RS_POST_HOOK {}: {}", hook, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("PICO_SDK_RS_POST_HOOK {} failed with {}", hook, status))
+    }
+}
+
+/// Formats the generated code with rustfmt (the one named by `RUSTFMT`, like bindgen does),
+/// keeping it unformatted with a warning if rustfmt cannot be run.
+fn format_code(code: String) -> String {
+    let rustfmt = std::env::var("RUSTFMT").unwrap_or("rustfmt".to_string());
+    let process = Command::new(&rustfmt)
+        .args(["--edition", "2018", "--emit", "stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut process = match process {
+        Ok(process) => process,
+        Err(e) => {
+            println!("cargo:warning=bindings.rs is left unformatted: failed to run {}: {}", rustfmt, e);
+            return code;
+        }
+    };
+    // rustfmt only starts writing once its input is closed, so the pipe is dropped right away.
+    let written = process.stdin.take().unwrap().write_all(code.as_bytes());
+    let output = process.wait_with_output();
+    match (written, output) {
+        (Ok(()), Ok(output)) if output.status.success() => String::from_utf8(output.stdout).expect("rustfmt printed invalid UTF-8"),
+        (_, Ok(output)) => {
+            println!("cargo:warning=bindings.rs is left unformatted: {} failed: {}", rustfmt, String::from_utf8_lossy(&output.stderr).trim());
+            code
+        }
+        (_, Err(e)) => {
+            println!("cargo:warning=bindings.rs is left unformatted: {} failed: {}", rustfmt, e);
+            code
+        }
+    }
+}
+
+/// The board named by `PICO_SDK_RS_BOARD`, or by `PICO_BOARD`, which the SDK would otherwise
+/// read from the environment behind cargo's back.
+fn selected_board() -> Option<String> {
+    get_env("PICO_SDK_RS_BOARD").or_else(|| get_env("PICO_BOARD"))
+}
+
+/// Runs the whole pipeline for one board in `out_dir` and returns the generated Rust code
+/// together with the board the SDK was configured for. Without `board` the SDK's default board
+/// is used.
+fn generate_board_binding(target_triple: &str, implicit_include_directories: &[String], out_dir: &Path, board: Option<&str>, sync_alternatives: bool) -> BuildResult<(String, Option<String>)> {
+    let prefix = wrapper_prefix()?;
+    let (entry_path, mut entry) = create_entry_point_file_to_out_dir(out_dir)?;
+
+    let (include_directories, definitions) = get_compile_options(target_triple, out_dir, &entry_path, board)?;
+    let include_directories = prioritize_include_directories(include_directories, &split_env_paths("PICO_SDK_RS_INCLUDE_PRIORITY"));
+    if get_env_flag("PICO_SDK_RS_LTO") {
+        check_lto_toolchain(out_dir)?;
+    }
+    if let Some(requirement) = get_env("PICO_SDK_RS_ASSERT_SDK_VERSION") {
+        assert_sdk_version(&requirement, &include_directories)?;
+    }
+
+    let sdk_version = get_sdk_version(&include_directories);
+    let board = get_board(&definitions);
+    if sync_alternatives {
+        let chip = get_chip(&definitions, target_triple);
+        println!("cargo:rustc-check-cfg=cfg(pico_platform, values(\"rp2040\", \"rp2350\"))");
+        println!("cargo:rustc-cfg=pico_platform={:?}", chip);
+        println!("cargo:platform={}", chip);
+    }
+
+    let (clang_arguments, wrapped_functions, sdk_types) = write_wrapper_function(implicit_include_directories, &include_directories, definitions, &entry_path, &mut entry, sync_alternatives, &prefix)?;
+
+    let manifest = create_build_manifest(sdk_version.as_deref(), board.as_deref(), wrapped_functions.len());
+    let bound_functions = select_used_functions(wrapped_functions, &split_env_list("PICO_SDK_RS_USED_FUNCTIONS"), &prefix);
+    let code = generate_rust_binding(&BindingInputs {
+        target_triple,
+        implicit_include_directories,
+        entry_path: &entry_path,
+        clang_arguments: &clang_arguments,
+        manifest: &manifest,
+        wrapped_functions: &bound_functions,
+        sdk_types: &sdk_types,
+        prefix: &prefix,
+    })?;
+    if get_env_flag("PICO_SDK_RS_METADATA") {
+        let metadata_path = out_dir.join("metadata.rs");
+        fs::write(&metadata_path, create_metadata(&code, &prefix)).expect("failed to write metadata.rs");
+        if sync_alternatives {
+            println!("cargo:metadata={}", metadata_path.display());
+        }
+    }
+    Ok((code, board))
+}
+
+/// Generates the bindings of every board in `boards`, each in its own directory under
+/// `out_dir`, and gates each set behind `#[cfg(pico_board = "<board>")]`.
+///
+/// The cfg can only hold one value per compilation, so the board named by `PICO_SDK_RS_BOARD`
+/// or `PICO_BOARD` (or the first one) is the one enabled, and only its wrappers are written to
+/// the binding alternatives. Switching boards within the list reuses the other cmake builds.
+fn generate_combined_binding(target_triple: &str, implicit_include_directories: &[String], out_dir: &Path, boards: &[String]) -> BuildResult<(String, Option<String>)> {
+    if let Some(board) = boards.iter().find(|board| !is_identifier(board)) {
+        return Err(format!("PICO_SDK_RS_BOARDS entry `{}` is not a valid board name", board));
+    }
+    let active_board = active_board(boards);
+    println!(
+        "cargo:rustc-check-cfg=cfg(pico_board, values({}))",
+        boards.iter().map(|board| format!("{:?}", board)).collect::<Vec<_>>().join(", ")
+    );
+    println!("cargo:rustc-cfg=pico_board={:?}", active_board);
+
+    let mut code = String::new();
+    for board in boards {
+        let board_dir = out_dir.join("boards").join(board);
+        fs::create_dir_all(&board_dir).expect("failed create_dir_all");
+        let (board_code, _) = generate_board_binding(target_triple, implicit_include_directories, &board_dir, Some(board), board == &active_board)?;
+        code += &format!(
+            "\n#[cfg(pico_board = \"{0}\")]\nmod board_{0} {{\n{1}\n}}\n#[cfg(pico_board = \"{0}\")]\npub use board_{0}::*;\n",
+            board, board_code
+        );
+    }
+    Ok((code, Some(active_board)))
+}
+
+/// The board of `PICO_SDK_RS_BOARDS` that is enabled: the selected one if listed, else the first.
+fn active_board(boards: &[String]) -> String {
+    selected_board()
+        .filter(|board| boards.contains(board))
+        .unwrap_or(boards[0].clone())
+}
 
-    let clang_arguments = write_wrapper_function(&implicit_include_directories, &out_dir, &entry_path, &mut entry);
+/// Prefix of the wrapper functions the Rust code calls, whatever the C symbols are named.
+const RUST_WRAPPER_PREFIX: &str = "wrapped_";
+
+/// The prefix of the C wrapper functions, `PICO_SDK_RS_WRAPPER_PREFIX` or `wrapped_`. Two
+/// binding crates linked into one firmware need different prefixes.
+fn wrapper_prefix() -> BuildResult<String> {
+    let prefix = get_env("PICO_SDK_RS_WRAPPER_PREFIX").unwrap_or(RUST_WRAPPER_PREFIX.to_string());
+    if is_identifier(&prefix) {
+        Ok(prefix)
+    } else {
+        Err(format!("PICO_SDK_RS_WRAPPER_PREFIX `{}` does not start a valid C identifier", prefix))
+    }
+}
+
+/// Enum styles of `PICO_SDK_RS_ENUM_RULES`, named like bindgen's `--default-enum-style`.
+const ENUM_STYLES: [&str; 6] = ["consts", "moduleconsts", "bitfield", "newtype", "rustified", "rustified_non_exhaustive"];
+
+/// Macros and variables declared in `bindings.rs` unless `PICO_SDK_RS_ALLOWLIST_VARS` is set:
+/// the SDK and board configuration (`PICO_DEFAULT_UART`, ...) and the peripheral base addresses.
+const DEFAULT_ALLOWLIST_VARS: &[&str] = &["PICO_.*", ".*_BASE"];
+
+/// The patterns of `PICO_SDK_RS_ALLOWLIST_VARS`, or [`DEFAULT_ALLOWLIST_VARS`] if it is unset.
+fn allowed_vars() -> Vec<String> {
+    let allowed_vars = split_env_list("PICO_SDK_RS_ALLOWLIST_VARS");
+    if allowed_vars.is_empty() {
+        DEFAULT_ALLOWLIST_VARS.iter().map(|pattern| pattern.to_string()).collect()
+    } else {
+        allowed_vars
+    }
+}
 
-    generate_rust_binding(target_triple, implicit_include_directories, out_dir, &entry_path, clang_arguments);
+/// What [`generate_rust_binding`] generates the bindings of one board from.
+struct BindingInputs<'a> {
+    target_triple: &'a str,
+    implicit_include_directories: &'a [String],
+    /// The entry point with the wrappers, which bindgen parses.
+    entry_path: &'a Path,
+    /// The include directories and definitions of the SDK build.
+    clang_arguments: &'a [String],
+    /// The [`create_build_manifest`] summary for `BUILD_MANIFEST`.
+    manifest: &'a str,
+    /// Names (without the prefix) and arities of the wrappers to declare.
+    wrapped_functions: &'a [(String, usize)],
+    sdk_types: &'a SdkTypes,
+    prefix: &'a str,
 }
 
-fn generate_rust_binding(target_triple: String, implicit_include_directories: Vec<String>, out_dir: &Path, entry_path: &PathBuf, clang_arguments: Vec<String>) {
-    let bindings = bindgen::builder()
+fn generate_rust_binding(inputs: &BindingInputs) -> BuildResult<String> {
+    let BindingInputs { target_triple, implicit_include_directories, entry_path, clang_arguments, manifest, wrapped_functions, sdk_types, prefix } = *inputs;
+    let strict_bindings = get_env_flag("PICO_SDK_RS_WARNINGS_AS_ERRORS");
+    println!("cargo:rustc-check-cfg=cfg(pico_sdk_strict_bindings)");
+    if strict_bindings {
+        println!("cargo:rustc-cfg=pico_sdk_strict_bindings");
+    }
+
+    let converted_enums = split_env_list("PICO_SDK_RS_ENUM_CONVERSIONS");
+    let mut builder = bindgen::builder()
         .header(entry_path.display().to_string())
         .use_core()
         .ctypes_prefix("cty")
         .parse_callbacks(Box::new(bindgen::CargoCallbacks))
+        // bindgen's layout tests dereference null pointers, which rustc warns about.
+        .layout_tests(!strict_bindings)
+        // The complete file is formatted once the code appended below is in place.
+        .rustfmt_bindings(false)
         .detect_include_paths(true)
         .clang_args(clang_arguments)
         .clang_args(implicit_include_directories.iter().map(|path| format!("-I{}", path)))
         .clang_arg(format!("--target={}", target_triple))
-        .whitelist_function("wrapped_.*")
+        // `PICO_SDK_RS_INLINE_WRAPPERS` declares the wrappers `inline`, and `entry.c` instantiates them.
+        .generate_inline_functions(get_env_flag("PICO_SDK_RS_INLINE_WRAPPERS"))
+        .whitelist_function(format!(
+            "{}({})",
+            prefix,
+            wrapped_functions.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join("|")
+        ))
+        // `raspi_pico_sdk::led` needs these whatever else is listed.
+        .whitelist_var("PICO_DEFAULT_LED_PIN")
+        .whitelist_var("CYW43_WL_GPIO_LED_PIN")
+        .whitelist_var(format!("{}.*", CHIP_CONSTANT_PREFIX));
+    for pattern in allowed_vars() {
+        builder = builder.whitelist_var(pattern);
+    }
+    if !sdk_types.names.is_empty() {
+        builder = builder.whitelist_type(format!("({})", sdk_types.names.join("|")));
+    }
+    if !sdk_types.anonymous_enum_constants.is_empty() {
+        builder = builder.whitelist_var(format!("({})", sdk_types.anonymous_enum_constants.join("|")));
+    }
+    let board_pins = get_env_flag("PICO_SDK_RS_BOARD_PINS");
+    if board_pins {
+        builder = builder.whitelist_var(format!("{}.*_PIN", BOARD_PIN_PREFIX));
+    }
+    for name in &converted_enums {
+        builder = builder.rustified_enum(format!("^{}$", name));
+    }
+    for (pattern, style) in parse_enum_rules(&split_env_list("PICO_SDK_RS_ENUM_RULES"))? {
+        let pattern = format!("^(?:{})$", pattern);
+        builder = match style {
+            "consts" => builder.constified_enum(pattern),
+            "moduleconsts" => builder.constified_enum_module(pattern),
+            "bitfield" => builder.bitfield_enum(pattern),
+            "newtype" => builder.newtype_enum(pattern),
+            "rustified" => builder.rustified_enum(pattern),
+            _ => builder.rustified_non_exhaustive_enum(pattern),
+        };
+    }
+    // `typedef int32_t alarm_id_t;` becomes `#[repr(transparent)] pub struct alarm_id_t(pub i32);`,
+    // and the wrappers taking or returning it use the struct.
+    for name in split_env_list("PICO_SDK_RS_NEWTYPE_HANDLES") {
+        builder = builder.new_type_alias(format!("^{}$", name));
+    }
+    let bindings = builder
         .generate()
-        .expect("failed to generate binding");
-    bindings.write_to_file(out_dir.join("bindings.rs"))
-        .expect("failed to write bindings.rs");
+        .map_err(|()| "bindgen failed to generate the bindings, see the clang errors above".to_string())?;
+    let mut code = gate_wrapper_declarations(&rename_wrappers(&bindings.to_string(), prefix))
+        .replace(&format!("pub const {}", CHIP_CONSTANT_PREFIX), "pub const ");
+    code += &format!("\n/// Summary of the SDK this crate was generated from.\npub static BUILD_MANIFEST: &str = {:?};\n", manifest);
+    code += &create_arity_check(wrapped_functions);
+    if get_env_flag("PICO_SDK_RS_CONFIG_BUILDERS") {
+        code += &create_config_builders(&code);
+    }
+    code += &create_host_stubs(&code);
+    code += &create_enum_conversions(&code, &converted_enums)?;
+    if board_pins {
+        code += &create_board_pins(&code);
+    }
+    code += &create_send_sync_impls(&defined_type_names(&code), &split_env_list("PICO_SDK_RS_SEND_SYNC_TYPES"))?;
+    Ok(code)
+}
+
+/// The `(pattern, style)` pairs of the `<pattern>=<style>` entries of `PICO_SDK_RS_ENUM_RULES`.
+fn parse_enum_rules(rules: &[String]) -> BuildResult<Vec<(&str, &str)>> {
+    rules.iter()
+        .map(|rule| {
+            let (pattern, style) = rule.rsplit_once('=')
+                .ok_or_else(|| format!("PICO_SDK_RS_ENUM_RULES entry `{}` is not of the form `<pattern>=<style>`", rule))?;
+            check_one_of(&format!("PICO_SDK_RS_ENUM_RULES style of `{}`", rule), style, &ENUM_STYLES)?;
+            Ok((pattern, style))
+        })
+        .collect()
+}
+
+/// Names of the structs and type aliases defined in the generated code.
+fn defined_type_names(code: &str) -> Vec<String> {
+    code.split(['\n', ';', '{'])
+        .filter_map(|item| {
+            let item = item.trim_start();
+            let rest = item.strip_prefix("pub struct ").or_else(|| item.strip_prefix("pub type "))?;
+            rest.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).next().map(str::to_string)
+        })
+        .collect()
+}
+
+const BOARD_PIN_PREFIX: &str = "PICO_DEFAULT_";
+
+/// `pub mod board` with the `PICO_DEFAULT_*_PIN` constants of the board header as `u8`s
+/// without the prefix, e.g. `board::I2C_SDA_PIN`. Constants that are not a pin number, such as
+/// the -1 some boards use for "not connected", are left out.
+fn create_board_pins(code: &str) -> String {
+    let mut pins = code.split(';')
+        .filter_map(|item| {
+            let rest = item.trim_start().strip_prefix("pub const ")?;
+            let (name, rest) = rest.split_at(rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?);
+            let name = name.strip_prefix(BOARD_PIN_PREFIX).filter(|name| name.ends_with("_PIN"))?;
+            let value = rest[rest.find('=')? + 1..].trim().parse::<u8>().ok()?;
+            Some(format!("    pub const {}: u8 = {};\n", name, value))
+        })
+        .collect::<Vec<_>>();
+    pins.sort();
+    pins.dedup();
+    format!("\n/// Default pin assignments of the board header.\npub mod board {{\n{}}}\n", pins.concat())
+}
+
+/// `From<enum> for <repr>` and `TryFrom<<repr>> for enum` for the enums listed in
+/// `PICO_SDK_RS_ENUM_CONVERSIONS`, which bindgen was told to emit as Rust enums.
+fn create_enum_conversions(code: &str, enums: &[String]) -> BuildResult<String> {
+    let mut conversions = String::new();
+    for name in enums {
+        let (repr, variants) = find_rust_enum(code, name).ok_or_else(|| {
+            format!("PICO_SDK_RS_ENUM_CONVERSIONS lists `{}`, which is not an enum in the generated bindings", name)
+        })?;
+        let arms = variants.iter()
+            .map(|(variant, value)| format!("            {} => Ok({}::{}),\n", value, name, variant))
+            .collect::<String>();
+        conversions += &format!(
+            concat!(
+                "\nimpl From<{0}> for {1} {{\n",
+                "    fn from(value: {0}) -> {1} {{\n",
+                "        value as {1}\n",
+                "    }}\n",
+                "}}\n",
+                "\nimpl core::convert::TryFrom<{1}> for {0} {{\n",
+                "    type Error = {1};\n\n",
+                "    /// Returns the value itself as the error if no variant has it.\n",
+                "    fn try_from(value: {1}) -> Result<{0}, {1}> {{\n",
+                "        match value {{\n",
+                "{2}",
+                "            _ => Err(value),\n",
+                "        }}\n",
+                "    }}\n",
+                "}}\n",
+            ),
+            name, repr, arms
+        );
+    }
+    Ok(conversions)
+}
+
+/// The `#[repr]` type and the `(variant, value)` pairs of `pub enum <name>` in `code`. Works on
+/// formatted code as well as on the token spacing of unformatted bindgen output.
+fn find_rust_enum(code: &str, name: &str) -> Option<(String, Vec<(String, String)>)> {
+    let (start, body) = code.match_indices("pub enum ").find_map(|(start, keyword)| {
+        let rest = &code[start + keyword.len()..];
+        let rest = rest.strip_prefix(name)?.trim_start();
+        Some((start, rest.strip_prefix('{')?))
+    })?;
+    let body = &body[..body.find('}')?];
+    let repr = code[..start].rsplit_once("repr").map(|(_, after)| after)?;
+    let repr = repr.trim_start().strip_prefix('(')?;
+    let repr = repr[..repr.find(')')?].trim().to_string();
+    let variants = strip_attributes(body).split(',')
+        .filter_map(|variant| {
+            let (variant, value) = variant.split_once('=')?;
+            Some((variant.trim().to_string(), value.split_whitespace().collect()))
+        })
+        .collect();
+    Some((repr, variants))
+}
+
+/// Removes `///` comments and `#[...]` attributes (such as the `#[doc]` form of comments in
+/// unformatted output), which may contain `,` or `=`.
+fn strip_attributes(code: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = code;
+    loop {
+        let comment = rest.find("///");
+        let attribute = rest.find('#');
+        let start = match (comment, attribute) {
+            (Some(comment), Some(attribute)) => comment.min(attribute),
+            (Some(start), None) | (None, Some(start)) => start,
+            (None, None) => break,
+        };
+        stripped.push_str(&rest[..start]);
+        let end = if Some(start) == comment {
+            rest[start..].find('\n').map_or(rest.len(), |end| start + end)
+        } else {
+            rest[start..].find(']').map_or(rest.len(), |end| start + end + 1)
+        };
+        rest = &rest[end..];
+    }
+    stripped.push_str(rest);
+    stripped
 }
 
-fn write_wrapper_function(implicit_include_directories: &Vec<String>, out_dir: &Path, entry_path: &PathBuf, entry: &mut File) -> Vec<String> {
-    let (include_directories, definitions) = get_compile_options(out_dir, entry_path);
+/// `unsafe impl Send`/`Sync` for the SDK handle types listed in `PICO_SDK_RS_SEND_SYNC_TYPES`.
+///
+/// Only explicitly listed types get them: it is sound for instance structs like `i2c_inst_t`
+/// that describe a fixed peripheral, not for SDK types in general.
+fn create_send_sync_impls(defined_types: &[String], types: &[String]) -> BuildResult<String> {
+    let mut code = String::new();
+    for name in types {
+        if !defined_types.contains(name) {
+            return Err(format!("PICO_SDK_RS_SEND_SYNC_TYPES lists `{}`, which is not a type in the generated bindings", name));
+        }
+        code += &format!(
+            "\n// SAFETY: listed in PICO_SDK_RS_SEND_SYNC_TYPES as a handle to fixed hardware.\nunsafe impl Send for {0} {{}}\nunsafe impl Sync for {0} {{}}\n",
+            name
+        );
+    }
+    Ok(code)
+}
+
+fn to_upper_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
 
-    let clang = Clang::new().expect("failed Clang::new()");
+fn write_wrapper_function(implicit_include_directories: &[String], include_directories: &[String], definitions: Vec<String>, entry_path: &PathBuf, entry: &mut File, sync_alternatives: bool, prefix: &str) -> BuildResult<(Vec<String>, Vec<WrappedFunction>, SdkTypes)> {
+    let clang = Clang::new().map_err(|e| format!(
+        "failed to load libclang ({}); install it (e.g. `libclang-dev` on Debian/Ubuntu) or point `PICO_SDK_RS_LIBCLANG` or `LIBCLANG_PATH` at it",
+        e
+    ))?;
+    if std::env::var("PICO_SDK_RS_LIBCLANG").is_ok() {
+        println!("cargo:warning=PICO_SDK_RS_LIBCLANG: using {}", clang::get_version());
+    }
     let index = Index::new(&clang, false, true);
     let mut parser = index.parser(entry_path);
 
@@ -66,23 +771,114 @@ fn write_wrapper_function(implicit_include_directories: &Vec<String>, out_dir: &
         .collect::<Vec<_>>();
     parser.arguments(&clang_arguments);
     parser.skip_function_bodies(true);
+    // The macros of the SDK headers, for the `_u(...)` ones bindgen cannot evaluate.
+    parser.detailed_preprocessing_record(true);
 
-    let parsed = parser.parse().expect("failed to parse");
+    let parsed = parser.parse().map_err(|e| format!("libclang failed to parse {}: {}", entry_path.display(), e))?;
     let mut code = String::from(GENERATED_CODE_MARKER);
-    for entity in parsed.get_entity().get_children() {
-        if is_not_in_include_directories(&include_directories, &entity) {
+    let mut inline_header = get_env_flag("PICO_SDK_RS_INLINE_WRAPPERS").then(|| String::from(INLINE_HEADER_PROLOGUE));
+    let mut wrapped_functions = Vec::new();
+    let mut signatures = Vec::new();
+    let mut sdk_types = SdkTypes::default();
+    // `va_list` for the wrappers of variadic functions.
+    code += "#include <stdarg.h>\n\n";
+    let entities = parsed.get_entity().get_children();
+    // Variadic functions are wrapped through their `va_list` variant, which may well be declared
+    // outside the include directories (`vprintf` in the toolchain's stdio.h).
+    let declared_functions = entities.iter()
+        .filter(|entity| entity.get_kind() == EntityKind::FunctionDecl)
+        .filter_map(|entity| entity.get_name())
+        .collect::<Vec<_>>();
+    let function_filter = FunctionFilter::from_env()?;
+    // A function declared again, e.g. by a second prototype in another header, is wrapped once.
+    let mut seen_functions = HashSet::new();
+    let mut sdk_headers = SdkHeaders::new(include_directories);
+    let allowed_vars = Regex::new(&format!("^(?:{})$", allowed_vars().join("|")))
+        .map_err(|e| format!("PICO_SDK_RS_ALLOWLIST_VARS is not a list of valid regular expressions: {}", e))?;
+    let mut unsigned_macros = Vec::new();
+    for entity in entities {
+        if entity.is_preprocessing() {
+            if entity.get_kind() == EntityKind::MacroDefinition && is_unsigned_macro(&entity, &allowed_vars) && sdk_headers.declares(&entity) {
+                unsigned_macros.push(entity.get_name().unwrap());
+            }
+            continue;
+        }
+        if !sdk_headers.declares(&entity) {
             println!("ignored: {:?}", entity);
             continue;
         }
+        sdk_types.collect(&entity);
         if entity.get_kind() == EntityKind::FunctionDecl {
-            let wrapper_function = create_wrapper_function(&entity);
-            code += &wrapper_function;
+            if !function_filter.is_wrapped(&entity.get_name().unwrap()) {
+                println!("ignored: {:?}", entity);
+                continue;
+            }
+            if !seen_functions.insert(entity.get_name().unwrap()) {
+                println!("skipped repeated declaration: {:?}", entity);
+                continue;
+            }
+            let va_list_variant = if entity.get_type().unwrap().is_variadic() {
+                let name = entity.get_name().unwrap();
+                match find_va_list_variant(&declared_functions, &name) {
+                    Some(variant) => Some(variant),
+                    None => {
+                        println!("skipped variadic function {} without a va_list variant", name);
+                        continue;
+                    }
+                }
+            } else {
+                None
+            };
+            let doc_comment = create_doc_comment(&entity);
+            code += &doc_comment;
+            if let Some(header) = &mut inline_header {
+                code += &create_wrapper_declaration(&entity, INLINE_INSTANTIATION_QUALIFIERS, va_list_variant.as_deref(), prefix);
+                *header += &doc_comment;
+                *header += &create_wrapper_function(&entity, INLINE_WRAPPER_QUALIFIERS, va_list_variant.as_deref(), prefix);
+            } else {
+                code += &create_wrapper_function(&entity, "", va_list_variant.as_deref(), prefix);
+            }
+            let arity = entity.get_children()
+                .into_iter()
+                .filter(|child| child.get_kind() == EntityKind::ParmDecl)
+                .count() + va_list_variant.iter().count();
+            wrapped_functions.push((entity.get_name().unwrap(), arity));
+            signatures.push(create_wrapper_signature(&entity, va_list_variant.is_some(), prefix));
         }
     }
+    if inline_header.is_some() {
+        code += &format!("\n#define {} inline\n#include \"{}\"\n", INLINE_WRAPPER_MACRO, INLINE_HEADER_NAME);
+    }
+    code += &create_unsigned_constants(&unsigned_macros);
+    if let Some(symbol) = get_env("PICO_SDK_RS_ENTRY_MAIN_SYMBOL") {
+        code += &create_entry_main(&symbol)?;
+    }
 
+    let alternatives = if sync_alternatives { get_c_binding_alternatives() } else { Vec::new() };
+    // Written first, as the entry point includes it.
+    if let Some(mut header) = inline_header {
+        header += INLINE_HEADER_EPILOGUE;
+        let header_path = entry_path.with_file_name(INLINE_HEADER_NAME);
+        fs::write(&header_path, &header).expect("failed to write the inline wrapper header");
+        if sync_alternatives {
+            println!("cargo:inline_wrappers={}", header_path.display());
+        }
+        for alternative_path in &alternatives {
+            let alternative_header = Path::new(alternative_path).with_file_name(INLINE_HEADER_NAME);
+            if let Err(e) = fs::write(&alternative_header, &header) {
+                eprintln!("failed to write {} by {}", alternative_header.display(), e);
+            }
+        }
+    }
     entry.write_all(code.as_bytes())
         .expect("failed to write to entry.c");
-    for alternative_path in get_c_binding_alternatives() {
+    report_implicit_declarations(&index, entry_path, &clang_arguments, include_directories);
+    if sync_alternatives {
+        if let Some(golden) = get_env("PICO_SDK_RS_ABI_GOLDEN") {
+            check_abi_golden(&golden, signatures, get_env_flag("PICO_SDK_RS_ABI_GOLDEN_UPDATE"))?;
+        }
+    }
+    for alternative_path in alternatives {
         let mut file = match File::open(&alternative_path) {
             Ok(a) => a,
             Err(e) => {
@@ -103,134 +899,1591 @@ fn write_wrapper_function(implicit_include_directories: &Vec<String>, out_dir: &
             continue;
         }
     }
-    clang_arguments
+    Ok((clang_arguments, wrapped_functions, sdk_types))
 }
 
-fn get_c_binding_alternatives() -> Vec<String> {
-    std::env::var("PICO_SDK_RS_C_BINDING_ALTERNATIVES").unwrap_or(String::new())
-        .trim()
-        .split(":")
-        .map(str::to_string)
-        .collect()
+/// The functions selected by `PICO_SDK_RS_FUNCTION_ALLOWLIST` and
+/// `PICO_SDK_RS_FUNCTION_BLOCKLIST`, each a `:`-separated list of regular expressions matching
+/// whole function names. A function is wrapped if it matches the allowlist (or no allowlist is
+/// set) and nothing on the blocklist.
+struct FunctionFilter {
+    allowlist: Vec<Regex>,
+    blocklist: Vec<Regex>,
 }
 
-fn get_compile_options(out_dir: &Path, entry_path: &PathBuf) -> (Vec<String>, Vec<String>) {
-    cmake::Config::new("cmake_pico")
-        .define("ENTRY_POINT", entry_path)
-        .no_build_target(true)
-        .build();
+impl FunctionFilter {
+    fn from_env() -> BuildResult<FunctionFilter> {
+        Ok(FunctionFilter {
+            allowlist: compile_patterns("PICO_SDK_RS_FUNCTION_ALLOWLIST")?,
+            blocklist: compile_patterns("PICO_SDK_RS_FUNCTION_BLOCKLIST")?,
+        })
+    }
 
-    fs::create_dir_all(out_dir.join("test")).expect("failed create_dir_all");
-    std::env::set_var("OUT_DIR", out_dir.join("test").display().to_string());
-    cmake::Config::new("cmake_depend")
-        .target(guess_host_triple::guess_host_triple().unwrap_or("x86_64-unknown-linux-gnu"))
-        .define(
-            "DEPENDINFO_PATH",
-            out_dir.join("build/CMakeFiles/pico.dir/DependInfo.cmake"),
-        )
-        .define(
-            "INCLUDE_PATH_FILE",
-            out_dir.join("include_path"),
-        )
-        .define("DEFINITIONS_FILE", Path::new(&out_dir).join("definitions"))
-        .build_target("write")
-        .build();
-    std::env::set_var("OUT_DIR", out_dir.display().to_string());
+    fn is_wrapped(&self, name: &str) -> bool {
+        (self.allowlist.is_empty() || self.allowlist.iter().any(|pattern| pattern.is_match(name)))
+            && !self.blocklist.iter().any(|pattern| pattern.is_match(name))
+    }
+}
 
-    let include_directories = fs::read_to_string(out_dir.join("include_path"))
-        .expect("failed to read include_path")
-        .split(':')
-        .map(|path| {
-            out_dir.join("build").join(path.trim())
-                .display().to_string()
+fn compile_patterns(name: &str) -> BuildResult<Vec<Regex>> {
+    split_env_list(name)
+        .iter()
+        .map(|pattern| {
+            Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(|e| format!("{} entry `{}` is not a valid regular expression: {}", name, pattern, e))
         })
-        .collect::<Vec<_>>();
-    let definitions = fs::read_to_string(out_dir.join("definitions")).expect("failed to read definitions")
-        .split(':').map(str::to_string).collect::<Vec<_>>();
-    (include_directories, definitions)
+        .collect()
 }
 
-fn create_wrapper_function(entity: &Entity) -> String {
-    let (formal_arguments, actual_arguments) = entity.get_children()
-        .into_iter()
-        .filter_map(|entity| {
-            if entity.get_kind() == EntityKind::ParmDecl {
-                let param_name = entity.get_name().unwrap();
-                Some((
-                    format!(
-                        "{} {}",
-                        entity.get_type().unwrap().get_display_name(),
-                        param_name
-                    ),
-                    param_name))
-            } else { None }
-        })
-        .fold((String::new(), String::new()), |(mut formal, mut actual), (f, a)| {
-            if !formal.is_empty() {
-                formal.push_str(", ");
-                actual.push_str(", ");
+/// The types declared by the SDK headers, which are declared in `bindings.rs` even if no
+/// wrapper uses them, so that e.g. the `gpio_function` constants are available for
+/// `wrapped_gpio_set_function`.
+#[derive(Debug, Default)]
+struct SdkTypes {
+    names: Vec<String>,
+    /// The constants of `enum { ... };`, which has no name to declare the enum by.
+    anonymous_enum_constants: Vec<String>,
+}
+
+impl SdkTypes {
+    fn collect(&mut self, entity: &Entity) {
+        match entity.get_kind() {
+            EntityKind::EnumDecl if entity.is_anonymous() => {
+                self.anonymous_enum_constants.extend(
+                    entity.get_children()
+                        .into_iter()
+                        .filter(|child| child.get_kind() == EntityKind::EnumConstantDecl)
+                        .filter_map(|child| child.get_name()),
+                );
             }
-            formal.push_str(&f);
-            actual.push_str(&a);
-            (formal, actual)
-        });
+            EntityKind::EnumDecl | EntityKind::StructDecl | EntityKind::UnionDecl | EntityKind::TypedefDecl => {
+                if let Some(name) = entity.get_name().filter(|name| is_identifier(name)) {
+                    if !self.names.contains(&name) {
+                        self.names.push(name);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
 
-    let return_type = entity.get_result_type().unwrap().get_display_name();
-    let function_name = entity.get_name().unwrap();
-    format!(
-        "{} wrapped_{}({}) {{ {}{1}({}); }}\n",
-        return_type,
-        function_name,
-        formal_arguments,
-        if return_type == "void" { "" } else { "return " },
-        actual_arguments
-    )
+/// A C `main` that hands over to the Rust function exported as `symbol`, which must be
+/// `#[no_mangle] pub extern "C" fn symbol()`.
+fn create_entry_main(symbol: &str) -> BuildResult<String> {
+    if !is_identifier(symbol) {
+        return Err(format!("PICO_SDK_RS_ENTRY_MAIN_SYMBOL `{}` is not a valid C identifier", symbol));
+    }
+    Ok(format!("\nvoid {0}(void);\n\nint main(void) {{\n    {0}();\n    return 0;\n}}\n", symbol))
 }
 
-fn is_not_in_include_directories(include_directories: &Vec<String>, entity: &Entity) -> bool {
-    let location = entity.get_location().unwrap();
-    let (location, _, _) = location.get_presumed_location();
-    let location = location.into_bytes();
-    include_directories.iter().all(|dir| {
-        let dir = dir.as_bytes();
-        dir.len() > location.len() || &location[..dir.len()] != dir
-    })
+fn select_used_functions(wrapped_functions: Vec<WrappedFunction>, used_functions: &[String], prefix: &str) -> Vec<WrappedFunction> {
+    if used_functions.is_empty() {
+        return wrapped_functions;
+    }
+    let used_functions = used_functions.iter()
+        .map(|name| name.strip_prefix(RUST_WRAPPER_PREFIX).or_else(|| name.strip_prefix(prefix)).unwrap_or(name))
+        .collect::<Vec<_>>();
+    for name in &used_functions {
+        if wrapped_functions.iter().all(|(wrapped, _)| wrapped != name) {
+            println!("cargo:warning=PICO_SDK_RS_USED_FUNCTIONS: `{}` is not a wrapped function", name);
+        }
+    }
+    wrapped_functions.into_iter()
+        .filter(|(name, _)| used_functions.contains(&name.as_str()))
+        .collect()
 }
 
-fn create_entry_point_file_to_out_dir(out_dir: &Path) -> (PathBuf, File) {
-    let entry_c = std::env::var("PICO_SDK_RS_CUSTOM_ENTRY_POINT")
-        .map(|entry_point|
-            match fs::read_to_string(&entry_point) {
-                Ok(mut s) => {
-                    println!("cargo:rerun-if-changed={}", entry_point);
-                    s.drain(s.find(GENERATED_CODE_MARKER).unwrap_or(s.len())..);
-                    Ok(s)
-                }
-                e => e
-            })
-        .unwrap_or_else(|_| fs::read_to_string("cmake_pico/entry.c"))
-        .expect("failed to read entry.c");
+fn report_implicit_declarations(index: &Index, entry_path: &PathBuf, clang_arguments: &[String], include_directories: &[String]) {
+    let mut parser = index.parser(entry_path);
+    parser.arguments(clang_arguments);
+    let parsed = match parser.parse() {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("failed to parse the generated entry.c by {}", e);
+            return;
+        }
+    };
+    for diagnostic in parsed.get_diagnostics() {
+        if diagnostic.get_severity() < Severity::Warning {
+            continue;
+        }
+        let function = match implicitly_declared_function(&diagnostic.get_text()) {
+            Some(function) => function,
+            None => continue,
+        };
+        let (file, line, _) = diagnostic.get_location().get_presumed_location();
+        let headers = find_declaring_headers(include_directories, &function);
+        if headers.is_empty() {
+            println!("cargo:warning={}:{}: `{}` is implicitly declared and no SDK header declares it", file, line, function);
+        } else {
+            println!(
+                "cargo:warning={}:{}: `{}` is implicitly declared; add `#include <{}>` to the entry point",
+                file, line, function, headers.join(">` or `#include <")
+            );
+        }
+    }
+}
 
-    let entry_path = out_dir.join("entry.c");
-    let mut entry = File::create(&entry_path).expect("failed to create entry.c");
-    entry.write_all(entry_c.as_bytes())
-        .expect("failed to write to entry.c");
-    (entry_path, entry)
+fn implicitly_declared_function(message: &str) -> Option<String> {
+    if !message.contains("implicit declaration of function") && !message.contains("call to undeclared function") {
+        return None;
+    }
+    let start = message.find('\'')? + 1;
+    let end = start + message[start..].find('\'')?;
+    Some(message[start..end].to_string())
 }
 
-fn get_implicit_include_directories(target_triple: &str) -> Vec<String> {
-    let mut gcc_command = if target_triple == "thumbv6m-none-eabi" {
-        Command::new("arm-none-eabi-gcc")
-    } else {
-        Command::new("gcc")
+fn find_declaring_headers(include_directories: &[String], function: &str) -> Vec<String> {
+    let mut headers = Vec::new();
+    for dir in include_directories {
+        let dir = Path::new(dir);
+        let mut files = Vec::new();
+        collect_headers(dir, &mut files);
+        for file in files {
+            let declares = fs::read_to_string(&file)
+                .map(|content| declares_function(&content, function))
+                .unwrap_or(false);
+            if declares {
+                let header = file.strip_prefix(dir).unwrap_or(&file).display().to_string();
+                if !headers.contains(&header) {
+                    headers.push(header);
+                }
+            }
+        }
+    }
+    headers
+}
+
+fn collect_headers(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_headers(&path, files);
+        } else if path.extension().is_some_and(|extension| extension == "h") {
+            files.push(path);
+        }
+    }
+}
+
+fn declares_function(content: &str, function: &str) -> bool {
+    content.match_indices(function).any(|(position, _)| {
+        let line_start = content[..position].rfind('\n').map_or(0, |newline| newline + 1);
+        let before = content[line_start..position].trim_end_matches(|c: char| c.is_whitespace() || c == '*');
+        let preceding_word = before.rsplit(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).next().unwrap_or("");
+        let after = content[position + function.len()..].trim_start().chars().next();
+        let is_name = !content[line_start..position].ends_with(|c: char| c.is_ascii_alphanumeric() || c == '_');
+        // A declaration has its return type right before the name; calls do not.
+        is_name && after == Some('(') && !preceding_word.is_empty() && preceding_word != "return"
+    })
+}
+
+fn create_build_manifest(sdk_version: Option<&str>, board: Option<&str>, wrapper_count: usize) -> String {
+    format!(
+        "pico-sdk={} board={} wrappers={}",
+        sdk_version.unwrap_or("unknown"),
+        board.unwrap_or("unknown"),
+        wrapper_count
+    )
+}
+
+fn create_arity_check(wrapped_functions: &[(String, usize)]) -> String {
+    let mut code = String::from(
+        "\n#[cfg(feature = \"arity-check\")]\n#[allow(dead_code)]\nfn check_wrapper_arity() {\n    fn placeholder<T>() -> T { loop {} }\n    unsafe {\n",
+    );
+    for (name, arity) in wrapped_functions {
+        code += &format!(
+            "        wrapped_{}({});\n",
+            name,
+            vec!["placeholder()"; *arity].join(", ")
+        );
+    }
+    code += "    }\n}\n";
+    code
+}
+
+/// Keeps the Rust names of the wrappers at `wrapped_*` when the C symbols have another
+/// `prefix`, so `raspi_pico_sdk` works with any prefix.
+fn rename_wrappers(bindings: &str, prefix: &str) -> String {
+    if prefix == RUST_WRAPPER_PREFIX {
+        return bindings.to_string();
+    }
+    let declaration = format!("pub fn {}", prefix);
+    let mut code = String::new();
+    let mut rest = bindings;
+    while let Some(start) = rest.find(&declaration) {
+        let name_start = start + declaration.len();
+        let name_end = rest[name_start..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .map_or(rest.len(), |end| name_start + end);
+        let name = &rest[name_start..name_end];
+        code += &rest[..start];
+        code += &format!("#[link_name = \"{}{}\"] pub fn {}{}", prefix, name, RUST_WRAPPER_PREFIX, name);
+        rest = &rest[name_end..];
+    }
+    code += rest;
+    code
+}
+
+/// Marks the setters [`create_config_builders`] chains, such as `pwm_config_set_wrap` and
+/// `sm_config_set_out_pins`.
+const CONFIG_SETTER_MARKER: &str = "config_set_";
+
+/// Keywords a setter name can end in, which are escaped as raw identifiers.
+const RUST_KEYWORDS: [&str; 12] = ["as", "fn", "for", "if", "impl", "in", "loop", "match", "mod", "ref", "type", "use"];
+
+/// A builder for every SDK config struct with setters, e.g. `PwmConfigBuilder` for `pwm_config`.
+///
+/// A setter is a wrapper without a return value whose name contains `config_set_` and whose
+/// first parameter is `*mut <struct>`; it becomes the builder method named after what follows
+/// `config_set_`, taking the remaining parameters. A wrapper without parameters returning the
+/// struct and named `*default*config*` (`pwm_get_default_config`) is what `new` starts from;
+/// without one, `new` starts from the zeroed struct. `from_raw` starts from any other value,
+/// such as the result of `dma_channel_get_default_config(channel)`.
+fn create_config_builders(bindings: &str) -> String {
+    let declarations = wrapper_declarations(bindings);
+    let mut builders: Vec<(&str, Vec<&WrapperDeclaration>)> = Vec::new();
+    for declaration in &declarations {
+        let name = declaration.name();
+        if !name.contains(CONFIG_SETTER_MARKER) || declaration.return_type().is_some() {
+            continue;
+        }
+        let config_type = match declaration.parameters().first().and_then(|(_, parameter_type)| parameter_type.strip_prefix("* mut ")) {
+            Some(config_type) if is_identifier(config_type) => config_type,
+            _ => continue,
+        };
+        match builders.iter_mut().find(|(existing, _)| *existing == config_type) {
+            Some((_, setters)) => setters.push(declaration),
+            None => builders.push((config_type, vec![declaration])),
+        }
+    }
+
+    let mut code = String::new();
+    for (config_type, setters) in builders {
+        let builder = format!("{}Builder", to_upper_camel_case(config_type));
+        let default_getter = declarations.iter().find(|declaration| {
+            let name = declaration.name();
+            declaration.parameters().is_empty()
+                && declaration.return_type() == Some(config_type)
+                && name.contains("default")
+                && name.contains("config")
+        });
+        let (new_doc, new_body) = match default_getter {
+            Some(getter) => (format!("The SDK's default (`{}`).", getter.name()), format!("{}()", getter.name())),
+            None => ("The zeroed struct.".to_string(), "core::mem::zeroed()".to_string()),
+        };
+        code += &format!(
+            concat!(
+                "\n/// Builder over `{0}` chaining its setter wrappers.\n",
+                "pub struct {1} {{\n    config: {0},\n}}\n\n",
+                "#[allow(clippy::new_without_default)]\n",
+                "impl {1} {{\n",
+                "    /// {2}\n",
+                "    pub fn new() -> Self {{\n        Self {{ config: unsafe {{ {3} }} }}\n    }}\n\n",
+                "    pub fn from_raw(config: {0}) -> Self {{\n        Self {{ config }}\n    }}\n",
+            ),
+            config_type, builder, new_doc, new_body
+        );
+        for setter in setters {
+            let name = setter.name();
+            let method = &name[name.find(CONFIG_SETTER_MARKER).unwrap() + CONFIG_SETTER_MARKER.len()..];
+            let method = if RUST_KEYWORDS.contains(&method) { format!("r#{}", method) } else { method.to_string() };
+            let parameters = setter.parameters();
+            let arguments = parameters[1..].iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            code += &format!(
+                "\n    pub fn {}(mut self{}) -> Self {{\n        unsafe {{ {}(&mut self.config{}) }};\n        self\n    }}\n",
+                method,
+                parameters[1..].iter().map(|(name, parameter_type)| format!(", {}: {}", name, parameter_type)).collect::<String>(),
+                name,
+                arguments.iter().map(|argument| format!(", {}", argument)).collect::<String>()
+            );
+        }
+        code += &format!("\n    pub fn build(self) -> {} {{\n        self.config\n    }}\n}}\n", config_type);
+    }
+    code
+}
+
+/// The cfg under which [`create_host_stubs`] replaces the wrapper declarations: the
+/// `host-stubs` feature, on anything but the Arm cores the SDK is built for.
+const HOST_STUBS_CFG: &str = "all(feature = \"host-stubs\", not(target_arch = \"arm\"))";
+
+/// Definitions of every declared `wrapped_*` function for running code on the host, built from
+/// the declarations bindgen generated. They are plain Rust functions that
+/// [`gate_wrapper_declarations`] puts in place of the declarations, so nothing is exported
+/// under the C symbols. The stubs return what [`host_stub_value`] picks and report each call to
+/// the hook set with `host_stubs::set_call_hook`.
+fn create_host_stubs(bindings: &str) -> String {
+    let declarations = wrapper_declarations(bindings);
+    let mut code = format!(
+        concat!(
+            "\n#[cfg({})]\n#[allow(unused_variables, clippy::missing_safety_doc)]\npub mod host_stubs {{\n",
+            "    use super::*;\n",
+            "    use core::sync::atomic::{{AtomicUsize, Ordering}};\n\n",
+            "    /// The hook as a `usize`, 0 for none, so it can be swapped from any thread.\n",
+            "    static CALL_HOOK: AtomicUsize = AtomicUsize::new(0);\n\n",
+            "    /// Calls `hook` with the name of every stubbed function that is called.\n",
+            "    pub fn set_call_hook(hook: Option<fn(&'static str)>) {{\n",
+            "        CALL_HOOK.store(hook.map_or(0, |hook| hook as usize), Ordering::SeqCst)\n",
+            "    }}\n\n",
+            "    fn called(name: &'static str) {{\n",
+            "        let hook = CALL_HOOK.load(Ordering::SeqCst);\n",
+            "        if hook != 0 {{\n",
+            "            let hook: fn(&'static str) = unsafe {{ core::mem::transmute(hook) }};\n",
+            "            hook(name)\n",
+            "        }}\n",
+            "    }}\n",
+        ),
+        HOST_STUBS_CFG
+    );
+    for declaration in &declarations {
+        let name = declaration.name();
+        let (abi, body) = match declaration.return_type().map(|return_type| host_stub_value(bindings, return_type)) {
+            None => ("C", format!("called({:?})", name)),
+            Some(Some(value)) => ("C", format!("called({:?}); {}", name, value)),
+            // Unwinding out of the stub lets the test that called it fail on its own.
+            Some(None) => ("C-unwind", format!("called({0:?}); panic!(\"{0} has no value to return on the host\")", name)),
+        };
+        code += &format!("\n    pub unsafe extern \"{}\" fn {} {{ {} }}\n", abi, declaration.signature, body);
+    }
+    code += "}\n";
+    // With `PICO_SDK_RS_BOARDS`, every board declares the wrapper again.
+    let mut names = declarations.iter().map(|declaration| declaration.name()).collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+    code += &format!("#[cfg({})]\npub use host_stubs::{{{}}};\n", HOST_STUBS_CFG, names.join(", "));
+    code
+}
+
+/// What the host stub of a function returning `return_type` returns: the first variant of a
+/// Rust enum, which a zeroed value need not be one of, and zeroed values (0, `false`, null,
+/// `None`) otherwise. `None` for `!` and enums without variants, whose stubs panic instead.
+fn host_stub_value(bindings: &str, return_type: &str) -> Option<String> {
+    if return_type == "!" {
+        return None;
+    }
+    let mut enum_name = return_type.to_string();
+    while let Some(target) = aliased_type(bindings, &enum_name) {
+        enum_name = target;
+    }
+    match find_rust_enum(bindings, &enum_name) {
+        Some((_, variants)) => variants.first().map(|(variant, _)| format!("{}::{}", enum_name, variant)),
+        None => Some("core::mem::zeroed()".to_string()),
+    }
+}
+
+/// The type `name` stands for in `code` by `pub type <name> = <type>;` or, as bindgen writes
+/// the typedefs of Rust enums, `pub use self::<type> as <name>;`.
+fn aliased_type(code: &str, name: &str) -> Option<String> {
+    code.split([';', '{', '}']).find_map(|item| {
+        let item = item.split_whitespace().collect::<Vec<_>>().join(" ").replace(" ::", "::").replace(":: ", "::");
+        let target = match item.strip_prefix("pub type ") {
+            Some(alias) => alias.strip_prefix(name)?.trim_start().strip_prefix('=')?.trim(),
+            None => {
+                let (target, alias) = item.strip_prefix("pub use self::")?.split_once(" as ")?;
+                if alias != name {
+                    return None;
+                }
+                target
+            }
+        };
+        target.chars().all(|c| c.is_ascii_alphanumeric() || c == '_').then(|| target.to_string())
+    })
+}
+
+/// Compiles out the `extern "C"` blocks declaring `wrapped_*` functions when the host stubs
+/// define them instead.
+fn gate_wrapper_declarations(bindings: &str) -> String {
+    let mut code = String::new();
+    let mut rest = bindings;
+    while let Some(start) = rest.find("extern \"C\"") {
+        let block_start = start + "extern \"C\"".len();
+        if !rest[block_start..].trim_start().starts_with('{') {
+            code += &rest[..block_start];
+            rest = &rest[block_start..];
+            continue;
+        }
+        let end = block_start + extern_block_end(&rest[block_start..]);
+        code += &rest[..start];
+        if rest[start..end].contains("pub fn wrapped_") {
+            code += &format!("#[cfg(not({}))]\n", HOST_STUBS_CFG);
+        }
+        code += &rest[start..end];
+        rest = &rest[end..];
+    }
+    code += rest;
+    code
+}
+
+/// The length of `block` up to and including the `}` closing the `{` it starts with, skipping
+/// the braces of string literals such as doc comments.
+fn extern_block_end(block: &str) -> usize {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in block.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return index + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    block.len()
+}
+
+/// A `wrapped_*` declaration of the bindings, with its tokens separated by single spaces as
+/// bindgen prints them, e.g. `wrapped_gpio_put (gpio : uint , value : bool)`.
+struct WrapperDeclaration {
+    signature: String,
+    /// Index of the `)` closing the parameter list.
+    parameters_end: usize,
+}
+
+impl WrapperDeclaration {
+    fn name(&self) -> &str {
+        self.signature[..self.signature.find('(').unwrap()].trim_end()
+    }
+
+    /// The C symbol the declaration links to.
+    fn symbol(&self, prefix: &str) -> String {
+        format!("{}{}", prefix, &self.name()[RUST_WRAPPER_PREFIX.len()..])
+    }
+
+    /// Names and types of the parameters.
+    fn parameters(&self) -> Vec<(&str, &str)> {
+        let list = &self.signature[self.signature.find('(').unwrap() + 1..self.parameters_end];
+        let mut parameters = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        let mut previous = ' ';
+        for (index, c) in list.char_indices() {
+            match c {
+                '(' | '<' | '[' => depth += 1,
+                // The `>` of `->` in function pointer types closes nothing.
+                '>' if previous == '-' => {}
+                ')' | '>' | ']' => depth -= 1,
+                ',' if depth == 0 => {
+                    parameters.extend(split_parameter(&list[start..index]));
+                    start = index + 1;
+                }
+                _ => {}
+            }
+            previous = c;
+        }
+        parameters.extend(split_parameter(&list[start..]));
+        parameters
+    }
+
+    fn return_type(&self) -> Option<&str> {
+        self.signature[self.parameters_end + 1..].trim().strip_prefix("->").map(str::trim)
+    }
+}
+
+fn split_parameter(parameter: &str) -> Option<(&str, &str)> {
+    let (name, parameter_type) = parameter.split_once(':')?;
+    Some((name.trim(), parameter_type.trim()))
+}
+
+fn wrapper_declarations(bindings: &str) -> Vec<WrapperDeclaration> {
+    bindings.match_indices("pub fn wrapped_")
+        .map(|(start, _)| {
+            let declaration = &bindings[start..];
+            let declaration = declaration[..declaration.find(';').expect("unterminated declaration")]
+                .split_whitespace().collect::<Vec<_>>().join(" ");
+            let signature = declaration.trim_start_matches("pub fn ").to_string();
+            let mut depth = 0;
+            let parameters_end = signature.char_indices()
+                .find(|&(_, c)| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    c == ')' && depth == 0
+                })
+                .map(|(index, _)| index)
+                .unwrap();
+            WrapperDeclaration { signature, parameters_end }
+        })
+        .collect()
+}
+
+/// Rust types of scalars that every bit pattern is a valid value of, or `bool`, which the C
+/// side only ever returns as 0 or 1.
+const SCALAR_TYPES: [&str; 23] = [
+    "bool", "u8", "u16", "u32", "u64", "usize", "i8", "i16", "i32", "i64", "isize", "cty::c_char", "cty::c_schar",
+    "cty::c_uchar", "cty::c_short", "cty::c_ushort", "cty::c_int", "cty::c_uint", "cty::c_long", "cty::c_ulong",
+    "cty::c_longlong", "cty::c_ulonglong", "cty::c_void",
+];
+
+/// Whether `rust_type` is one of [`SCALAR_TYPES`], directly or through the type aliases of
+/// `bindings` (`uint`, `uint32_t`, constified enums). `c_void` only counts as a return type,
+/// where it stands for `void` as `()` does.
+fn is_scalar_type(bindings: &str, rust_type: &str) -> bool {
+    let mut rust_type = rust_type.trim_start_matches(":: ").replace(" :: ", "::").replace(":: ", "::");
+    // Aliases can only chain so far before reaching a builtin type.
+    for _ in 0..8 {
+        if rust_type != "cty::c_void" && SCALAR_TYPES.contains(&rust_type.as_str()) {
+            return true;
+        }
+        let alias = format!("pub type {} = ", rust_type);
+        let target = match bindings.find(&alias) {
+            Some(start) => &bindings[start + alias.len()..],
+            None => return false,
+        };
+        let target = target[..target.find(';').unwrap_or(target.len())].split_whitespace().collect::<Vec<_>>().join(" ");
+        rust_type = target.trim_start_matches(":: ").trim_start_matches("::").replace(" :: ", "::").replace(":: ", "::");
+    }
+    false
+}
+
+/// The bindings declared in `pub mod <module_name>` for `PICO_SDK_RS_MODULE_NAME`, and
+/// re-exported at the crate root so that `raspi_pico_sdk` finds them where it expects them.
+fn wrap_in_module(code: &str, module_name: &str) -> String {
+    format!("pub mod {0} {{\n{1}\n}}\npub use {0}::*;\n", module_name, code)
+}
+
+/// Safe functions for the wrappers whose parameters and return value are all scalars, named
+/// without the `wrapped_` prefix, e.g. `pub fn gpio_put(gpio: uint, value: bool)`. Functions
+/// with pointers, structs or callbacks stay unsafe only. `module_name` is the module of
+/// `PICO_SDK_RS_MODULE_NAME` the wrappers are declared in.
+fn create_safe_wrappers(bindings: &str, module_name: Option<&str>) -> String {
+    let mut code = format!(
+        "#[allow(unused_imports)]\nuse super::{}*;\n",
+        module_name.map(|name| format!("{}::", name)).unwrap_or_default()
+    );
+    let mut seen = HashSet::new();
+    for declaration in wrapper_declarations(bindings) {
+        let name = declaration.name().to_string();
+        let parameters = declaration.parameters();
+        let scalar = parameters.iter().all(|(_, parameter_type)| is_scalar_type(bindings, parameter_type))
+            && declaration.return_type().is_none_or(|return_type| return_type == "!" || is_scalar_type(bindings, return_type));
+        // With `PICO_SDK_RS_BOARDS`, every board declares the wrapper again.
+        if !scalar || !seen.insert(name.clone()) {
+            continue;
+        }
+        let safe_name = &name[RUST_WRAPPER_PREFIX.len()..];
+        let safe_name = if RUST_KEYWORDS.contains(&safe_name) { format!("r#{}", safe_name) } else { safe_name.to_string() };
+        code += &format!(
+            "\n/// Calls [`{0}`].\n#[inline(always)]\npub fn {1}({2}){3} {{\n    unsafe {{ {0}({4}) }}\n}}\n",
+            name,
+            safe_name,
+            parameters.iter().map(|(name, parameter_type)| format!("{}: {}", name, parameter_type)).collect::<Vec<_>>().join(", "),
+            declaration.return_type().map(|return_type| format!(" -> {}", return_type)).unwrap_or_default(),
+            parameters.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+        );
+    }
+    code
+}
+
+/// With `PICO_SDK_RS_METADATA`, the Rust declarations of the wrappers are also written as data
+/// to `metadata.rs`, for the build scripts of crates generating code from them. The file is
+/// self-contained Rust, so it can be `include!`d as well as parsed.
+const METADATA_PROLOGUE: &str = "\
+/// A wrapped SDK function of `raspi_pico_sdk_sys`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrappedFunction {
+    /// Name of the Rust declaration, e.g. `wrapped_gpio_put`.
+    pub name: &'static str,
+    /// C symbol the declaration links to.
+    pub symbol: &'static str,
+    /// Names and Rust types of the parameters, as in the declaration.
+    pub parameters: &'static [(&'static str, &'static str)],
+    /// Rust return type, `None` for `void` functions.
+    pub return_type: Option<&'static str>,
+}
+
+/// Every wrapper declared in the bindings, in declaration order.
+pub const WRAPPED_FUNCTIONS: &[WrappedFunction] = &[
+";
+
+fn create_metadata(bindings: &str, prefix: &str) -> String {
+    let mut code = String::from(METADATA_PROLOGUE);
+    for declaration in wrapper_declarations(bindings) {
+        let parameters = declaration.parameters()
+            .iter()
+            .map(|(name, parameter_type)| format!("({:?}, {:?})", name, parameter_type))
+            .collect::<Vec<_>>();
+        code += &format!(
+            "    WrappedFunction {{ name: {:?}, symbol: {:?}, parameters: &[{}], return_type: {:?} }},\n",
+            declaration.name(),
+            declaration.symbol(prefix),
+            parameters.join(", "),
+            declaration.return_type()
+        );
+    }
+    code += "];\n";
+    code
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Reads a build option, falling back to the value the selected `PICO_SDK_RS_PROFILE` gives it.
+fn get_env(name: &str) -> Option<String> {
+    println!("cargo:rerun-if-env-changed={}", name);
+    std::env::var(name).ok().or_else(|| profile_option(name))
+}
+
+/// Option presets selected with `PICO_SDK_RS_PROFILE`. Options set in the environment take
+/// precedence over them.
+const PROFILES: &[(&str, &[(&str, &str)])] = &[
+    ("usb-debug", &[
+        ("PICO_SDK_RS_CMAKE_BUILD_TYPE", "Debug"),
+        ("PICO_SDK_RS_STDIO_USB", "1"),
+        ("PICO_SDK_RS_STDIO_UART", "0"),
+    ]),
+    ("uart-release", &[
+        ("PICO_SDK_RS_CMAKE_BUILD_TYPE", "Release"),
+        ("PICO_SDK_RS_LTO", "1"),
+        ("PICO_SDK_RS_STDIO_USB", "0"),
+        ("PICO_SDK_RS_STDIO_UART", "1"),
+    ]),
+    ("ram-test", &[
+        ("PICO_SDK_RS_CMAKE_BUILD_TYPE", "Debug"),
+        ("PICO_SDK_RS_BINARY_TYPE", "no_flash"),
+    ]),
+];
+
+/// Fails on a `PICO_SDK_RS_PROFILE` that names none of [`PROFILES`], which [`profile_option`]
+/// would otherwise ignore.
+fn check_profile() -> BuildResult<()> {
+    match std::env::var("PICO_SDK_RS_PROFILE") {
+        Ok(profile) if !PROFILES.iter().any(|(known, _)| *known == profile) => Err(format!(
+            "unknown PICO_SDK_RS_PROFILE `{}`; known profiles are {:?}",
+            profile,
+            PROFILES.iter().map(|(known, _)| *known).collect::<Vec<_>>()
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn profile_option(name: &str) -> Option<String> {
+    let profile = std::env::var("PICO_SDK_RS_PROFILE").ok()?;
+    let (_, options) = PROFILES.iter().find(|(known, _)| *known == profile)?;
+    options.iter()
+        .find(|(option, _)| *option == name)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Points both the `clang` crate and bindgen at the libclang named by `PICO_SDK_RS_LIBCLANG`.
+/// Both load libclang at runtime through `LIBCLANG_PATH`, which takes a directory as well as
+/// the path of the library itself, so this has to run before the first parse.
+fn pin_libclang() -> BuildResult<()> {
+    if let Some(libclang) = get_env("PICO_SDK_RS_LIBCLANG") {
+        if !Path::new(&libclang).exists() {
+            return Err(format!("PICO_SDK_RS_LIBCLANG points to {}, which does not exist", libclang));
+        }
+        std::env::set_var("LIBCLANG_PATH", &libclang);
+    }
+    Ok(())
+}
+
+fn get_env_flag(name: &str) -> bool {
+    get_env(name)
+        .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "on" | "yes"))
+        .unwrap_or(false)
+}
+
+fn emit_link_directives(out_dir: &Path) {
+    for path in split_env_paths("PICO_SDK_RS_LINK_SEARCH") {
+        println!("cargo:rustc-link-search={}", path);
+    }
+    for library in split_env_list("PICO_SDK_RS_LINK_LIBS") {
+        println!("cargo:rustc-link-lib={}", library);
+    }
+    // Scoped to binaries so that tests and examples, which may well be built for the host, keep
+    // the default link. Cargo applies these to this package only; dependents get them through
+    // the `link_args` metadata (`DEP_PICO_SDK_LINK_ARGS`) for their own build scripts.
+    let link_args = firmware_link_args(out_dir);
+    for arg in &link_args {
+        println!("cargo:rustc-link-arg-bins={}", arg);
+    }
+    if !link_args.is_empty() {
+        println!("cargo:link_args={}", link_args.join(" "));
+    }
+}
+
+/// Linker flags the firmware has to be linked with for the selected options.
+fn firmware_link_args(out_dir: &Path) -> Vec<String> {
+    let mut link_args = Vec::new();
+    if get_env_flag("PICO_SDK_RS_LTO") {
+        link_args.push(LTO_FLAG.to_string());
+    }
+    // The heap of the `alloc` feature calls `pico_malloc`'s `__wrap_*` functions, which only
+    // reach newlib's allocator as `__real_*` in a firmware linked with these.
+    if std::env::var_os("CARGO_FEATURE_ALLOC").is_some() {
+        link_args.extend(MALLOC_WRAP_FLAGS.iter().map(|flag| flag.to_string()));
+    }
+    // Nothing refers to boot stage 2, which the linker script only places by its section, so
+    // its archive is linked whole.
+    if get_env("PICO_SDK_RS_BOOT_STAGE2").is_some() {
+        link_args.push("-Wl,--whole-archive".to_string());
+        link_args.push(sdk_build_dir(out_dir).join(BOOT_STAGE2_ARCHIVE).display().to_string());
+        link_args.push("-Wl,--no-whole-archive".to_string());
+    }
+    link_args
+}
+
+/// The boot stage 2 selected with `PICO_SDK_RS_BOOT_STAGE2`, which `cmake_pico` archives on its
+/// own in the SDK build directory.
+const BOOT_STAGE2_ARCHIVE: &str = "libpico_boot_stage2.a";
+
+/// The build directory of the SDK the firmware is linked against, that of the active board
+/// with `PICO_SDK_RS_BOARDS`.
+fn sdk_build_dir(out_dir: &Path) -> PathBuf {
+    let boards = split_env_list("PICO_SDK_RS_BOARDS");
+    if boards.is_empty() {
+        out_dir.join("build")
+    } else {
+        out_dir.join("boards").join(active_board(&boards)).join("build")
+    }
+}
+
+const MALLOC_WRAP_FLAGS: [&str; 4] = ["-Wl,--wrap=malloc", "-Wl,--wrap=calloc", "-Wl,--wrap=realloc", "-Wl,--wrap=free"];
+
+/// Passed to both the SDK compile and the final link so the wrapper object takes part in LTO.
+const LTO_FLAG: &str = "-flto";
+
+/// LTO across the Rust/C boundary only works when the C side is compiled by a clang built on the
+/// same LLVM major version as rustc. With GCC the SDK and the wrappers are still optimized
+/// together, but the Rust code is not.
+fn check_lto_toolchain(out_dir: &Path) -> BuildResult<()> {
+    let cache_path = out_dir.join("build/CMakeCache.txt");
+    let cache = fs::read_to_string(&cache_path)
+        .map_err(|e| format!("PICO_SDK_RS_LTO: failed to read {} to find the C compiler: {}", cache_path.display(), e))?;
+    let compiler = cache.lines()
+        .find_map(|line| line.strip_prefix("CMAKE_C_COMPILER:"))
+        .and_then(|entry| entry.split_once('=').map(|(_, value)| value))
+        .map(str::trim)
+        .ok_or_else(|| format!("PICO_SDK_RS_LTO: CMAKE_C_COMPILER is missing from {}", cache_path.display()))?
+        .to_string();
+    let compiler_version = command_output(&compiler, "--version");
+    let clang_major = compiler_version.as_deref()
+        .and_then(|output| output.split("clang version ").nth(1))
+        .and_then(|version| version.split('.').next())
+        .map(str::to_string);
+    let clang_major = match clang_major {
+        Some(major) => major,
+        None => {
+            println!("cargo:warning=PICO_SDK_RS_LTO: {} is not clang, so LTO covers the SDK and the wrappers but not the Rust code", compiler);
+            return Ok(());
+        }
+    };
+
+    let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+    let rustc_llvm_major = command_output(&rustc, "-vV").as_deref()
+        .and_then(|output| output.lines().find_map(|line| line.strip_prefix("LLVM version: ")))
+        .and_then(|version| version.split('.').next())
+        .map(str::to_string);
+    match rustc_llvm_major {
+        Some(rustc_major) if rustc_major == clang_major => {}
+        Some(rustc_major) => return Err(format!(
+            "PICO_SDK_RS_LTO: {} uses LLVM {}, but rustc uses LLVM {}; cross-language LTO needs matching LLVM versions",
+            compiler, clang_major, rustc_major
+        )),
+        None => println!("cargo:warning=PICO_SDK_RS_LTO: could not detect the LLVM version of rustc"),
+    }
+    Ok(())
+}
+
+fn command_output(program: &str, argument: &str) -> Option<String> {
+    std::process::Command::new(program)
+        .arg(argument)
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn split_env_list(name: &str) -> Vec<String> {
+    get_env(name).unwrap_or_default()
+        .split(':')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Like [`split_env_list`], but for lists of paths, which are separated by the platform's
+/// separator as in `PATH` (`;` on Windows, where `:` is part of `C:\...`).
+fn split_env_paths(name: &str) -> Vec<String> {
+    std::env::split_paths(&get_env(name).unwrap_or_default())
+        .map(|path| path.display().to_string().trim().to_string())
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+fn assert_sdk_version(requirement: &str, include_directories: &[String]) -> BuildResult<()> {
+    let version = get_sdk_version(include_directories)
+        .ok_or("PICO_SDK_RS_ASSERT_SDK_VERSION is set, but PICO_SDK_VERSION_STRING could not be detected")?;
+    match version_satisfies(&version, requirement) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(format!(
+            "pico-sdk {} does not satisfy PICO_SDK_RS_ASSERT_SDK_VERSION ({})",
+            version, requirement
+        )),
+        Err(e) => Err(format!("invalid PICO_SDK_RS_ASSERT_SDK_VERSION ({}): {}", requirement, e)),
+    }
+}
+
+fn get_sdk_version(include_directories: &[String]) -> Option<String> {
+    include_directories.iter()
+        .filter_map(|dir| fs::read_to_string(Path::new(dir).join("pico/version.h")).ok())
+        .find_map(|header| {
+            header.lines().find_map(|line| {
+                let mut tokens = line.split_whitespace();
+                match (tokens.next(), tokens.next(), tokens.next()) {
+                    (Some("#define"), Some("PICO_SDK_VERSION_STRING"), Some(version)) => {
+                        Some(version.trim_matches('"').to_string())
+                    }
+                    _ => None,
+                }
+            })
+        })
+}
+
+fn get_board(definitions: &[String]) -> Option<String> {
+    definitions.iter()
+        .find_map(|definition| definition.strip_prefix("PICO_BOARD="))
+        .map(|board| board.trim_matches('"').to_string())
+}
+
+/// The chip the SDK was configured for, without the core variant of the RP2350 platforms
+/// (`rp2350-arm-s`, `rp2350-riscv`). The `PICO_RP2040` and `PICO_RP2350` definitions of the SDK
+/// tell however the platform was chosen; before 2.0 the SDK only knew the RP2040.
+fn get_chip(definitions: &[String], target_triple: &str) -> String {
+    for chip in ["rp2040", "rp2350"] {
+        if definitions.iter().any(|definition| *definition == format!("PICO_{}=1", chip.to_uppercase())) {
+            return chip.to_string();
+        }
+    }
+    let platform = std::env::var("PICO_PLATFORM").ok()
+        .or_else(|| pico_platform(target_triple).map(|(platform, _)| platform.to_string()))
+        .unwrap_or("rp2040".to_string());
+    platform.split('-').next().unwrap().to_string()
+}
+
+fn parse_version(version: &str) -> Result<[u64; 3], String> {
+    parse_partial_version(version).map(|(parsed, _)| parsed)
+}
+
+/// Parses a version that may leave out its minor and patch components, which are read as 0, and
+/// also returns how many components were written.
+fn parse_partial_version(version: &str) -> Result<([u64; 3], usize), String> {
+    let version = version.trim();
+    let version = version.split(['-', '+']).next().unwrap_or(version);
+    let mut parsed = [0; 3];
+    let mut components = 0;
+    for (i, part) in version.split('.').enumerate() {
+        if i >= parsed.len() {
+            return Err(format!("too many components in version `{}`", version));
+        }
+        parsed[i] = part.parse()
+            .map_err(|_| format!("`{}` is not a valid version", version))?;
+        components = i + 1;
+    }
+    Ok((parsed, components))
+}
+
+/// The smallest version above `version` in which the component at `index` changed.
+fn next_version(version: [u64; 3], index: usize) -> [u64; 3] {
+    let mut next = [0; 3];
+    next[..index].copy_from_slice(&version[..index]);
+    next[index] = version[index] + 1;
+    next
+}
+
+fn version_satisfies(version: &str, requirement: &str) -> Result<bool, String> {
+    let version = parse_version(version)?;
+    for comparator in requirement.split(',') {
+        let comparator = comparator.trim();
+        let (operator, expected) = ["==", ">=", "<=", "=", ">", "<", "^", "~"].iter()
+            .find(|operator| comparator.starts_with(**operator))
+            .map(|operator| (*operator, &comparator[operator.len()..]))
+            .unwrap_or(("^", comparator));
+        let (expected, components) = parse_partial_version(expected)?;
+        // As in Cargo, `^` allows the changes that keep the leftmost non-zero component, `~` those
+        // that keep the minor version (or the major one if that is all that is given), and `=`
+        // any value of the components that were left out.
+        let upper_bound = |index: usize| version >= expected && version < next_version(expected, index);
+        let satisfied = match operator {
+            ">=" => version >= expected,
+            "<=" => version <= expected,
+            ">" => version > expected,
+            "<" => version < expected,
+            "^" => upper_bound(expected[..components].iter().position(|&c| c != 0).unwrap_or(components - 1)),
+            "~" => upper_bound(components.min(2) - 1),
+            _ => upper_bound(components - 1),
+        };
+        if !satisfied {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn get_c_binding_alternatives() -> Vec<String> {
+    split_env_paths("PICO_SDK_RS_C_BINDING_ALTERNATIVES")
+}
+
+/// Values of `pico_set_binary_type`.
+const BINARY_TYPES: [&str; 4] = ["default", "no_flash", "copy_to_ram", "blocked_ram"];
+
+/// Values of `pico_set_float_implementation` that `pico_set_double_implementation` accepts too.
+const FLOAT_IMPLEMENTATIONS: [&str; 3] = ["pico", "compiler", "none"];
+
+/// Suffixes of the SDK's `pico_cyw43_arch_<variant>` libraries that work without FreeRTOS.
+const WIRELESS_ARCHITECTURES: [&str; 5] = ["none", "poll", "threadsafe_background", "lwip_poll", "lwip_threadsafe_background"];
+
+/// Parses a size in bytes of the environment variable `name`, decimal or `0x`-prefixed
+/// hexadecimal. The SDK keeps the stack 8-byte aligned, so sizes have to be a multiple of 8.
+fn parse_memory_size(name: &str, size: &str) -> BuildResult<u32> {
+    let parsed = match size.strip_prefix("0x").or_else(|| size.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => size.parse::<u32>(),
+    };
+    match parsed {
+        Ok(parsed) if parsed % 8 == 0 => Ok(parsed),
+        _ => Err(format!("{} must be a size in bytes and a multiple of 8, not `{}`", name, size)),
+    }
+}
+
+/// Fails unless the value of the option `name` is one of `values`.
+fn check_one_of(name: &str, value: &str, values: &[&str]) -> BuildResult<()> {
+    if values.contains(&value) {
+        Ok(())
+    } else {
+        Err(format!("{} must be one of {:?}, not `{}`", name, values, value))
+    }
+}
+
+fn get_compile_options(target_triple: &str, out_dir: &Path, entry_path: &PathBuf, board: Option<&str>) -> BuildResult<CompileOptions> {
+    check_cmake()?;
+    let mut pico = cmake::Config::new("cmake_pico");
+    pico.define("ENTRY_POINT", entry_path)
+        .out_dir(out_dir);
+    // The SDK reads `PICO_PLATFORM` from the environment itself, which takes precedence over the
+    // platform derived from the target.
+    println!("cargo:rerun-if-env-changed=PICO_PLATFORM");
+    if let (Err(_), Some((platform, _))) = (std::env::var("PICO_PLATFORM"), pico_platform(target_triple)) {
+        pico.define("PICO_PLATFORM", platform);
+    }
+    if let Some(board) = board {
+        pico.define("PICO_BOARD", board);
+    }
+    if let Some(build_type) = get_env("PICO_SDK_RS_CMAKE_BUILD_TYPE") {
+        pico.profile(&build_type);
+    }
+    if let Some(binary_type) = get_env("PICO_SDK_RS_BINARY_TYPE") {
+        check_one_of("PICO_SDK_RS_BINARY_TYPE", &binary_type, &BINARY_TYPES)?;
+        pico.define("PICO_DEFAULT_BINARY_TYPE", binary_type);
+    }
+    if let Some(boot_stage2) = get_env("PICO_SDK_RS_BOOT_STAGE2") {
+        if !boot_stage2.starts_with("boot2_") || !is_identifier(&boot_stage2) {
+            return Err(format!("PICO_SDK_RS_BOOT_STAGE2 must name an SDK boot stage 2 such as `boot2_w25q080`, not `{}`", boot_stage2));
+        }
+        pico.define("PICO_DEFAULT_BOOT_STAGE2", boot_stage2);
+    }
+    if let Some(float_impl) = get_env("PICO_SDK_RS_FLOAT_IMPL") {
+        check_one_of("PICO_SDK_RS_FLOAT_IMPL", &float_impl, &FLOAT_IMPLEMENTATIONS)?;
+        pico.define("FLOAT_IMPL", float_impl);
+    }
+    // The SDK's crt0 reserves the stack and heap sections with these sizes; the linker script
+    // places them at the end of RAM.
+    for (name, define) in [("PICO_SDK_RS_STACK_SIZE", "PICO_STACK_SIZE"), ("PICO_SDK_RS_HEAP_SIZE", "PICO_HEAP_SIZE")] {
+        if let Some(size) = get_env(name) {
+            pico.define(define, parse_memory_size(name, &size)?.to_string());
+        }
+    }
+    // `pico_malloc` only takes its mutex when multicore support is linked; the `alloc` feature
+    // allocates from Rust on either core regardless.
+    if std::env::var_os("CARGO_FEATURE_ALLOC").is_some() {
+        pico.define("USE_MALLOC_MUTEX", "1");
+    }
+    // Left to the SDK's defaults (UART on, USB off) unless set.
+    for (name, define) in [("PICO_SDK_RS_STDIO_USB", "STDIO_USB"), ("PICO_SDK_RS_STDIO_UART", "STDIO_UART")] {
+        if get_env(name).is_some() {
+            pico.define(define, if get_env_flag(name) { "1" } else { "0" });
+        }
+    }
+    if get_env_flag("PICO_SDK_RS_ASYNC_CONTEXT") {
+        pico.define("ASYNC_CONTEXT", "1");
+    }
+    if get_env_flag("PICO_SDK_RS_WIRELESS") {
+        let architecture = get_env("PICO_SDK_RS_WIRELESS_ARCH").unwrap_or("lwip_threadsafe_background".to_string());
+        check_one_of("PICO_SDK_RS_WIRELESS_ARCH", &architecture, &WIRELESS_ARCHITECTURES)?;
+        pico.define("WIRELESS_ARCH", architecture);
+    }
+    let link_libraries = split_env_list("PICO_SDK_RS_LINK_LIBRARIES");
+    if !link_libraries.is_empty() {
+        pico.define("LINK_LIBRARIES", link_libraries.join(";"));
+    }
+    if let Some(extras_path) = get_env("PICO_SDK_RS_PICO_EXTRAS_PATH") {
+        let extras_path = fs::canonicalize(&extras_path)
+            .map_err(|e| format!("failed to find PICO_SDK_RS_PICO_EXTRAS_PATH {}: {}", extras_path, e))?;
+        pico.define("PICO_EXTRAS_PATH", extras_path);
+    }
+    let extra_sources = split_env_paths("PICO_SDK_RS_EXTRA_SOURCES")
+        .iter()
+        .map(|source| {
+            println!("cargo:rerun-if-changed={}", source);
+            fs::canonicalize(source)
+                .map(|source| source.display().to_string())
+                .map_err(|e| format!("failed to find PICO_SDK_RS_EXTRA_SOURCES entry {}: {}", source, e))
+        })
+        .collect::<BuildResult<Vec<_>>>()?;
+    if !extra_sources.is_empty() {
+        pico.define("EXTRA_SOURCES", extra_sources.join(";"));
+    }
+    if get_env_flag("PICO_SDK_RS_LTO") {
+        pico.define("CMAKE_INTERPROCEDURAL_OPTIMIZATION", "ON")
+            .cflag(LTO_FLAG);
+    }
+    // Configuring is enough for the include paths and definitions the bindings are generated
+    // from, so the no-op target only configures, and `cmake_depend` extracts them while the SDK
+    // and the wrappers compile. It reads a copy of the `DependInfo.cmake` written by the
+    // configure, as the compile configures again and may rewrite the file meanwhile.
+    pico.build_target("configure_only");
+    pico.build();
+    if let Ok(missing_libraries) = fs::read_to_string(out_dir.join("build/missing_libraries")) {
+        for library in missing_libraries.lines().filter(|library| !library.is_empty()) {
+            println!("cargo:warning=`{}` is not a library of the SDK configured for this board, so it is neither linked nor wrapped", library);
+        }
+    }
+    let depend_dir = out_dir.join("test");
+    fs::create_dir_all(&depend_dir).expect("failed create_dir_all");
+    let depend_info = depend_dir.join("DependInfo.cmake");
+    let written_depend_info = out_dir.join("build/CMakeFiles/pico.dir/DependInfo.cmake");
+    fs::copy(&written_depend_info, &depend_info).map_err(|e| format!(
+        "failed to read {} ({}); configuring the SDK most likely failed, see the cmake output above",
+        written_depend_info.display(),
+        e
+    ))?;
+    let extract_compile_options = || {
+        cmake::Config::new("cmake_depend")
+            .out_dir(&depend_dir)
+            .target(guess_host_triple::guess_host_triple().unwrap_or("x86_64-unknown-linux-gnu"))
+            .define("DEPENDINFO_PATH", &depend_info)
+            .define("INCLUDE_PATH_FILE", out_dir.join("include_path"))
+            .define("DEFINITIONS_FILE", out_dir.join("definitions"))
+            .build_target("write")
+            .build();
+    };
+    if get_env_flag("PICO_SDK_RS_CHECK_ONLY") {
+        extract_compile_options();
+    } else {
+        pico.no_build_target(true);
+        std::thread::scope(|scope| {
+            let sdk_build = scope.spawn(|| pico.build());
+            extract_compile_options();
+            sdk_build.join()
+        })
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+    }
+
+    let include_directories = read_cmake_output(&out_dir.join("include_path"))?
+        .lines()
+        .filter(|path| !path.trim().is_empty())
+        .map(|path| {
+            out_dir.join("build").join(path.trim())
+                .display().to_string()
+        })
+        // The SDK lists many directories more than once; only the first occurrence matters for
+        // the search order.
+        .fold(Vec::new(), |mut include_directories, dir| {
+            if !include_directories.contains(&dir) {
+                include_directories.push(dir);
+            }
+            include_directories
+        });
+    let definitions = read_cmake_output(&out_dir.join("definitions"))?
+        .lines().filter(|definition| !definition.is_empty()).map(str::to_string).collect::<Vec<_>>();
+    Ok((include_directories, definitions))
+}
+
+/// The cmake crate runs the program named by `CMAKE`, and only reports a missing one with a
+/// panic in the middle of the configure.
+fn check_cmake() -> BuildResult<()> {
+    println!("cargo:rerun-if-env-changed=CMAKE");
+    let cmake = std::env::var("CMAKE").unwrap_or("cmake".to_string());
+    match Command::new(&cmake).arg("--version").stdout(Stdio::null()).status() {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(format!(
+            "`{}` was not found; install CMake 3.12 or newer (`cmake` on Debian/Ubuntu and Homebrew) or point `CMAKE` at it",
+            cmake
+        )),
+        Err(e) => Err(format!("failed to run `{}`: {}", cmake, e)),
+    }
+}
+
+/// Reads a file the `cmake_depend` step writes from the SDK build's dependency information.
+fn read_cmake_output(path: &Path) -> BuildResult<String> {
+    fs::read_to_string(path).map_err(|e| format!(
+        "failed to read {} ({}); cmake_depend did not write it, so configuring the SDK most likely failed, see the cmake output above",
+        path.display(),
+        e
+    ))
+}
+
+/// Moves directories starting with one of `priority` to the front, in the order of `priority`,
+/// so that clang finds a header there before any same-named header elsewhere. The remaining
+/// directories keep their order from cmake.
+fn prioritize_include_directories(mut include_directories: Vec<String>, priority: &[String]) -> Vec<String> {
+    include_directories.sort_by_key(|dir| {
+        priority.iter()
+            .position(|prefix| Path::new(dir).starts_with(prefix))
+            .unwrap_or(priority.len())
+    });
+    include_directories
+}
+
+/// With `PICO_SDK_RS_INLINE_WRAPPERS`, the wrappers are defined in this header, which C code can
+/// include (after the SDK headers) to call the wrapped functions without the extra call. There
+/// they are `static inline` copies. The generated section declares every wrapper `extern inline`
+/// and defines [`INLINE_WRAPPER_MACRO`] as plain `inline` before including it, which makes the
+/// definitions in `entry.c` external C99 inline ones: they are the `wrapped_*` symbols bindgen
+/// declares and Rust links against. Only the copies elsewhere are `static`, because a function
+/// with internal linkage cannot be the symbol, and an inline definition with external linkage
+/// outside `entry.c` may not call the `static inline` SDK functions most wrappers call.
+const INLINE_HEADER_NAME: &str = "pico_sdk_rs_wrappers.h";
+const INLINE_WRAPPER_MACRO: &str = "PICO_SDK_RS_WRAPPER_INLINE";
+const INLINE_HEADER_PROLOGUE: &str = concat!(
+    "/// Generated Code\n\n#ifndef PICO_SDK_RS_WRAPPERS_H\n#define PICO_SDK_RS_WRAPPERS_H\n\n#include <stdarg.h>\n\n",
+    "#ifndef PICO_SDK_RS_WRAPPER_INLINE\n#define PICO_SDK_RS_WRAPPER_INLINE static inline\n#endif\n\n",
+);
+const INLINE_HEADER_EPILOGUE: &str = "\n#endif\n";
+const INLINE_WRAPPER_QUALIFIERS: &str = "PICO_SDK_RS_WRAPPER_INLINE __attribute__((always_inline)) ";
+const INLINE_INSTANTIATION_QUALIFIERS: &str = "extern inline ";
+
+/// The `va_list` taking counterpart of the variadic function `name`, e.g. `vprintf` for
+/// `printf`.
+fn find_va_list_variant(declared_functions: &[String], name: &str) -> Option<String> {
+    let candidates = [format!("v{}", name), format!("{}_v", name)];
+    candidates.iter().find(|candidate| declared_functions.contains(candidate)).cloned()
+}
+
+/// The documentation of `entity` in its header as `///` lines, which clang attaches to the
+/// wrapper below them and bindgen turns into doc attributes. The comment markers and the `*`
+/// starting each line of a `/** ... */` block are removed. Empty without a comment.
+fn create_doc_comment(entity: &Entity) -> String {
+    let comment = match entity.get_comment() {
+        Some(comment) => comment,
+        None => return String::new(),
+    };
+    let lines = comment.lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = ["/**", "/*!", "///", "//!", "*/"].iter()
+                .fold(line, |line, marker| line.strip_prefix(marker).unwrap_or(line));
+            let line = line.strip_suffix("*/").unwrap_or(line);
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        })
+        .collect::<Vec<_>>();
+    let start = lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len());
+    let end = lines.iter().rposition(|line| !line.is_empty()).map_or(start, |end| end + 1);
+    lines[start..end].iter()
+        .map(|line| if line.is_empty() { "///\n".to_string() } else { format!("/// {}\n", line) })
+        .collect()
+}
+
+/// The wrapper of `entity`. A variadic function is wrapped with a trailing `va_list` parameter
+/// forwarded to `va_list_variant` instead, as C cannot forward `...` itself.
+fn create_wrapper_function(entity: &Entity, qualifiers: &str, va_list_variant: Option<&str>, prefix: &str) -> String {
+    let (formal_arguments, actual_arguments) = wrapper_arguments(entity, va_list_variant);
+    let result_type = entity.get_result_type().unwrap();
+    let function_name = entity.get_name().unwrap();
+    format!(
+        "{}{} {{ {}{}({}); }}\n",
+        qualifiers,
+        declare(&result_type, &format!("{}{}({})", prefix, function_name, formal_arguments)),
+        if result_type.get_display_name() == "void" { "" } else { "return " },
+        va_list_variant.unwrap_or(&function_name),
+        actual_arguments
+    )
+}
+
+/// The prototype of the wrapper [`create_wrapper_function`] defines.
+fn create_wrapper_declaration(entity: &Entity, qualifiers: &str, va_list_variant: Option<&str>, prefix: &str) -> String {
+    let (formal_arguments, _) = wrapper_arguments(entity, va_list_variant);
+    let declarator = format!("{}{}({})", prefix, entity.get_name().unwrap(), formal_arguments);
+    format!("{}{};\n", qualifiers, declare(&entity.get_result_type().unwrap(), &declarator))
+}
+
+/// The parameters of the wrapper of `entity` and the arguments it passes them on as.
+fn wrapper_arguments(entity: &Entity, va_list_variant: Option<&str>) -> (String, String) {
+    entity.get_children()
+        .into_iter()
+        .filter(|entity| entity.get_kind() == EntityKind::ParmDecl)
+        .enumerate()
+        .map(|(index, entity)| {
+            // Prototypes may leave parameters unnamed, `int foo(int, const char *)`.
+            let param_name = entity.get_name().unwrap_or(format!("arg{}", index));
+            (declare(&entity.get_type().unwrap(), &param_name), param_name)
+        })
+        .chain(va_list_variant.map(|_| ("va_list args".to_string(), "args".to_string())))
+        .fold((String::new(), String::new()), |(mut formal, mut actual), (f, a)| {
+            if !formal.is_empty() {
+                formal.push_str(", ");
+                actual.push_str(", ");
+            }
+            formal.push_str(&f);
+            actual.push_str(&a);
+            (formal, actual)
+        })
+}
+
+/// Declares `declarator` (a parameter name, or a function with its parameters) with type
+/// `declared_type`. Usually that is the type followed by the declarator, but the type names
+/// clang prints for function pointers and arrays, `void (*)(uint)` and `int [4]`, show where
+/// the declarator goes: `void (*callback)(uint)` and `int values[4]`.
+fn declare(declared_type: &Type, declarator: &str) -> String {
+    let display_name = declared_type.get_display_name();
+    let position = match declared_type.get_kind() {
+        TypeKind::Pointer if is_function_pointer(declared_type) => {
+            // After the `*`s (and qualifiers) of the innermost `(*...)`.
+            display_name.find("(*")
+                .and_then(|start| display_name[start..].find(')').map(|end| start + end))
+        }
+        TypeKind::ConstantArray | TypeKind::IncompleteArray => {
+            display_name.find('[')
+        }
+        _ => None,
+    };
+    match position {
+        Some(position) => {
+            let (before, after) = display_name.split_at(position);
+            let separator = if before.ends_with(['*', '(']) { "" } else { " " };
+            format!("{}{}{}{}", before.trim_end_matches(' '), separator, declarator, after)
+        }
+        None => format!("{} {}", qualified_spelling(declared_type), declarator),
+    }
+}
+
+/// The spelling of `declared_type` with all of its qualifiers. The display name keeps the
+/// SDK's typedef names, which bindgen turns into the Rust types, but for some pointers it loses
+/// the qualifiers of the pointer or of what it points to, so those are spelled from their
+/// pointee instead: `const uint8_t *`, `uint8_t *restrict`, `const char *const *`.
+fn qualified_spelling(declared_type: &Type) -> String {
+    let display_name = declared_type.get_display_name();
+    let pointee = match declared_type.get_kind() {
+        TypeKind::Pointer if points_to_plain_type(declared_type) => declared_type.get_pointee_type(),
+        _ => None,
+    };
+    let mut spelling = match pointee {
+        Some(pointee) => {
+            let pointee = qualified_spelling(&pointee);
+            let separator = if pointee.ends_with('*') { "" } else { " " };
+            format!("{}{}*", pointee, separator)
+        }
+        None => display_name.clone(),
+    };
+    let qualifiers = [
+        (declared_type.is_const_qualified(), "const"),
+        (declared_type.is_volatile_qualified(), "volatile"),
+        (declared_type.is_restrict_qualified(), "restrict"),
+    ];
+    for (qualified, qualifier) in qualifiers {
+        if !qualified || (pointee.is_none() && display_name.split(|c: char| !c.is_ascii_alphanumeric() && c != '_').any(|word| word == qualifier)) {
+            continue;
+        }
+        if pointee.is_some() {
+            // A pointer's own qualifiers follow its `*`.
+            if !spelling.ends_with('*') {
+                spelling.push(' ');
+            }
+            spelling.push_str(qualifier);
+        } else {
+            spelling = format!("{} {}", qualifier, spelling);
+        }
+    }
+    spelling
+}
+
+/// Whether `pointer_type` points, possibly through further pointers, to something that is
+/// neither a function nor an array, whose declarators nest differently.
+fn points_to_plain_type(pointer_type: &Type) -> bool {
+    let mut pointee = pointer_type.get_pointee_type();
+    while let Some(pointee_type) = pointee {
+        match pointee_type.get_kind() {
+            TypeKind::FunctionPrototype | TypeKind::FunctionNoPrototype | TypeKind::ConstantArray | TypeKind::IncompleteArray => return false,
+            TypeKind::Pointer => pointee = pointee_type.get_pointee_type(),
+            _ => return true,
+        }
+    }
+    false
+}
+
+/// Whether `pointer_type` points, possibly through further pointers, to a function.
+fn is_function_pointer(pointer_type: &Type) -> bool {
+    let mut pointee = pointer_type.get_pointee_type();
+    while let Some(pointee_type) = pointee {
+        match pointee_type.get_kind() {
+            TypeKind::FunctionPrototype | TypeKind::FunctionNoPrototype => return true,
+            TypeKind::Pointer => pointee = pointee_type.get_pointee_type(),
+            _ => return false,
+        }
+    }
+    false
+}
+
+/// The C signature of the wrapper of `entity` without parameter names, e.g.
+/// `uint wrapped_pwm_gpio_to_slice_num(uint)`.
+fn create_wrapper_signature(entity: &Entity, takes_va_list: bool, prefix: &str) -> String {
+    let parameters = entity.get_children()
+        .into_iter()
+        .filter(|child| child.get_kind() == EntityKind::ParmDecl)
+        .map(|parameter| qualified_spelling(&parameter.get_type().unwrap()))
+        .chain(takes_va_list.then(|| "va_list".to_string()))
+        .collect::<Vec<_>>();
+    format!(
+        "{} {}{}({})",
+        qualified_spelling(&entity.get_result_type().unwrap()),
+        prefix,
+        entity.get_name().unwrap(),
+        parameters.join(", ")
+    )
+}
+
+/// Compares the wrapper signatures with the golden file at `path`, one sorted signature per
+/// line, and fails the build with the differences if they changed. With `update`, the file is
+/// rewritten instead.
+fn check_abi_golden(path: &str, mut signatures: Vec<String>, update: bool) -> BuildResult<()> {
+    println!("cargo:rerun-if-changed={}", path);
+    signatures.sort();
+    signatures.dedup();
+    if update {
+        let content = signatures.iter().map(|signature| format!("{}\n", signature)).collect::<String>();
+        fs::write(path, content).map_err(|e| format!("failed to write PICO_SDK_RS_ABI_GOLDEN {}: {}", path, e))?;
+        println!("cargo:warning=PICO_SDK_RS_ABI_GOLDEN: wrote {} signatures to {}", signatures.len(), path);
+        return Ok(());
+    }
+    let golden = fs::read_to_string(path).map_err(|e| {
+        format!("failed to read PICO_SDK_RS_ABI_GOLDEN {}: {} (set PICO_SDK_RS_ABI_GOLDEN_UPDATE=1 to create it)", path, e)
+    })?;
+    let golden = golden.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>();
+    let diff = golden.iter()
+        .filter(|signature| !signatures.iter().any(|current| current == *signature))
+        .map(|signature| format!("- {}", signature))
+        .chain(signatures.iter()
+            .filter(|current| !golden.contains(&current.as_str()))
+            .map(|signature| format!("+ {}", signature)))
+        .collect::<Vec<_>>();
+    if diff.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "wrapper signatures differ from PICO_SDK_RS_ABI_GOLDEN {} (set PICO_SDK_RS_ABI_GOLDEN_UPDATE=1 to accept them):\n{}",
+            path,
+            diff.join("\n")
+        ))
+    }
+}
+
+/// Decides which declarations are the SDK's: those in a header under one of the include
+/// directories. Paths are compared canonicalized, as the SDK's may contain `..` segments or run
+/// through symlinks (`/tmp` is one on macOS) where clang reports the resolved path; a path that
+/// cannot be canonicalized is compared as it is.
+struct SdkHeaders {
+    include_directories: Vec<PathBuf>,
+    /// Whether each header seen so far is under an include directory.
+    headers: HashMap<String, bool>,
+}
+
+impl SdkHeaders {
+    fn new(include_directories: &[String]) -> SdkHeaders {
+        SdkHeaders {
+            include_directories: include_directories.iter().map(|dir| canonicalize_or_keep(dir)).collect(),
+            headers: HashMap::new(),
+        }
+    }
+
+    fn declares(&mut self, entity: &Entity) -> bool {
+        let location = entity.get_location().unwrap();
+        let (header, _, _) = location.get_presumed_location();
+        let include_directories = &self.include_directories;
+        *self.headers.entry(header).or_insert_with_key(|header| {
+            let header = canonicalize_or_keep(header);
+            include_directories.iter().any(|dir| header.starts_with(dir))
+        })
+    }
+}
+
+fn canonicalize_or_keep(path: &str) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+/// Macros of the chip the SDK is configured for that `raspi_pico_sdk` sizes its tables by and
+/// finds registers at. The SDK defines them through `_u(...)`, which bindgen cannot evaluate,
+/// so the entry point declares a constant of each, under [`CHIP_CONSTANT_PREFIX`], that clang
+/// evaluates for bindgen.
+const CHIP_CONSTANTS: [&str; 8] = [
+    "NUM_BANK0_GPIOS", "NUM_DMA_CHANNELS", "NUM_PWM_SLICES", "NUM_PIOS", "NUM_ADC_CHANNELS", "ADC_BASE_PIN", "CLOCKS_BASE", "WATCHDOG_BASE",
+];
+
+/// Prefix of the C constants of [`CHIP_CONSTANTS`], which the bindings declare without it.
+const CHIP_CONSTANT_PREFIX: &str = "pico_sdk_rs_chip_";
+
+fn create_chip_constants() -> String {
+    CHIP_CONSTANTS.iter().map(|name| create_chip_constant(name)).collect()
+}
+
+/// A C constant of macro `name` under [`CHIP_CONSTANT_PREFIX`], if the macro is defined.
+fn create_chip_constant(name: &str) -> String {
+    format!("#ifdef {0}\nstatic const uint32_t {1}{0} __attribute__((unused)) = {0};\n#endif\n", name, CHIP_CONSTANT_PREFIX)
+}
+
+/// Whether `macro_definition` is an object-like macro of `allowed_vars` defined through
+/// `_u(...)`, like the addresses of `hardware/regs/addressmap.h`, which bindgen cannot evaluate.
+fn is_unsigned_macro(macro_definition: &Entity, allowed_vars: &Regex) -> bool {
+    !macro_definition.is_function_like_macro()
+        && macro_definition.get_name().is_some_and(|name| allowed_vars.is_match(&name) && !CHIP_CONSTANTS.contains(&name.as_str()))
+        && macro_definition.get_range().is_some_and(|range| range.tokenize().iter().skip(1).any(|token| token.get_spelling() == "_u"))
+}
+
+/// The constants of the macros [`is_unsigned_macro`] picked, declared like [`CHIP_CONSTANTS`].
+fn create_unsigned_constants(names: &[String]) -> String {
+    if names.is_empty() {
+        return String::new();
+    }
+    format!("\n{}", names.iter().map(|name| create_chip_constant(name)).collect::<String>())
+}
+
+/// The header the SDK declares the functions of `library` in by convention:
+/// `hardware_pwm` in `hardware/pwm.h`, `pico_multicore` in `pico/multicore.h`.
+fn library_header(library: &str) -> Option<String> {
+    ["hardware_", "pico_"].iter().find_map(|prefix| {
+        library.strip_prefix(prefix)
+            .filter(|name| is_identifier(name))
+            .map(|name| format!("{}/{}.h", &prefix[..prefix.len() - 1], name))
+    })
+}
+
+/// The headers next to `PICO_SDK_RS_EXTRA_SOURCES` entries with the same name (`fast.h` for
+/// `fast.c`), which the entry point includes so that their functions are wrapped.
+fn extra_source_headers(extra_sources: &[PathBuf]) -> Vec<PathBuf> {
+    extra_sources.iter()
+        .map(|source| source.with_extension("h"))
+        .filter(|header| header.is_file())
+        .collect()
+}
+
+fn create_entry_point_file_to_out_dir(out_dir: &Path) -> BuildResult<(PathBuf, File)> {
+    let mut entry_c = match std::env::var("PICO_SDK_RS_CUSTOM_ENTRY_POINT") {
+        Ok(entry_point) => {
+            println!("cargo:rerun-if-changed={}", entry_point);
+            let mut s = fs::read_to_string(&entry_point)
+                .map_err(|e| format!("failed to read PICO_SDK_RS_CUSTOM_ENTRY_POINT {}: {}", entry_point, e))?;
+            s.drain(s.find(GENERATED_CODE_MARKER).unwrap_or(s.len())..);
+            s
+        }
+        Err(_) => fs::read_to_string("cmake_pico/entry.c").expect("failed to read entry.c"),
+    };
+
+    if !entry_c.ends_with('\n') {
+        entry_c.push('\n');
+    }
+    for library in split_env_list("PICO_SDK_RS_LINK_LIBRARIES") {
+        if let Some(header) = library_header(&library) {
+            entry_c += &format!("#if __has_include(<{0}>)\n#include <{0}>\n#endif\n", header);
+        }
+    }
+
+    let extra_sources = split_env_paths("PICO_SDK_RS_EXTRA_SOURCES").iter().map(PathBuf::from).collect::<Vec<_>>();
+    for header in extra_source_headers(&extra_sources) {
+        println!("cargo:rerun-if-changed={}", header.display());
+        let header = fs::canonicalize(&header).unwrap_or(header);
+        entry_c += &format!("#include \"{}\"\n", header.display());
+    }
+
+    entry_c += &create_chip_constants();
+
+    let entry_path = out_dir.join("entry.c");
+    let mut entry = File::create(&entry_path).expect("failed to create entry.c");
+    entry.write_all(entry_c.as_bytes())
+        .expect("failed to write to entry.c");
+    Ok((entry_path, entry))
+}
+
+/// Whether `target_triple` is a bare-metal Cortex-M target, which is built with the
+/// `arm-none-eabi` toolchain like the SDK itself.
+fn is_arm_baremetal(target_triple: &str) -> bool {
+    target_triple.starts_with("thumbv") && (target_triple.ends_with("-none-eabi") || target_triple.ends_with("-none-eabihf"))
+}
+
+/// The `PICO_PLATFORM` of the SDK and the `-mcpu` of the chip a target is meant for: the RP2040
+/// for the Cortex-M0+ and the Arm cores of the RP2350 for the Cortex-M33.
+fn pico_platform(target_triple: &str) -> Option<(&'static str, &'static str)> {
+    if target_triple.starts_with("thumbv6m-") {
+        Some(("rp2040", "cortex-m0plus"))
+    } else if target_triple.starts_with("thumbv8m.main-") {
+        Some(("rp2350-arm-s", "cortex-m33"))
+    } else {
+        None
+    }
+}
+
+/// The gcc that reports the implicit include directories and the variable that named it, if
+/// any: `PICO_SDK_RS_ARM_GCC` or `CC_<target>` for bare-metal targets, `PICO_SDK_RS_HOST_GCC` or
+/// `CC` otherwise.
+fn select_gcc(target_triple: &str) -> (String, Option<String>) {
+    let variables = if is_arm_baremetal(target_triple) {
+        vec!["PICO_SDK_RS_ARM_GCC".to_string(), format!("CC_{}", target_triple.replace('-', "_"))]
+    } else {
+        vec!["PICO_SDK_RS_HOST_GCC".to_string(), "CC".to_string()]
     };
+    for variable in variables {
+        if let Some(gcc) = get_env(&variable) {
+            return (gcc, Some(variable));
+        }
+    }
+    let default = if is_arm_baremetal(target_triple) { "arm-none-eabi-gcc" } else { "gcc" };
+    (default.to_string(), None)
+}
+
+fn get_implicit_include_directories(target_triple: &str) -> BuildResult<Vec<String>> {
+    let (gcc, variable) = select_gcc(target_triple);
+    let mut gcc_command = Command::new(&gcc);
+    if is_arm_baremetal(target_triple) {
+        if let Some((_, cpu)) = pico_platform(target_triple) {
+            gcc_command.arg(format!("-mcpu={}", cpu));
+        }
+        if target_triple.ends_with("eabihf") {
+            gcc_command.arg("-mfloat-abi=hard");
+        }
+    }
     let mut gcc_process = gcc_command
-        .args(&["-xc", "-v", "-E", "-"])
+        .args(["-xc", "-v", "-E", "-"])
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .stderr(Stdio::piped())
         .spawn()
-        .expect("failed to execute gcc command");
+        .map_err(|e| match (e.kind(), &variable) {
+            (_, Some(variable)) => format!("failed to run `{}`, which {} names: {}", gcc, variable, e),
+            (io::ErrorKind::NotFound, None) if is_arm_baremetal(target_triple) => format!(
+                "`{}` was not found; install the GNU Arm Embedded toolchain (`gcc-arm-none-eabi` on Debian/Ubuntu, `arm-none-eabi-gcc` on Homebrew) and add it to `PATH`, or point `PICO_SDK_RS_ARM_GCC` at it",
+                gcc
+            ),
+            (io::ErrorKind::NotFound, None) => format!("`{}` was not found; install a C compiler and add it to `PATH`, or point `PICO_SDK_RS_HOST_GCC` at it", gcc),
+            _ => format!("failed to run `{}`: {}", gcc, e),
+        })?;
 
     let gcc_output = {
         let out = gcc_process.stderr.as_mut().expect("failed to open stderr of gcc");
@@ -239,15 +2492,15 @@ fn get_implicit_include_directories(target_triple: &str) -> Vec<String> {
             .expect("failed to read stderr of gcc");
         gcc_output
     };
-    let mut gcc_output = gcc_output.split("\n");
-    while let Some(s) = gcc_output.next() {
+    let mut gcc_output = gcc_output.split('\n');
+    for s in gcc_output.by_ref() {
         if s.trim() == "#include <...> search starts here:" {
             break;
         }
     }
 
     let mut implicit_include_directories = Vec::new();
-    while let Some(s) = gcc_output.next() {
+    for s in gcc_output {
         let s = s.trim();
         if Path::new(s).exists() {
             implicit_include_directories.push(s.to_string());
@@ -255,5 +2508,1180 @@ fn get_implicit_include_directories(target_triple: &str) -> Vec<String> {
             break;
         }
     }
-    implicit_include_directories
+    Ok(implicit_include_directories)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory for `name` under the system's temporary directory.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raspi-pico-sdk-sys-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Runs `f` with the environment variables of `vars` set (or removed for `None`), restoring
+    /// them afterwards. Tests touching the environment take turns, as it is shared by the threads
+    /// running the tests; tests loading libclang do too, as `LIBCLANG_PATH` picks the library.
+    fn with_env<R>(vars: &[(&str, Option<&str>)], f: impl FnOnce() -> R) -> R {
+        static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let previous = vars.iter().map(|(name, _)| (*name, std::env::var_os(name))).collect::<Vec<_>>();
+        for (name, value) in vars {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+        for (name, value) in previous {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+        result.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    /// Declarations standing in for the SDK headers, with a wrapper of each kind of type the
+    /// bindings declare.
+    const FIXTURE_HEADER: &str = "\
+typedef unsigned int uint;
+typedef struct { uint wrap; } pwm_config;
+enum gpio_function { GPIO_FUNC_SPI = 1, GPIO_FUNC_UART = 2 };
+uint wrapped_pwm_config_get_wrap(const pwm_config *config);
+void wrapped_gpio_set_function(uint gpio, enum gpio_function fn);
+";
+
+    /// Generates the bindings of `header`, declaring the wrappers of `functions` and the types
+    /// of `types`, in the current environment and formatted as `bindings.rs` is. Needs
+    /// libclang, like the build itself.
+    fn generate_fixture_bindings(name: &str, header: &str, functions: &[(&str, usize)], types: &[&str]) -> String {
+        let entry_path = temp_dir(name).join("fixture.h");
+        fs::write(&entry_path, header).unwrap();
+        let wrapped_functions = functions.iter().map(|(name, arity)| (name.to_string(), *arity)).collect::<Vec<_>>();
+        let sdk_types = SdkTypes { names: types.iter().map(|name| name.to_string()).collect(), ..SdkTypes::default() };
+        let code = generate_rust_binding(&BindingInputs {
+            target_triple: "thumbv6m-none-eabi",
+            implicit_include_directories: &[],
+            entry_path: &entry_path,
+            clang_arguments: &[],
+            manifest: "pico-sdk=unknown board=unknown wrappers=0",
+            wrapped_functions: &wrapped_functions,
+            sdk_types: &sdk_types,
+            prefix: RUST_WRAPPER_PREFIX,
+        });
+        format_code(code.unwrap())
+    }
+
+    /// Writes an SDK made of `headers` (paths under the include directory and their contents)
+    /// to `dir`, returning its include directory.
+    fn write_fixture_sdk(dir: &Path, headers: &[(&str, &str)]) -> PathBuf {
+        let include_directory = dir.join("include");
+        for (path, content) in [("pico/stdlib.h", "")].iter().chain(headers) {
+            let path = include_directory.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        include_directory
+    }
+
+    /// Runs the wrapper generation over `cmake_pico/entry.c` with an SDK made of `headers` and
+    /// `definitions`, returning the entry point with the wrappers appended and the wrapped
+    /// functions. Needs libclang.
+    fn wrap_fixture_sdk(name: &str, headers: &[(&str, &str)], definitions: &[&str]) -> (String, Vec<(String, usize)>) {
+        let dir = temp_dir(name);
+        let include_directory = write_fixture_sdk(&dir, headers);
+        wrap_fixture_entry(&dir, &include_directory.display().to_string(), definitions, &[])
+    }
+
+    /// [`wrap_fixture_sdk`] with the entry point in `dir`, the SDK found as `include_directory`
+    /// and the environment variables of `vars` set as by [`with_env`].
+    fn wrap_fixture_entry(dir: &Path, include_directory: &str, definitions: &[&str], vars: &[(&str, Option<&str>)]) -> (String, Vec<(String, usize)>) {
+        // Only one instance of libclang can be loaded at a time.
+        with_env(vars, || {
+            let entry_path = dir.join("entry.c");
+            fs::copy(Path::new(env!("CARGO_MANIFEST_DIR")).join("cmake_pico/entry.c"), &entry_path).unwrap();
+            let mut entry = fs::OpenOptions::new().append(true).open(&entry_path).unwrap();
+            let include_directories = vec![include_directory.to_string()];
+            let definitions = definitions.iter().map(|definition| definition.to_string()).collect();
+            let (_, wrapped_functions, _) =
+                write_wrapper_function(&Vec::new(), &include_directories, definitions, &entry_path, &mut entry, false, RUST_WRAPPER_PREFIX).unwrap();
+            (fs::read_to_string(&entry_path).unwrap(), wrapped_functions)
+        })
+    }
+
+    /// Links `dir/entry.c` and the files of `sources`, written to `dir`, into a program with the
+    /// host C compiler (`CC`, or `cc`) and runs it, returning the errors if either fails.
+    fn link_fixture_program(dir: &Path, include_directory: &str, sources: &[(&str, &str)]) -> Result<(), String> {
+        for (name, content) in sources {
+            fs::write(dir.join(name), content).unwrap();
+        }
+        let program = dir.join("program");
+        let compiler = std::env::var("CC").unwrap_or("cc".to_string());
+        let output = Command::new(compiler)
+            .args(["-std=c11", "-Werror", "-I", include_directory, "-o"])
+            .arg(&program)
+            .arg(dir.join("entry.c"))
+            .args(sources.iter().map(|(name, _)| dir.join(name)))
+            .output()
+            .unwrap();
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+        }
+        let status = Command::new(&program).status().unwrap();
+        if status.success() { Ok(()) } else { Err(format!("{} exited with {}", program.display(), status)) }
+    }
+
+    /// Checks that the host C compiler (`CC`, or `cc`) accepts `entry` over an SDK made of
+    /// `headers`, returning its errors if not.
+    fn check_fixture_entry(name: &str, headers: &[(&str, &str)], entry: &str) -> Result<(), String> {
+        let dir = temp_dir(name);
+        let include_directory = write_fixture_sdk(&dir, headers);
+        fs::write(dir.join("entry.c"), entry).unwrap();
+        let compiler = std::env::var("CC").unwrap_or("cc".to_string());
+        let output = Command::new(compiler)
+            .args(["-fsyntax-only", "-Werror", "-I"])
+            .arg(&include_directory)
+            .arg(dir.join("entry.c"))
+            .output()
+            .unwrap();
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+
+    /// Compiles `src/lib.rs` with `code` as `bindings.rs` and the `cfgs`, returning rustc's
+    /// errors if it fails. The generated code refers to `cty`, which is declared next to it.
+    fn compile_sys_crate(name: &str, code: &str, cfgs: &[&str]) -> Result<(), String> {
+        let out_dir = temp_dir(name);
+        let c_types = "pub mod cty { pub type c_int = i32; pub type c_uint = u32; pub type c_char = u8; pub type c_void = core::ffi::c_void; }\n";
+        fs::write(out_dir.join("bindings.rs"), format!("{}{}", c_types, code)).unwrap();
+        let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+        let mut command = Command::new(rustc);
+        command
+            .args(["--edition", "2018", "--crate-type", "lib", "--crate-name", "raspi_pico_sdk_sys", "--out-dir"])
+            .arg(&out_dir)
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("src/lib.rs"))
+            .env("OUT_DIR", &out_dir);
+        for cfg in cfgs {
+            command.args(["--cfg", cfg]);
+        }
+        let output = command.output().unwrap();
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+
+    /// Runs [`get_compile_options`] for the host with `vars` set and a `CMAKE` that only logs
+    /// its arguments, returning the result, an error unless the files the configure writes are
+    /// already in `dir`, and the logged arguments.
+    #[cfg(unix)]
+    fn configure_with_fake_cmake(dir: &Path, vars: &[(&str, Option<&str>)]) -> (BuildResult<CompileOptions>, String) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (cmake, log) = (dir.join("cmake"), dir.join("cmake-arguments"));
+        fs::write(&cmake, format!("#!/bin/sh\necho \"$@\" >> {}\n", log.display())).unwrap();
+        fs::set_permissions(&cmake, fs::Permissions::from_mode(0o755)).unwrap();
+        let _ = fs::remove_file(&log);
+        let cmake = cmake.display().to_string();
+        let host = guess_host_triple::guess_host_triple().unwrap_or("x86_64-unknown-linux-gnu");
+        let mut all_vars = vec![
+            ("CMAKE", Some(cmake.as_str())),
+            ("PICO_PLATFORM", None),
+            ("TARGET", Some(host)),
+            ("HOST", Some(host)),
+            ("OPT_LEVEL", Some("0")),
+            ("PROFILE", Some("debug")),
+            ("DEBUG", Some("true")),
+        ];
+        all_vars.extend_from_slice(vars);
+        let result = with_env(&all_vars, || get_compile_options(host, dir, &dir.join("entry.c"), None));
+        (result, fs::read_to_string(&log).unwrap_or_default())
+    }
+
+    #[test]
+    fn parse_version_fills_missing_components_and_drops_suffixes() {
+        assert_eq!(parse_version("2.0.0"), Ok([2, 0, 0]));
+        assert_eq!(parse_version(" 1.5 "), Ok([1, 5, 0]));
+        assert_eq!(parse_version("2"), Ok([2, 0, 0]));
+        assert_eq!(parse_version("2.1.0-develop"), Ok([2, 1, 0]));
+        assert_eq!(parse_version("1.5.1+git"), Ok([1, 5, 1]));
+        assert!(parse_version("1.2.3.4").is_err());
+        assert!(parse_version("two").is_err());
+        assert!(parse_version("").is_err());
+    }
+
+    #[test]
+    fn version_satisfies_every_comparator() {
+        assert_eq!(version_satisfies("2.0.0", ">=2.0.0, <3"), Ok(true));
+        assert_eq!(version_satisfies("3.0.0", ">=2.0.0, <3"), Ok(false));
+        assert_eq!(version_satisfies("1.5.1", ">=2.0.0, <3"), Ok(false));
+        assert_eq!(version_satisfies("1.5.1", ">1.5"), Ok(true));
+        assert_eq!(version_satisfies("1.5.0", ">1.5"), Ok(false));
+        assert_eq!(version_satisfies("1.5.0", "<=1.5"), Ok(true));
+        assert_eq!(version_satisfies("2.0.0-rc1", "==2.0.0"), Ok(true));
+        assert_eq!(version_satisfies("2.0.0", "=2"), Ok(true));
+        assert_eq!(version_satisfies("2.0.1", "=2.0"), Ok(true));
+        assert_eq!(version_satisfies("2.1.0", "=2.0"), Ok(false));
+        assert_eq!(version_satisfies("2.0.1", "=2.0.0"), Ok(false));
+    }
+
+    #[test]
+    fn version_satisfies_caret_and_tilde_requirements() {
+        assert_eq!(version_satisfies("2.1.1", "2.0"), Ok(true));
+        assert_eq!(version_satisfies("3.0.0", "2.0"), Ok(false));
+        assert_eq!(version_satisfies("1.5.1", "^2"), Ok(false));
+        assert_eq!(version_satisfies("2.9.0", "^2"), Ok(true));
+        assert_eq!(version_satisfies("0.3.1", "^0.3"), Ok(true));
+        assert_eq!(version_satisfies("0.4.0", "^0.3"), Ok(false));
+        assert_eq!(version_satisfies("0.0.4", "^0.0.3"), Ok(false));
+        assert_eq!(version_satisfies("0.0.9", "^0.0"), Ok(true));
+        assert_eq!(version_satisfies("2.0.5", "~2.0"), Ok(true));
+        assert_eq!(version_satisfies("2.1.0", "~2.0"), Ok(false));
+        assert_eq!(version_satisfies("2.1.0", "~2"), Ok(true));
+        assert_eq!(version_satisfies("2.1.2", "~2.1.3"), Ok(false));
+    }
+
+    #[test]
+    fn version_satisfies_rejects_invalid_requirements() {
+        assert!(version_satisfies("2.0.0", ">=x").is_err());
+        assert!(version_satisfies("2.0.0", ">=2,").is_err());
+        assert!(version_satisfies("unknown", ">=2").is_err());
+    }
+
+    #[test]
+    fn sdk_version_is_read_from_the_first_version_header() {
+        let dir = temp_dir("sdk-version");
+        let (empty, sdk) = (dir.join("empty"), dir.join("sdk"));
+        fs::create_dir_all(&empty).unwrap();
+        fs::create_dir_all(sdk.join("pico")).unwrap();
+        fs::write(
+            sdk.join("pico/version.h"),
+            "#define PICO_SDK_VERSION_MAJOR 2\n#define PICO_SDK_VERSION_STRING \"2.0.0\"\n",
+        )
+        .unwrap();
+        let include_directories = vec![empty.display().to_string(), sdk.display().to_string()];
+        assert_eq!(get_sdk_version(&include_directories), Some("2.0.0".to_string()));
+        assert_eq!(get_sdk_version(&include_directories[..1]), None);
+    }
+
+    #[test]
+    fn development_sdk_versions_satisfy_the_requirements_of_their_release() {
+        let dir = temp_dir("development-sdk-version");
+        fs::create_dir_all(dir.join("pico")).unwrap();
+        fs::write(dir.join("pico/version.h"), "#define PICO_SDK_VERSION_STRING \"2.1.1-develop\"\n").unwrap();
+        let include_directories = vec![dir.display().to_string()];
+        assert_eq!(get_sdk_version(&include_directories), Some("2.1.1-develop".to_string()));
+        assert_eq!(assert_sdk_version(">= 2.1.1, < 3", &include_directories), Ok(()));
+        assert_eq!(assert_sdk_version("2.1", &include_directories), Ok(()));
+        assert!(assert_sdk_version("~2.0", &include_directories).unwrap_err().contains("2.1.1-develop"));
+    }
+
+    #[test]
+    fn extra_link_libraries_and_search_paths_are_split_from_their_lists() {
+        let vars = [("PICO_SDK_RS_LINK_LIBS", Some(" static=foo : bar ::")), ("PICO_SDK_RS_LINK_SEARCH", Some("/opt/lib"))];
+        with_env(&vars, || {
+            assert_eq!(split_env_list("PICO_SDK_RS_LINK_LIBS"), ["static=foo", "bar"]);
+            assert_eq!(split_env_paths("PICO_SDK_RS_LINK_SEARCH"), ["/opt/lib"]);
+        });
+        with_env(&[("PICO_SDK_RS_LINK_LIBS", None), ("PICO_SDK_RS_LINK_SEARCH", Some(""))], || {
+            assert!(split_env_list("PICO_SDK_RS_LINK_LIBS").is_empty());
+            assert!(split_env_paths("PICO_SDK_RS_LINK_SEARCH").is_empty());
+        });
+    }
+
+    #[test]
+    fn board_is_read_from_the_sdk_definitions() {
+        let definitions = vec!["PICO_ON_DEVICE=1".to_string(), "PICO_BOARD=\"pico_w\"".to_string()];
+        assert_eq!(get_board(&definitions), Some("pico_w".to_string()));
+        assert_eq!(get_board(&definitions[..1]), None);
+    }
+
+    #[test]
+    fn arity_check_calls_every_wrapper_with_its_parameter_count() {
+        let wrapped_functions = vec![("gpio_put".to_string(), 2), ("get_core_num".to_string(), 0)];
+        let check = create_arity_check(&wrapped_functions);
+        assert!(check.contains("        wrapped_gpio_put(placeholder(), placeholder());\n"));
+        assert!(check.contains("        wrapped_get_core_num();\n"));
+        let declarations = "extern \"C\" { pub fn wrapped_gpio_put(gpio: u32, value: bool); pub fn wrapped_get_core_num() -> u32; }\n";
+        let cfg = &["feature=\"arity-check\""];
+        compile_sys_crate("arity-check", &format!("{}{}", declarations, check), cfg).unwrap();
+        let changed = declarations.replace("gpio: u32, value: bool", "gpio: u32");
+        assert!(compile_sys_crate("arity-check-changed", &format!("{}{}", changed, check), cfg).unwrap_err().contains("wrapped_gpio_put"));
+    }
+
+    #[test]
+    fn used_functions_are_matched_with_or_without_the_prefix() {
+        let wrapped_functions = vec![("gpio_put".to_string(), 2), ("gpio_init".to_string(), 1), ("sleep_ms".to_string(), 1)];
+        assert_eq!(select_used_functions(wrapped_functions.clone(), &Vec::new(), "wrapped_"), wrapped_functions);
+        let used_functions = vec!["wrapped_gpio_put".to_string(), "pico_sleep_ms".to_string(), "gpio_deinit".to_string()];
+        assert_eq!(
+            select_used_functions(wrapped_functions, &used_functions, "pico_"),
+            [("gpio_put".to_string(), 2), ("sleep_ms".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn implicitly_declared_functions_are_named_by_both_wordings() {
+        assert_eq!(
+            implicitly_declared_function("implicit declaration of function 'adc_init' is invalid in C99"),
+            Some("adc_init".to_string())
+        );
+        assert_eq!(
+            implicitly_declared_function("call to undeclared function 'adc_read'; ISO C99 and later do not support implicit function declarations"),
+            Some("adc_read".to_string())
+        );
+        assert_eq!(implicitly_declared_function("unused variable 'x'"), None);
+    }
+
+    #[test]
+    fn declaring_headers_are_told_from_the_ones_calling_the_function() {
+        assert!(declares_function("static inline uint16_t adc_read(void) {", "adc_read"));
+        assert!(declares_function("void *adc_buffer (uint n);", "adc_buffer"));
+        assert!(!declares_function("    return adc_read();", "adc_read"));
+        assert!(!declares_function("    x = adc_read();", "adc_read"));
+        assert!(!declares_function("void my_adc_read(void);", "adc_read"));
+        let dir = temp_dir("declaring-headers");
+        let include_directory = write_fixture_sdk(&dir, &[
+            ("hardware/adc.h", "uint16_t adc_read(void);\n"),
+            ("hardware/uses_adc.h", "static inline uint16_t twice(void) { return adc_read() * 2; }\n"),
+        ]);
+        let include_directories = vec![include_directory.display().to_string()];
+        assert_eq!(find_declaring_headers(&include_directories, "adc_read"), ["hardware/adc.h"]);
+        assert!(find_declaring_headers(&include_directories, "pwm_init").is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn lto_needs_a_clang_on_the_llvm_of_rustc() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("lto-toolchain");
+        let script = |name: &str, output: &str| {
+            let path = dir.join(name);
+            fs::write(&path, format!("#!/bin/sh\necho '{}'\n", output)).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            path.display().to_string()
+        };
+        let (clang, gcc) = (script("clang", "clang version 17.0.6"), script("gcc", "arm-none-eabi-gcc (GNU) 13.2.1"));
+        let (rustc_17, rustc_18) = (script("rustc-17", "LLVM version: 17.0.6"), script("rustc-18", "LLVM version: 18.1.7"));
+        let use_compiler = |compiler: &str| {
+            fs::create_dir_all(dir.join("build")).unwrap();
+            fs::write(dir.join("build/CMakeCache.txt"), format!("CMAKE_C_COMPILER:FILEPATH={}\n", compiler)).unwrap();
+        };
+        use_compiler(&clang);
+        with_env(&[("RUSTC", Some(rustc_17.as_str()))], || assert_eq!(check_lto_toolchain(&dir), Ok(())));
+        with_env(&[("RUSTC", Some(rustc_18.as_str()))], || {
+            assert!(check_lto_toolchain(&dir).unwrap_err().contains("uses LLVM 17, but rustc uses LLVM 18"));
+        });
+        use_compiler(&gcc);
+        with_env(&[("RUSTC", Some(rustc_18.as_str()))], || assert_eq!(check_lto_toolchain(&dir), Ok(())));
+        let vars = [("PICO_SDK_RS_LTO", Some("1")), ("CARGO_FEATURE_ALLOC", None), ("PICO_SDK_RS_BOOT_STAGE2", None)];
+        with_env(&vars, || assert_eq!(firmware_link_args(&dir), [LTO_FLAG]));
+    }
+
+    #[test]
+    fn priority_directories_come_first_in_the_order_given() {
+        let include_directories = ["/sdk/src/common", "/app/include", "/sdk/src/rp2040", "/vendor/lib/include", "/sdk/src/host"]
+            .iter().map(|dir| dir.to_string()).collect::<Vec<_>>();
+        let priority = vec!["/vendor".to_string(), "/app".to_string()];
+        assert_eq!(
+            prioritize_include_directories(include_directories.clone(), &priority),
+            ["/vendor/lib/include", "/app/include", "/sdk/src/common", "/sdk/src/rp2040", "/sdk/src/host"]
+        );
+        // Prefixes match whole path components only.
+        let priority = vec!["/sdk/src/rp".to_string()];
+        assert_eq!(prioritize_include_directories(include_directories.clone(), &priority), include_directories);
+    }
+
+    #[test]
+    fn the_pinned_libclang_is_handed_on_through_libclang_path() {
+        let libclang = temp_dir("pinned-libclang").join("libclang.so");
+        fs::write(&libclang, "").unwrap();
+        let libclang = libclang.display().to_string();
+        with_env(&[("PICO_SDK_RS_LIBCLANG", Some(libclang.as_str())), ("LIBCLANG_PATH", Some("/usr/lib/llvm-10/lib"))], || {
+            assert_eq!(pin_libclang(), Ok(()));
+            assert_eq!(std::env::var("LIBCLANG_PATH").as_deref(), Ok(libclang.as_str()));
+        });
+        with_env(&[("PICO_SDK_RS_LIBCLANG", None), ("LIBCLANG_PATH", Some("/usr/lib/llvm-10/lib"))], || {
+            assert_eq!(pin_libclang(), Ok(()));
+            assert_eq!(std::env::var("LIBCLANG_PATH").as_deref(), Ok("/usr/lib/llvm-10/lib"));
+        });
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn extra_sources_are_compiled_with_the_sdk_and_their_headers_wrapped() {
+        let dir = temp_dir("extra-sources");
+        let (fast, slow) = (dir.join("fast.c"), dir.join("slow.c"));
+        for path in [&fast, &slow, &dir.join("fast.h")] {
+            fs::write(path, "").unwrap();
+        }
+        assert_eq!(extra_source_headers(&[fast.clone(), slow.clone()]), [dir.join("fast.h")]);
+
+        let sources = format!("{}:{}", fast.display(), slow.display());
+        let vars = [("PICO_SDK_RS_EXTRA_SOURCES", Some(sources.as_str())), ("PICO_SDK_RS_BOOT_STAGE2", None), ("PICO_SDK_RS_LTO", None)];
+        let (_, arguments) = configure_with_fake_cmake(&dir, &vars);
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap().display().to_string();
+        assert!(arguments.contains(&format!("-DEXTRA_SOURCES={};{}", canonical(&fast), canonical(&slow))), "{}", arguments);
+        let (result, _) = configure_with_fake_cmake(&dir, &[("PICO_SDK_RS_EXTRA_SOURCES", Some("/nonexistent/extra.c"))]);
+        let error = result.unwrap_err();
+        assert!(error.contains("failed to find PICO_SDK_RS_EXTRA_SOURCES entry /nonexistent/extra.c"), "{}", error);
+    }
+
+    #[test]
+    fn listed_handle_types_become_send_and_sync() {
+        let code = "#[repr(C)]\npub struct i2c_inst { pub hw: *mut u32, pub restart_on_next: bool }\npub type i2c_inst_t = i2c_inst;\n";
+        let defined_types = defined_type_names(code);
+        assert_eq!(defined_types, ["i2c_inst", "i2c_inst_t"]);
+        let impls = create_send_sync_impls(&defined_types, &["i2c_inst_t".to_string()]).unwrap();
+        let check = "fn _assert_send_sync<T: Send + Sync>() {}\nfn _check() { _assert_send_sync::<i2c_inst>(); }\n";
+        compile_sys_crate("send-sync", &format!("{}{}{}", code, impls, check), &[]).unwrap();
+        assert!(compile_sys_crate("send-sync-unlisted", &format!("{}{}", code, check), &[]).is_err());
+        let error = create_send_sync_impls(&defined_types, &["spi_inst_t".to_string()]).unwrap_err();
+        assert!(error.contains("`spi_inst_t`, which is not a type"));
+    }
+
+    #[test]
+    fn host_stubs_replace_the_wrapper_declarations() {
+        let bindings = concat!(
+            "pub type irq_handler_t = :: core :: option :: Option < unsafe extern \"C\" fn () > ;\n",
+            "extern \"C\" { # [doc = \" Returns `{ 0 }` \\\" }\"] pub fn wrapped_gpio_get (gpio : u32) -> bool ; }\n",
+            "extern \"C\" { pub fn wrapped_irq_set_exclusive_handler (num : u32 , handler : irq_handler_t) ; }\n",
+            "# [repr (u32)] # [derive (Debug , Copy , Clone)] pub enum gpio_function { GPIO_FUNC_XIP = 0 , GPIO_FUNC_SIO = 5 , }\n",
+            "pub use self :: gpio_function as gpio_function_t ;\n",
+            "extern \"C\" { pub fn wrapped_gpio_get_function (gpio : u32) -> gpio_function_t ; }\n",
+            "extern \"C\" { pub fn wrapped_panic_unsupported () -> ! ; }\n",
+            "extern \"C\" { pub static mut other : u32 ; }\n",
+        );
+        let gated = gate_wrapper_declarations(bindings);
+        assert_eq!(gated.matches(&format!("#[cfg(not({}))]\nextern", HOST_STUBS_CFG)).count(), 4);
+        assert!(gated.ends_with("\nextern \"C\" { pub static mut other : u32 ; }\n"));
+        let stubs = create_host_stubs(&gated);
+        assert!(!stubs.contains("export_name") && !stubs.contains("no_mangle"));
+        assert!(stubs.contains("called(\"wrapped_gpio_get\"); core::mem::zeroed()"));
+        // A zeroed `gpio_function` is valid by chance; the first variant always is.
+        assert!(stubs.contains("called(\"wrapped_gpio_get_function\"); gpio_function::GPIO_FUNC_XIP"));
+        assert!(stubs.contains("extern \"C-unwind\" fn wrapped_panic_unsupported () -> ! { called(\"wrapped_panic_unsupported\"); panic!("));
+        let names = "wrapped_gpio_get, wrapped_gpio_get_function, wrapped_irq_set_exclusive_handler, wrapped_panic_unsupported";
+        assert!(stubs.ends_with(&format!("pub use host_stubs::{{{}}};\n", names)));
+        let code = gated + &stubs;
+        compile_sys_crate("host-stubs", &code, &["feature=\"host-stubs\""]).unwrap();
+        compile_sys_crate("host-stubs-off", &code, &[]).unwrap();
+    }
+
+    #[test]
+    fn the_entry_main_calls_the_rust_symbol() {
+        let main = create_entry_main("rust_main").unwrap();
+        assert!(main.contains("void rust_main(void);") && main.contains("    rust_main();\n"));
+        let entry = format!("{}\nvoid rust_main(void) {{}}\n", main);
+        assert_eq!(check_fixture_entry("entry-main", &[], &entry), Ok(()));
+        assert!(create_entry_main("1st_main").is_err());
+    }
+
+    #[test]
+    fn the_selected_board_of_the_list_is_the_active_one() {
+        let boards = vec!["pico".to_string(), "pico_w".to_string()];
+        with_env(&[("PICO_SDK_RS_BOARD", Some("pico_w")), ("PICO_BOARD", Some("pico"))], || assert_eq!(active_board(&boards), "pico_w"));
+        with_env(&[("PICO_SDK_RS_BOARD", None), ("PICO_BOARD", Some("pico_w"))], || assert_eq!(active_board(&boards), "pico_w"));
+        with_env(&[("PICO_SDK_RS_BOARD", Some("pico2")), ("PICO_BOARD", None)], || assert_eq!(active_board(&boards), "pico"));
+        let out_dir = Path::new("/out");
+        with_env(&[("PICO_SDK_RS_BOARD", None), ("PICO_BOARD", None), ("PICO_SDK_RS_BOARDS", Some("pico:pico_w"))], || {
+            assert_eq!(sdk_build_dir(out_dir), Path::new("/out/boards/pico/build"));
+        });
+        with_env(&[("PICO_SDK_RS_BOARDS", None)], || assert_eq!(sdk_build_dir(out_dir), Path::new("/out/build")));
+    }
+
+    #[test]
+    fn combined_boards_must_be_valid_names() {
+        let boards = vec!["pico".to_string(), "../pico_w".to_string()];
+        let error = generate_combined_binding("thumbv6m-none-eabi", &Vec::new(), &temp_dir("combined-boards"), &boards).unwrap_err();
+        assert_eq!(error, "PICO_SDK_RS_BOARDS entry `../pico_w` is not a valid board name");
+    }
+
+    #[test]
+    fn bindings_are_kept_unformatted_when_rustfmt_fails() {
+        let code = "pub const  A : u32 = 1 ;".to_string();
+        with_env(&[("RUSTFMT", None)], || assert_eq!(format_code(code.clone()), "pub const A: u32 = 1;\n"));
+        with_env(&[("RUSTFMT", Some("/nonexistent/rustfmt"))], || assert_eq!(format_code(code.clone()), code));
+        let invalid = "pub const A: u32 = ;".to_string();
+        with_env(&[("RUSTFMT", None)], || assert_eq!(format_code(invalid.clone()), invalid));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn the_float_implementation_is_passed_to_the_sdk_build() {
+        let dir = temp_dir("float-impl");
+        let (_, arguments) = configure_with_fake_cmake(&dir, &[("PICO_SDK_RS_FLOAT_IMPL", Some("compiler"))]);
+        assert!(arguments.contains("-DFLOAT_IMPL=compiler"), "{}", arguments);
+        let (result, arguments) = configure_with_fake_cmake(&dir, &[("PICO_SDK_RS_FLOAT_IMPL", Some("fast"))]);
+        assert!(result.unwrap_err().contains("PICO_SDK_RS_FLOAT_IMPL must be one of"));
+        assert!(!arguments.contains("FLOAT_IMPL"), "{}", arguments);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn include_directories_are_listed_once_in_the_order_of_their_first_mention() {
+        let dir = temp_dir("duplicate-includes");
+        fs::create_dir_all(dir.join("build/CMakeFiles/pico.dir")).unwrap();
+        fs::write(dir.join("build/CMakeFiles/pico.dir/DependInfo.cmake"), "").unwrap();
+        fs::write(dir.join("include_path"), "/sdk/common\n/sdk/rp2040\n /sdk/common \n\n/sdk/host\n/sdk/rp2040\n").unwrap();
+        fs::write(dir.join("definitions"), "PICO_RP2040=1\n\nPICO_BOARD=\"pico\"\n").unwrap();
+        let (result, _) = configure_with_fake_cmake(&dir, &[("PICO_SDK_RS_CHECK_ONLY", Some("1"))]);
+        let (include_directories, definitions) = result.unwrap();
+        assert_eq!(include_directories, ["/sdk/common", "/sdk/rp2040", "/sdk/host"]);
+        assert_eq!(definitions, ["PICO_RP2040=1", "PICO_BOARD=\"pico\""]);
+    }
+
+    #[test]
+    fn listed_enums_convert_to_and_from_their_repr() {
+        let formatted = concat!(
+            "#[repr(u32)]\n#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]\npub enum gpio_irq_level {\n",
+            "    /// IRQ when the GPIO pin is a logical 0, e.g. `level = 0`\n",
+            "    GPIO_IRQ_LEVEL_LOW = 1,\n    GPIO_IRQ_LEVEL_HIGH = 2,\n    GPIO_IRQ_EDGE_FALL = 4,\n    GPIO_IRQ_EDGE_RISE = 8,\n}\n",
+        );
+        let unformatted = "# [repr (i32)] # [derive (Debug , Copy , Clone , PartialEq , Eq)] pub enum pico_error_codes { # [doc = \" An error, = -1\"] PICO_ERROR_NONE = 0 , PICO_ERROR_GENERIC = - 1 , }";
+        let (repr, variants) = find_rust_enum(formatted, "gpio_irq_level").unwrap();
+        assert_eq!(repr, "u32");
+        assert_eq!(variants[0], ("GPIO_IRQ_LEVEL_LOW".to_string(), "1".to_string()));
+        assert_eq!(variants.len(), 4);
+        let (repr, variants) = find_rust_enum(unformatted, "pico_error_codes").unwrap();
+        assert_eq!(repr, "i32");
+        assert_eq!(variants, [("PICO_ERROR_NONE".to_string(), "0".to_string()), ("PICO_ERROR_GENERIC".to_string(), "-1".to_string())]);
+
+        let code = format!("{}\n{}\n", formatted, unformatted);
+        let conversions = create_enum_conversions(&code, &["gpio_irq_level".to_string(), "pico_error_codes".to_string()]).unwrap();
+        assert!(conversions.contains("impl From<gpio_irq_level> for u32"));
+        assert!(conversions.contains("            -1 => Ok(pico_error_codes::PICO_ERROR_GENERIC),\n"));
+        assert!(conversions.contains("            _ => Err(value),\n"));
+        let check = concat!(
+            "const _: () = assert!(8 == gpio_irq_level::GPIO_IRQ_EDGE_RISE as u32);\n",
+            "pub fn _check() -> (u32, Result<gpio_irq_level, u32>, Result<pico_error_codes, i32>) {\n",
+            "    use core::convert::TryFrom;\n",
+            "    (gpio_irq_level::GPIO_IRQ_EDGE_FALL.into(), gpio_irq_level::try_from(3), pico_error_codes::try_from(-1))\n",
+            "}\n",
+        );
+        compile_sys_crate("enum-conversions", &format!("{}{}{}", code, conversions, check), &[]).unwrap();
+        let error = create_enum_conversions(&code, &["gpio_function".to_string()]).unwrap_err();
+        assert!(error.contains("`gpio_function`, which is not an enum"));
+    }
+
+    #[test]
+    fn profiles_set_their_options_unless_the_environment_does() {
+        let vars = [
+            ("PICO_SDK_RS_PROFILE", Some("uart-release")),
+            ("PICO_SDK_RS_STDIO_USB", Some("1")),
+            ("PICO_SDK_RS_STDIO_UART", None),
+            ("PICO_SDK_RS_LTO", None),
+            ("PICO_SDK_RS_CMAKE_BUILD_TYPE", None),
+            ("PICO_SDK_RS_BINARY_TYPE", None),
+        ];
+        with_env(&vars, || {
+            assert_eq!(get_env("PICO_SDK_RS_CMAKE_BUILD_TYPE").as_deref(), Some("Release"));
+            assert!(get_env_flag("PICO_SDK_RS_LTO"));
+            assert!(get_env_flag("PICO_SDK_RS_STDIO_UART"));
+            assert!(get_env_flag("PICO_SDK_RS_STDIO_USB"));
+            assert_eq!(get_env("PICO_SDK_RS_BINARY_TYPE"), None);
+        });
+        with_env(&[("PICO_SDK_RS_PROFILE", None), ("PICO_SDK_RS_CMAKE_BUILD_TYPE", None)], || {
+            assert_eq!(get_env("PICO_SDK_RS_CMAKE_BUILD_TYPE"), None);
+        });
+    }
+
+    #[test]
+    fn inline_wrappers_are_written_to_their_own_header() {
+        let dir = temp_dir("inline-wrappers");
+        let irq = ("hardware/irq.h", "void irq_set_enabled(unsigned int num, _Bool enabled);\nstatic inline _Bool irq_is_valid(unsigned int num) { return num < 32; }\n");
+        let include_directory = write_fixture_sdk(&dir, &[irq]).display().to_string();
+        let vars = [("PICO_SDK_RS_INLINE_WRAPPERS", Some("1"))];
+        let (entry, _) = wrap_fixture_entry(&dir, &include_directory, &[], &vars);
+        assert!(entry.contains("extern inline void wrapped_irq_set_enabled(unsigned int num, _Bool enabled);\n"), "{}", entry);
+        assert!(!entry.contains("{ irq_set_enabled(num, enabled); }"));
+        assert!(entry.contains(&format!("#define {} inline\n#include \"{}\"\n", INLINE_WRAPPER_MACRO, INLINE_HEADER_NAME)));
+        let header = fs::read_to_string(dir.join(INLINE_HEADER_NAME)).unwrap();
+        assert!(header.starts_with(INLINE_HEADER_PROLOGUE) && header.ends_with(INLINE_HEADER_EPILOGUE));
+        assert!(header.contains("PICO_SDK_RS_WRAPPER_INLINE __attribute__((always_inline)) _Bool wrapped_irq_is_valid(unsigned int num) { return irq_is_valid(num); }"));
+        // `entry.c` defines the symbols Rust calls, and C code including the header gets its own copies.
+        let sources = [
+            ("sdk.c", "void irq_set_enabled(unsigned int num, _Bool enabled) { (void)num; (void)enabled; }\n"),
+            ("rust.c", "_Bool wrapped_irq_is_valid(unsigned int num);\nint call_from_rust(void) { return wrapped_irq_is_valid(4); }\n"),
+            ("user.c", "#include <hardware/irq.h>\n#include \"pico_sdk_rs_wrappers.h\"\nint call_from_rust(void);\nint main(void) { wrapped_irq_set_enabled(1, 1); return !(call_from_rust() && wrapped_irq_is_valid(4)); }\n"),
+        ];
+        assert_eq!(link_fixture_program(&dir, &include_directory, &sources), Ok(()));
+
+        let dir = temp_dir("no-inline-wrappers");
+        let include_directory = write_fixture_sdk(&dir, &[irq]).display().to_string();
+        let (entry, _) = wrap_fixture_entry(&dir, &include_directory, &[], &[("PICO_SDK_RS_INLINE_WRAPPERS", None)]);
+        assert!(entry.contains("void wrapped_irq_set_enabled(unsigned int num, _Bool enabled) { irq_set_enabled(num, enabled); }"));
+        assert!(!entry.contains(INLINE_HEADER_NAME) && !dir.join(INLINE_HEADER_NAME).exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn the_post_hook_edits_the_bindings_in_place() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("post-hook");
+        let (hook, bindings) = (dir.join("hook"), dir.join("bindings.rs"));
+        fs::write(&hook, "#!/bin/sh\necho 'pub const HOOKED: bool = true;' >> \"$1\"\n").unwrap();
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(&bindings, "pub type uint = u32;\n").unwrap();
+        assert_eq!(run_post_hook(&hook.display().to_string(), &bindings), Ok(()));
+        assert_eq!(fs::read_to_string(&bindings).unwrap(), "pub type uint = u32;\npub const HOOKED: bool = true;\n");
+
+        fs::write(&hook, "#!/bin/sh\nexit 3\n").unwrap();
+        let error = run_post_hook(&hook.display().to_string(), &bindings).unwrap_err();
+        assert!(error.contains("failed with exit status: 3"), "{}", error);
+    }
+
+    #[test]
+    fn abi_changes_are_reported_against_the_golden_file() {
+        let golden = temp_dir("abi-golden").join("abi.txt");
+        let golden = golden.display().to_string();
+        let signatures = |list: &[&str]| list.iter().map(|signature| signature.to_string()).collect::<Vec<_>>();
+        let error = check_abi_golden(&golden, signatures(&["void wrapped_tight_loop_contents(void)"]), false).unwrap_err();
+        assert!(error.contains("PICO_SDK_RS_ABI_GOLDEN_UPDATE=1 to create it"));
+
+        let current = ["uint wrapped_pwm_gpio_to_slice_num(uint)", "void wrapped_tight_loop_contents(void)"];
+        assert_eq!(check_abi_golden(&golden, signatures(&[current[1], current[0], current[1]]), true), Ok(()));
+        assert_eq!(fs::read_to_string(&golden).unwrap(), format!("{}\n{}\n", current[0], current[1]));
+        assert_eq!(check_abi_golden(&golden, signatures(&current), false), Ok(()));
+
+        let changed = signatures(&["uint wrapped_pwm_gpio_to_slice_num(uint, bool)", current[1]]);
+        let error = check_abi_golden(&golden, changed, false).unwrap_err();
+        assert!(error.ends_with("\n- uint wrapped_pwm_gpio_to_slice_num(uint)\n+ uint wrapped_pwm_gpio_to_slice_num(uint, bool)"), "{}", error);
+    }
+
+    #[test]
+    fn listed_handles_become_transparent_newtypes() {
+        let header = concat!(
+            "typedef int int32_t;\ntypedef int32_t alarm_id_t;\n",
+            "alarm_id_t wrapped_add_alarm_in_ms(unsigned int ms);\n_Bool wrapped_cancel_alarm(alarm_id_t alarm);\n",
+        );
+        let code = with_env(&[("PICO_SDK_RS_NEWTYPE_HANDLES", Some("alarm_id_t"))], || {
+            generate_fixture_bindings("newtype-handles", header, &[("add_alarm_in_ms", 1), ("cancel_alarm", 1)], &["int32_t", "alarm_id_t"])
+        });
+        assert!(code.contains("#[repr(transparent)]"));
+        assert!(code.contains("pub struct alarm_id_t(pub int32_t);"));
+        let check = "pub fn _check() -> bool { unsafe { wrapped_cancel_alarm(wrapped_add_alarm_in_ms(10)) } }\n";
+        compile_sys_crate("newtype-handles", &format!("{}{}", code, check), &[]).unwrap();
+        let mixed_up = "pub fn _check() -> bool { unsafe { wrapped_cancel_alarm(3) } }\n";
+        assert!(compile_sys_crate("newtype-handles-integer", &format!("{}{}", code, mixed_up), &[]).is_err());
+    }
+
+    #[test]
+    fn board_pins_are_declared_without_their_prefix() {
+        let code = concat!(
+            "pub const PICO_DEFAULT_LED_PIN: u32 = 25;\n",
+            "pub const PICO_DEFAULT_I2C_SDA_PIN: u32 = 4;\n",
+            "pub const PICO_DEFAULT_WS2812_PIN: i32 = -1;\n",
+            "pub const PICO_DEFAULT_UART_BAUD_RATE: u32 = 115200;\n",
+            "pub const PICO_DEFAULT_I2C: u32 = 0;\n",
+            "pub const SPI_RX_PIN: u32 = 16;\n",
+        );
+        // A pin defined by two headers is declared once, however it is spaced.
+        let pins = create_board_pins(&format!("{}pub const PICO_DEFAULT_I2C_SDA_PIN : u32 = 4 ;\n", code));
+        assert_eq!(
+            pins,
+            "\n/// Default pin assignments of the board header.\npub mod board {\n    pub const I2C_SDA_PIN: u8 = 4;\n    pub const LED_PIN: u8 = 25;\n}\n"
+        );
+        let check = "pub const _LED: u8 = board::LED_PIN;\n";
+        compile_sys_crate("board-pins", &format!("{}{}{}", code, pins, check), &[]).unwrap();
+    }
+
+    #[test]
+    fn memory_sizes_are_decimal_or_hexadecimal_multiples_of_eight() {
+        assert_eq!(parse_memory_size("PICO_SDK_RS_STACK_SIZE", "4096"), Ok(4096));
+        assert_eq!(parse_memory_size("PICO_SDK_RS_STACK_SIZE", "0x1000"), Ok(4096));
+        assert_eq!(parse_memory_size("PICO_SDK_RS_HEAP_SIZE", "0X10008"), Ok(0x10008));
+        for invalid in ["4100", "-8", "0x", "0x1_000", ""] {
+            let error = parse_memory_size("PICO_SDK_RS_HEAP_SIZE", invalid).unwrap_err();
+            assert!(error.contains(&format!("a multiple of 8, not `{}`", invalid)), "{}", error);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn memory_sizes_are_passed_to_the_sdk_build() {
+        let dir = temp_dir("memory-sizes");
+        let vars = [("PICO_SDK_RS_STACK_SIZE", Some("0x1000")), ("PICO_SDK_RS_HEAP_SIZE", Some("65536"))];
+        let (_, arguments) = configure_with_fake_cmake(&dir, &vars);
+        assert!(arguments.contains("-DPICO_STACK_SIZE=4096") && arguments.contains("-DPICO_HEAP_SIZE=65536"), "{}", arguments);
+    }
+
+    #[test]
+    fn firmware_link_args_combine_in_a_fixed_order() {
+        let out_dir = Path::new("/out");
+        let vars = [
+            ("PICO_SDK_RS_LTO", Some("1")),
+            ("CARGO_FEATURE_ALLOC", Some("1")),
+            ("PICO_SDK_RS_BOOT_STAGE2", Some("boot2_w25q080")),
+            ("PICO_SDK_RS_BOARDS", Some("pico:pico_w")),
+            ("PICO_SDK_RS_BOARD", None),
+            ("PICO_BOARD", Some("pico_w")),
+        ];
+        let archive = out_dir.join("boards/pico_w/build").join(BOOT_STAGE2_ARCHIVE).display().to_string();
+        let mut expected = vec![LTO_FLAG];
+        expected.extend(MALLOC_WRAP_FLAGS);
+        expected.extend(["-Wl,--whole-archive", archive.as_str(), "-Wl,--no-whole-archive"]);
+        with_env(&vars, || assert_eq!(firmware_link_args(out_dir), expected));
+    }
+
+    #[test]
+    fn the_wrapper_prefix_must_start_an_identifier() {
+        with_env(&[("PICO_SDK_RS_WRAPPER_PREFIX", None)], || assert_eq!(wrapper_prefix().as_deref(), Ok(RUST_WRAPPER_PREFIX)));
+        with_env(&[("PICO_SDK_RS_WRAPPER_PREFIX", Some("board2_"))], || assert_eq!(wrapper_prefix().as_deref(), Ok("board2_")));
+        for invalid in ["2nd_", "pico-", ""] {
+            let error = with_env(&[("PICO_SDK_RS_WRAPPER_PREFIX", Some(invalid))], wrapper_prefix).unwrap_err();
+            assert!(error.contains(&format!("`{}` does not start a valid C identifier", invalid)), "{}", error);
+        }
+    }
+
+    #[test]
+    fn metadata_lists_every_wrapper_with_its_symbol() {
+        let bindings = concat!(
+            "extern \"C\" {\n",
+            "    pub fn wrapped_gpio_put(gpio: uint, value: bool);\n",
+            "    pub fn wrapped_irq_set_exclusive_handler(num: uint, handler: irq_handler_t)\n        -> *mut cty::c_void;\n",
+            "}\n",
+        );
+        let metadata = create_metadata(bindings, "pico_");
+        assert!(metadata.starts_with(METADATA_PROLOGUE) && metadata.ends_with("];\n"));
+        let entries = &metadata[METADATA_PROLOGUE.len()..];
+        assert_eq!(
+            entries,
+            concat!(
+                "    WrappedFunction { name: \"wrapped_gpio_put\", symbol: \"pico_gpio_put\", ",
+                "parameters: &[(\"gpio\", \"uint\"), (\"value\", \"bool\")], return_type: None },\n",
+                "    WrappedFunction { name: \"wrapped_irq_set_exclusive_handler\", symbol: \"pico_irq_set_exclusive_handler\", ",
+                "parameters: &[(\"num\", \"uint\"), (\"handler\", \"irq_handler_t\")], return_type: Some(\"*mut cty::c_void\") },\n",
+                "];\n",
+            )
+        );
+        // The file stands on its own.
+        let path = temp_dir("metadata").join("metadata.rs");
+        fs::write(&path, &metadata).unwrap();
+        let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+        let output = Command::new(rustc)
+            .args(["--edition", "2018", "--crate-type", "lib", "--out-dir"])
+            .arg(path.parent().unwrap())
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    #[test]
+    fn functions_are_wrapped_if_allowed_and_not_blocked() {
+        let vars = [("PICO_SDK_RS_FUNCTION_ALLOWLIST", Some("gpio_.*:pwm_set_wrap")), ("PICO_SDK_RS_FUNCTION_BLOCKLIST", Some("gpio_.*_mask"))];
+        let filter = with_env(&vars, FunctionFilter::from_env).unwrap();
+        assert!(filter.is_wrapped("gpio_put"));
+        assert!(filter.is_wrapped("pwm_set_wrap"));
+        // Patterns match whole names.
+        assert!(!filter.is_wrapped("pwm_set_wrap_level") && !filter.is_wrapped("my_gpio_put"));
+        assert!(!filter.is_wrapped("gpio_init_mask"));
+        let vars = [("PICO_SDK_RS_FUNCTION_ALLOWLIST", None), ("PICO_SDK_RS_FUNCTION_BLOCKLIST", Some("sleep_.*"))];
+        let filter = with_env(&vars, FunctionFilter::from_env).unwrap();
+        assert!(filter.is_wrapped("gpio_put") && !filter.is_wrapped("sleep_ms"));
+        let vars = [("PICO_SDK_RS_FUNCTION_ALLOWLIST", Some("gpio_(")), ("PICO_SDK_RS_FUNCTION_BLOCKLIST", None)];
+        let error = with_env(&vars, FunctionFilter::from_env).err().unwrap();
+        assert!(error.contains("PICO_SDK_RS_FUNCTION_ALLOWLIST entry `gpio_(` is not a valid regular expression"));
+    }
+
+    #[test]
+    fn enum_rules_pair_a_pattern_with_a_style() {
+        let rules = ["gpio_irq_level=bitfield".to_string(), "dma_.*=rustified_non_exhaustive".to_string()];
+        assert_eq!(parse_enum_rules(&rules), Ok(vec![("gpio_irq_level", "bitfield"), ("dma_.*", "rustified_non_exhaustive")]));
+        // The style follows the last `=`, so patterns may contain one.
+        assert_eq!(parse_enum_rules(&["a{1,2}=b=newtype".to_string()]), Ok(vec![("a{1,2}=b", "newtype")]));
+        assert_eq!(parse_enum_rules(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn check_only_builds_nothing_but_the_configure() {
+        let dir = temp_dir("check-only");
+        fs::create_dir_all(dir.join("build/CMakeFiles/pico.dir")).unwrap();
+        fs::write(dir.join("build/CMakeFiles/pico.dir/DependInfo.cmake"), "").unwrap();
+        fs::write(dir.join("include_path"), "/sdk/common\n").unwrap();
+        fs::write(dir.join("definitions"), "PICO_RP2040=1\n").unwrap();
+        let builds = |check_only| {
+            let (result, arguments) = configure_with_fake_cmake(&dir, &[("PICO_SDK_RS_CHECK_ONLY", check_only)]);
+            assert!(result.is_ok());
+            let sdk_build = format!("--build {} ", dir.join("build").display());
+            arguments.lines().filter(|line| line.starts_with(&sdk_build)).map(|line| line[sdk_build.len()..].to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(builds(Some("1")), ["--target configure_only --config Debug"]);
+        assert_eq!(builds(None), ["--target configure_only --config Debug", "--config Debug"]);
+    }
+
+    #[test]
+    fn config_setters_are_chained_by_a_builder() {
+        // Token spacing of the unformatted bindgen output the builders are generated from.
+        let bindings = concat!(
+            "# [repr (C)] # [derive (Debug , Copy , Clone)] pub struct pwm_config { pub csr : u32 , pub div : u32 , pub top : u32 , }\n",
+            "# [repr (C)] # [derive (Debug , Copy , Clone)] pub struct uart_config { pub baud : u32 , }\n",
+            "extern \"C\" { pub fn wrapped_pwm_get_default_config () -> pwm_config ; }\n",
+            "extern \"C\" { pub fn wrapped_pwm_config_set_wrap (c : * mut pwm_config , wrap : u16) ; }\n",
+            "extern \"C\" { pub fn wrapped_pwm_config_set_phase_correct (c : * mut pwm_config , phase_correct : bool) ; }\n",
+            "extern \"C\" { pub fn wrapped_uart_config_set_type (c : * mut uart_config , value : u32) ; }\n",
+            "extern \"C\" { pub fn wrapped_pwm_config_get_wrap (c : * const pwm_config) -> u16 ; }\n",
+        );
+        let builders = create_config_builders(bindings);
+        assert!(builders.contains("pub struct PwmConfigBuilder"));
+        assert!(builders.contains("/// The SDK's default (`wrapped_pwm_get_default_config`)."));
+        assert!(builders.contains("    pub fn wrap(mut self, wrap: u16) -> Self {\n        unsafe { wrapped_pwm_config_set_wrap(&mut self.config, wrap) };\n"));
+        // Without a default getter the builder starts from zero, and keywords are escaped.
+        assert!(builders.contains("/// The zeroed struct.") && builders.contains("pub fn r#type(mut self, value: u32)"));
+        assert!(!builders.contains("get_wrap"));
+        let check = "pub fn _check() -> (pwm_config, uart_config) {\n    (PwmConfigBuilder::new().wrap(10).phase_correct(true).build(), UartConfigBuilder::new().r#type(1).build())\n}\n";
+        compile_sys_crate("config-builders", &format!("{}{}{}", bindings, builders, check), &[]).unwrap();
+    }
+
+    #[test]
+    fn invalid_options_are_reported_as_errors() {
+        assert_eq!(
+            check_one_of("PICO_SDK_RS_FLOAT_IMPL", "fast", &FLOAT_IMPLEMENTATIONS),
+            Err("PICO_SDK_RS_FLOAT_IMPL must be one of [\"pico\", \"compiler\", \"none\"], not `fast`".to_string())
+        );
+        assert_eq!(check_one_of("PICO_SDK_RS_BINARY_TYPE", "no_flash", &BINARY_TYPES), Ok(()));
+        assert!(create_entry_main("rust main").unwrap_err().contains("PICO_SDK_RS_ENTRY_MAIN_SYMBOL"));
+        assert!(parse_enum_rules(&["gpio_irq_level".to_string()]).unwrap_err().contains("<pattern>=<style>"));
+        assert!(parse_enum_rules(&["gpio_irq_level=enum".to_string()]).unwrap_err().contains("must be one of"));
+        assert!(parse_memory_size("PICO_SDK_RS_STACK_SIZE", "4k").unwrap_err().contains("PICO_SDK_RS_STACK_SIZE"));
+        assert!(check_lto_toolchain(&temp_dir("lto-without-cmake")).unwrap_err().contains("CMakeCache.txt"));
+        assert!(run_post_hook("/nonexistent/hook", Path::new("bindings.rs")).unwrap_err().contains("PICO_SDK_RS_POST_HOOK"));
+    }
+
+    #[test]
+    fn environment_options_are_reported_as_errors() {
+        with_env(&[("PICO_SDK_RS_PROFILE", Some("fast"))], || {
+            assert!(check_profile().unwrap_err().contains("unknown PICO_SDK_RS_PROFILE `fast`"));
+        });
+        with_env(&[("PICO_SDK_RS_PROFILE", Some("usb-debug"))], || assert_eq!(check_profile(), Ok(())));
+        with_env(&[("PICO_SDK_RS_LIBCLANG", Some("/nonexistent/libclang.so"))], || {
+            assert!(pin_libclang().unwrap_err().contains("/nonexistent/libclang.so"));
+        });
+        with_env(&[("PICO_SDK_RS_CUSTOM_ENTRY_POINT", Some("/nonexistent/entry.c"))], || {
+            let error = create_entry_point_file_to_out_dir(&temp_dir("missing-entry-point")).unwrap_err();
+            assert!(error.contains("PICO_SDK_RS_CUSTOM_ENTRY_POINT /nonexistent/entry.c"));
+        });
+    }
+
+    #[test]
+    fn unsatisfied_sdk_version_is_an_error() {
+        let dir = temp_dir("assert-sdk-version");
+        fs::create_dir_all(dir.join("pico")).unwrap();
+        fs::write(dir.join("pico/version.h"), "#define PICO_SDK_VERSION_STRING \"1.5.1\"\n").unwrap();
+        let include_directories = vec![dir.display().to_string()];
+        assert_eq!(assert_sdk_version(">=1.5", &include_directories), Ok(()));
+        assert!(assert_sdk_version(">=2", &include_directories).unwrap_err().contains("pico-sdk 1.5.1 does not satisfy"));
+        assert!(assert_sdk_version("~2", &include_directories).unwrap_err().contains("pico-sdk 1.5.1 does not satisfy"));
+        assert!(assert_sdk_version("~>2", &include_directories).unwrap_err().contains("invalid PICO_SDK_RS_ASSERT_SDK_VERSION"));
+        assert!(assert_sdk_version(">=2", &Vec::new()).unwrap_err().contains("could not be detected"));
+    }
+
+    #[test]
+    fn build_manifest_names_the_sdk_the_board_and_the_wrapper_count() {
+        assert_eq!(create_build_manifest(Some("2.0.0"), Some("pico_w"), 312), "pico-sdk=2.0.0 board=pico_w wrappers=312");
+        assert_eq!(create_build_manifest(None, None, 0), "pico-sdk=unknown board=unknown wrappers=0");
+    }
+
+    #[test]
+    fn strict_bindings_keep_the_c_names_and_compile_without_warnings() {
+        let code = with_env(&[("PICO_SDK_RS_WARNINGS_AS_ERRORS", Some("1"))], || {
+            generate_fixture_bindings(
+                "strict-bindings",
+                FIXTURE_HEADER,
+                &[("pwm_config_get_wrap", 1), ("gpio_set_function", 2)],
+                &["uint", "pwm_config", "gpio_function"],
+            )
+        });
+        assert!(code.contains("pub struct pwm_config"));
+        assert!(code.contains("pub fn wrapped_gpio_set_function"));
+        assert!(!code.contains("fn bindgen_test_layout_"));
+        compile_sys_crate("strict-bindings", &code, &["pico_sdk_strict_bindings"]).unwrap();
+    }
+
+    #[test]
+    fn module_name_wraps_the_bindings_and_re_exports_them() {
+        let code = "/// Summary of the SDK this crate was generated from.\npub static BUILD_MANIFEST: &str = \"\";\n";
+        let wrapped = wrap_in_module(code, "pico");
+        assert!(wrapped.starts_with("pub mod pico {\n/// Summary"));
+        assert!(wrapped.ends_with("}\npub use pico::*;\n"));
+        let uses = "pub static ROOT_MANIFEST: &&str = &crate::BUILD_MANIFEST;\npub static MODULE_MANIFEST: &&str = &crate::pico::BUILD_MANIFEST;\n";
+        compile_sys_crate("module-name", &(wrapped + uses), &[]).unwrap();
+    }
+
+    #[test]
+    fn the_sdk_configuration_is_declared_without_an_allowlist() {
+        let header = "#define PICO_DEFAULT_LED_PIN 25\n#define PICO_FLASH_SIZE_BYTES 2097152\n#define UART0_BASE 0x40034000\n#define XOSC_MHZ 12\n";
+        let code = with_env(&[("PICO_SDK_RS_ALLOWLIST_VARS", None)], || generate_fixture_bindings("default-vars", header, &[], &[]));
+        assert!(code.contains("pub const PICO_DEFAULT_LED_PIN: u32 = 25;"));
+        assert!(code.contains("pub const PICO_FLASH_SIZE_BYTES: u32 = 2097152;"));
+        assert!(code.contains("pub const UART0_BASE: u32 = 1074479104;"));
+        assert!(!code.contains("XOSC_MHZ"));
+        let code = with_env(&[("PICO_SDK_RS_ALLOWLIST_VARS", Some("XOSC_.*"))], || generate_fixture_bindings("allowed-vars", header, &[], &[]));
+        assert!(code.contains("pub const PICO_DEFAULT_LED_PIN: u32 = 25;") && code.contains("pub const XOSC_MHZ: u32 = 12;"));
+        assert!(!code.contains("PICO_FLASH_SIZE_BYTES") && !code.contains("UART0_BASE"));
+    }
+
+    #[test]
+    fn unsigned_base_addresses_become_constants() {
+        let headers = [
+            ("pico/stdlib.h", "#include <stdint.h>\n#include <hardware/regs/addressmap.h>\n"),
+            ("hardware/regs/addressmap.h", "#define _u(x) x ## u\n#define UART0_BASE _u(0x40034000)\n#define WATCHDOG_BASE _u(0x40058000)\n#define XIP_BASE 0x10000000\n#define XOSC_MHZ _u(12)\n"),
+        ];
+        let dir = temp_dir("unsigned-bases");
+        let include_directory = write_fixture_sdk(&dir, &headers).display().to_string();
+        let (entry, _) = wrap_fixture_entry(&dir, &include_directory, &[], &[("PICO_SDK_RS_ALLOWLIST_VARS", None)]);
+        assert!(entry.contains(&format!("static const uint32_t {}UART0_BASE __attribute__((unused)) = UART0_BASE;", CHIP_CONSTANT_PREFIX)), "{}", entry);
+        // bindgen declares `XIP_BASE` itself, and `WATCHDOG_BASE` is one of the chip constants.
+        assert!(!entry.contains("XIP_BASE;") && !entry.contains("WATCHDOG_BASE;") && !entry.contains("XOSC_MHZ"));
+        assert_eq!(check_fixture_entry("unsigned-bases-cc", &headers, &entry), Ok(()));
+    }
+
+    #[test]
+    fn chip_constants_are_declared_under_their_sdk_names() {
+        let header = format!(
+            "typedef unsigned int uint32_t;\n#define _u(x) x ## u\n#define NUM_DMA_CHANNELS _u(12)\n#define WATCHDOG_BASE _u(0x40058000)\n{}",
+            create_chip_constants()
+        );
+        let code = with_env(&[], || generate_fixture_bindings("chip-constants", &header, &[], &[]));
+        assert!(code.contains("pub const NUM_DMA_CHANNELS: uint32_t = 12;"));
+        assert!(code.contains("pub const WATCHDOG_BASE: uint32_t = 1074102272;"));
+        assert!(!code.contains("NUM_PIOS") && !code.contains(CHIP_CONSTANT_PREFIX));
+    }
+
+    #[test]
+    fn chip_is_taken_from_the_sdk_definitions() {
+        let definitions = |definitions: &[&str]| definitions.iter().map(|definition| definition.to_string()).collect::<Vec<_>>();
+        with_env(&[("PICO_PLATFORM", None)], || {
+            assert_eq!(get_chip(&definitions(&["PICO_RP2040=0", "PICO_RP2350=1"]), "thumbv6m-none-eabi"), "rp2350");
+            assert_eq!(get_chip(&definitions(&["PICO_RP2040=1"]), "x86_64-unknown-linux-gnu"), "rp2040");
+            assert_eq!(get_chip(&Vec::new(), "thumbv8m.main-none-eabihf"), "rp2350");
+            assert_eq!(get_chip(&Vec::new(), "x86_64-unknown-linux-gnu"), "rp2040");
+        });
+        with_env(&[("PICO_PLATFORM", Some("rp2350-riscv"))], || assert_eq!(get_chip(&Vec::new(), "x86_64-unknown-linux-gnu"), "rp2350"));
+    }
+
+    #[test]
+    fn powman_is_wrapped_for_the_rp2350() {
+        let powman = ("hardware/powman.h", "void powman_timer_start(void);\nunsigned long long powman_timer_get_ms(void);\n");
+        let (entry, wrapped_functions) = wrap_fixture_sdk("powman-rp2350", &[powman], &["PICO_RP2350=1", "PICO_SDK_RS_WRAP_POWMAN=1"]);
+        assert!(wrapped_functions.contains(&("powman_timer_start".to_string(), 0)));
+        assert!(wrapped_functions.contains(&("powman_timer_get_ms".to_string(), 0)));
+        assert!(entry.contains("wrapped_powman_timer_get_ms(void)"));
+        let (_, wrapped_functions) = wrap_fixture_sdk("powman-rp2040", &[powman], &["PICO_RP2040=1"]);
+        assert!(wrapped_functions.is_empty());
+    }
+
+    #[test]
+    fn the_heap_links_through_the_malloc_wrappers() {
+        let out_dir = Path::new("/out");
+        with_env(&[("PICO_SDK_RS_LTO", None), ("PICO_SDK_RS_BOOT_STAGE2", None), ("CARGO_FEATURE_ALLOC", None)], || {
+            assert!(firmware_link_args(out_dir).is_empty());
+        });
+        with_env(&[("PICO_SDK_RS_LTO", None), ("PICO_SDK_RS_BOOT_STAGE2", None), ("CARGO_FEATURE_ALLOC", Some("1"))], || {
+            assert_eq!(firmware_link_args(out_dir), MALLOC_WRAP_FLAGS);
+        });
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn boot_stage2_reaches_the_sdk_build_and_the_link() {
+        let dir = temp_dir("boot-stage2");
+        let vars = [
+            ("PICO_SDK_RS_BOOT_STAGE2", Some("boot2_w25q080")),
+            ("PICO_SDK_RS_BOARDS", None),
+            ("PICO_SDK_RS_LTO", None),
+            ("CARGO_FEATURE_ALLOC", None),
+        ];
+        // The fake cmake configures nothing, so reading its output fails.
+        let (result, arguments) = configure_with_fake_cmake(&dir, &vars);
+        let error = result.unwrap_err();
+        assert!(error.contains("DependInfo.cmake"), "{}", error);
+        assert!(arguments.contains("-DPICO_DEFAULT_BOOT_STAGE2=boot2_w25q080"), "{}", arguments);
+        with_env(&vars, || {
+            let archive = dir.join("build").join(BOOT_STAGE2_ARCHIVE).display().to_string();
+            assert_eq!(firmware_link_args(&dir)[..], ["-Wl,--whole-archive", archive.as_str(), "-Wl,--no-whole-archive"]);
+        });
+    }
+
+    #[test]
+    fn the_cache_hash_is_fnv_1a() {
+        use std::hash::Hasher;
+
+        let hash = |bytes: &[u8]| {
+            let mut hasher = Fnv1a::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn the_cache_key_follows_the_sdk_and_the_include_priority_headers() {
+        let (sdk, shims) = (temp_dir("cache-key-sdk"), temp_dir("cache-key-shims"));
+        fs::create_dir_all(sdk.join("src/.git")).unwrap();
+        fs::write(sdk.join("src/gpio.h"), "void gpio_init(unsigned gpio);\n").unwrap();
+        fs::write(shims.join("lwipopts.h"), "#define NO_SYS 1\n").unwrap();
+        let (sdk_path, shims_path) = (sdk.display().to_string(), shims.display().to_string());
+        let vars = [("PICO_SDK_PATH", Some(sdk_path.as_str())), ("PICO_SDK_RS_INCLUDE_PRIORITY", Some(shims_path.as_str()))];
+        with_env(&vars, || {
+            let key = binding_cache_key();
+            assert_eq!(binding_cache_key(), key);
+            // Neither a file outside the sources nor one under version control counts.
+            fs::write(sdk.join("README.md"), "changed").unwrap();
+            fs::write(sdk.join("src/.git/index"), "changed").unwrap();
+            assert_eq!(binding_cache_key(), key);
+            fs::write(sdk.join("src/gpio.h"), "void gpio_init(unsigned int gpio);\n").unwrap();
+            let sdk_changed = binding_cache_key();
+            assert_ne!(sdk_changed, key);
+            fs::write(shims.join("lwipopts.h"), "#define NO_SYS 0\n\n").unwrap();
+            assert_ne!(binding_cache_key(), sdk_changed);
+        });
+        assert_eq!(source_tree_files(&sdk), [sdk.join("src/gpio.h")]);
+    }
+
+    #[test]
+    fn only_the_named_variables_are_part_of_the_cache_key() {
+        let target = "thumbv6m-none-eabi";
+        for name in ["CC", "CC_thumbv6m_none_eabi", "CC_thumbv6m-none-eabi", "TARGET", "PICO_BOARD", "CARGO_FEATURE_HOST_STUBS"] {
+            assert!(is_cache_key_variable(name, target), "{}", name);
+        }
+        for name in ["CCACHE_DIR", "ARCH", "HOSTNAME", "TARGET_DIR", "CC_x86_64_unknown_linux_gnu", "CARGO_MAKEFLAGS"] {
+            assert!(!is_cache_key_variable(name, target), "{}", name);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn the_cache_key_follows_the_version_of_gcc() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let gcc = temp_dir("cache-key-gcc").join("gcc");
+        let write_gcc = |version: &str| {
+            fs::write(&gcc, format!("#!/bin/sh\necho 'gcc (GCC) {}'\n", version)).unwrap();
+            fs::set_permissions(&gcc, fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        write_gcc("13.2.0");
+        let gcc_path = gcc.display().to_string();
+        let key = |ccache_dir: Option<&str>| {
+            let vars = [("TARGET", Some("x86_64-unknown-linux-gnu")), ("PICO_SDK_RS_HOST_GCC", Some(gcc_path.as_str())), ("CCACHE_DIR", ccache_dir)];
+            with_env(&vars, binding_cache_key)
+        };
+        let unchanged = key(None);
+        assert_eq!(key(Some("/tmp/ccache")), unchanged);
+        write_gcc("14.1.0");
+        assert_ne!(key(None), unchanged);
+        assert_eq!(find_program(&gcc_path), Some(gcc.clone()));
+        assert_eq!(find_program("surely-not-a-program-on-path"), None);
+    }
+
+    #[test]
+    fn cached_builds_repeat_the_recorded_directives() {
+        let out_dir = temp_dir("cache-replay");
+        with_env(&[("PICO_SDK_RS_NO_CACHE", None)], || {
+            assert!(!replay_cached_build(&out_dir, "key"));
+            fs::write(out_dir.join("bindings.rs"), "").unwrap();
+            fs::write(out_dir.join(CACHE_KEY_FILE), "key").unwrap();
+            assert!(!replay_cached_build(&out_dir, "key"), "replayed without directives");
+            fs::write(out_dir.join(CACHE_KEY_FILE), "key").unwrap();
+            fs::write(out_dir.join(CACHE_DIRECTIVES_FILE), "cargo:board=pico\n").unwrap();
+            assert!(!replay_cached_build(&out_dir, "other key"));
+            assert!(!out_dir.join(CACHE_KEY_FILE).exists());
+            fs::write(out_dir.join(CACHE_KEY_FILE), "key").unwrap();
+            assert!(replay_cached_build(&out_dir, "key"));
+            assert_eq!(fs::read_to_string(out_dir.join(CACHE_KEY_FILE)).unwrap(), "key");
+        });
+        with_env(&[("PICO_SDK_RS_NO_CACHE", Some("1"))], || assert!(!replay_cached_build(&out_dir, "key")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn the_output_of_the_programs_the_pipeline_runs_is_recorded() {
+        let path = temp_dir("captured-stdout").join(CAPTURED_STDOUT_FILE);
+        let (status, output) = capture_stdout(&path, || Command::new("echo").arg("cargo:rustc-link-lib=pico").status().unwrap());
+        assert!(status.success());
+        assert_eq!(output.as_deref(), Some("cargo:rustc-link-lib=pico\n"));
+    }
+
+    #[test]
+    fn callback_parameters_are_declared_around_their_name() {
+        let irq = (
+            "hardware/irq.h",
+            concat!(
+                "typedef void (*irq_handler_t)(void);\n",
+                "void irq_set_alarm_callback(unsigned int alarm, void (*callback)(unsigned int alarm));\n",
+                "void irq_set_handler(unsigned int num, irq_handler_t handler);\n",
+                "void (*irq_get_handler(unsigned int num))(void);\n",
+            ),
+        );
+        let (entry, wrapped_functions) = wrap_fixture_sdk("callback-parameters", &[irq], &[]);
+        for function in ["irq_set_alarm_callback", "irq_set_handler", "irq_get_handler"] {
+            assert!(wrapped_functions.iter().any(|(name, _)| name == function), "{} is not wrapped", function);
+        }
+        assert!(entry.contains("void wrapped_irq_set_alarm_callback(unsigned int alarm, void (*callback)(unsigned int))"));
+        assert!(entry.contains("irq_set_alarm_callback(alarm, callback);"));
+        assert!(entry.contains("void wrapped_irq_set_handler(unsigned int num, irq_handler_t handler)"));
+        assert!(entry.contains("void (*wrapped_irq_get_handler(unsigned int num))(void)"));
+        assert!(entry.contains("return irq_get_handler(num);"));
+        assert_eq!(check_fixture_entry("callback-parameters-cc", &[irq], &entry), Ok(()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn include_directories_are_compared_canonicalized() {
+        let dir = temp_dir("symlinked-include");
+        let irq = ("hardware/irq.h", "void irq_set_enabled(unsigned int num, _Bool enabled);\n");
+        write_fixture_sdk(&dir.join("sdk"), &[irq]);
+        std::os::unix::fs::symlink(dir.join("sdk"), dir.join("link")).unwrap();
+        let linked = dir.join("link/include").display().to_string();
+        assert_eq!(canonicalize_or_keep(&linked), fs::canonicalize(dir.join("sdk/include")).unwrap());
+        assert_eq!(canonicalize_or_keep("/nonexistent/include"), PathBuf::from("/nonexistent/include"));
+        for include_directory in [linked.clone(), format!("{}/../include", linked)] {
+            let (entry, wrapped_functions) = wrap_fixture_entry(&dir, &include_directory, &[], &[]);
+            assert!(wrapped_functions.iter().any(|(name, _)| name == "irq_set_enabled"), "nothing wrapped through {}", include_directory);
+            assert!(entry.contains("void wrapped_irq_set_enabled(unsigned int num, _Bool enabled)"));
+        }
+    }
+
+    #[test]
+    fn wrappers_keep_every_qualifier_of_their_types() {
+        let spi = (
+            "hardware/spi.h",
+            concat!(
+                "typedef unsigned char uint8_t;\n",
+                "void spi_write(const uint8_t *const src, unsigned int len);\n",
+                "void spi_copy(uint8_t *restrict dst, const uint8_t *restrict src);\n",
+                "const char *const *spi_names(void);\n",
+                "void spi_fill(const char names[8], volatile unsigned int *status);\n",
+                "void spi_set_handler(void (*handler)(const uint8_t *data));\n",
+            ),
+        );
+        let (entry, _) = wrap_fixture_sdk("qualified-types", &[spi], &[]);
+        // Only the qualifiers matter, not how the `*`s are spaced.
+        let squeezed = entry.replace("* ", "*").replace(" *", "*");
+        for declaration in [
+            "void wrapped_spi_write(const uint8_t*const src, unsigned int len)",
+            "void wrapped_spi_copy(uint8_t*restrict dst, const uint8_t*restrict src)",
+            "const char*const*wrapped_spi_names(void)",
+            "void wrapped_spi_fill(const char names[8], volatile unsigned int*status)",
+            "void wrapped_spi_set_handler(void (*handler)(const uint8_t*))",
+        ] {
+            assert!(squeezed.contains(declaration), "{} is not declared in\n{}", declaration, entry);
+        }
+        assert_eq!(check_fixture_entry("qualified-types-cc", &[spi], &entry), Ok(()));
+    }
 }
@@ -1 +1,61 @@
 #include <pico/stdlib.h>
+
+// The libraries besides pico_stdlib can be chosen with PICO_SDK_RS_LINK_LIBRARIES, and only
+// the headers of linked ones are on the include path.
+#if __has_include(<pico/i2c_slave.h>)
+#include <pico/i2c_slave.h>
+#endif
+#if __has_include(<hardware/adc.h>)
+#include <hardware/adc.h>
+#endif
+#if __has_include(<hardware/clocks.h>)
+#include <hardware/clocks.h>
+#endif
+#if __has_include(<hardware/dma.h>)
+#include <hardware/dma.h>
+#endif
+#if __has_include(<hardware/i2c.h>)
+#include <hardware/i2c.h>
+#endif
+#if __has_include(<hardware/irq.h>)
+#include <hardware/irq.h>
+#endif
+#if __has_include(<hardware/pio.h>)
+#include <hardware/pio.h>
+#endif
+#if __has_include(<hardware/pwm.h>)
+#include <hardware/pwm.h>
+#endif
+#if __has_include(<hardware/rtc.h>)
+#include <hardware/rtc.h>
+#endif
+#if __has_include(<hardware/spi.h>)
+#include <hardware/spi.h>
+#endif
+#if __has_include(<hardware/sync.h>)
+#include <hardware/sync.h>
+#endif
+#if __has_include(<hardware/uart.h>)
+#include <hardware/uart.h>
+#endif
+#if __has_include(<hardware/watchdog.h>)
+#include <hardware/watchdog.h>
+#endif
+
+#if PICO_SDK_RS_WRAP_FLOAT
+#include <pico/float.h>
+#include <pico/double.h>
+#endif
+#if PICO_SDK_RS_WRAP_ASYNC_CONTEXT
+#include <pico/async_context_poll.h>
+#endif
+#if PICO_SDK_RS_WRAP_POWMAN
+#include <hardware/powman.h>
+#endif
+#if PICO_SDK_RS_WRAP_CYW43
+#include <pico/cyw43_arch.h>
+#endif
+#if PICO_SDK_RS_WRAP_SLEEP
+#include <pico/sleep.h>
+#include <hardware/rosc.h>
+#endif
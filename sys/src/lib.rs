@@ -1,6 +1,26 @@
 #![no_std]
-#![allow(non_upper_case_globals)]
-#![allow(non_camel_case_types)]
-#![allow(non_snake_case)]
+#![cfg_attr(not(pico_sdk_strict_bindings), allow(non_upper_case_globals))]
+#![cfg_attr(not(pico_sdk_strict_bindings), allow(non_camel_case_types))]
+#![cfg_attr(not(pico_sdk_strict_bindings), allow(non_snake_case))]
+#![cfg_attr(pico_sdk_strict_bindings, deny(warnings))]
 
+#[cfg(not(pico_sdk_strict_bindings))]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+/// With `PICO_SDK_RS_WARNINGS_AS_ERRORS`, only the generated bindings may use the C names of the
+/// SDK; every other warning is an error.
+#[cfg(pico_sdk_strict_bindings)]
+#[allow(non_upper_case_globals, non_camel_case_types, non_snake_case)]
+mod bindings {
+    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
+
+#[cfg(pico_sdk_strict_bindings)]
+pub use bindings::*;
+
+/// Safe versions of the wrappers that only take and return scalars, generated with
+/// `PICO_SDK_RS_SAFE_WRAPPERS`.
+#[cfg(pico_sdk_safe_wrappers)]
+pub mod safe {
+    include!(concat!(env!("OUT_DIR"), "/safe.rs"));
+}
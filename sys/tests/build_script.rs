@@ -0,0 +1,2 @@
+//! The unit tests of the build script, which cargo does not build as a test target itself.
+include!("../build.rs");